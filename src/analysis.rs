@@ -0,0 +1,353 @@
+// Static analysis of `include` and `AddCSLuaFile` calls
+
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::lexer::{Lexer, Token, TokenKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallKind {
+	Include,
+	AddCSLuaFile
+}
+
+/// Which realm(s) a call can run in, judging by the `if SERVER then`/`if CLIENT then` blocks around it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RealmFilter {
+	Any,
+	Server,
+	Client,
+	Never
+}
+impl RealmFilter {
+	fn and(self, other: RealmFilter) -> RealmFilter {
+		match (self, other) {
+			(RealmFilter::Any, other) | (other, RealmFilter::Any) => other,
+			(a, b) if a == b => a,
+			_ => RealmFilter::Never
+		}
+	}
+
+	fn invert(self) -> RealmFilter {
+		match self {
+			RealmFilter::Server => RealmFilter::Client,
+			RealmFilter::Client => RealmFilter::Server,
+			other => other
+		}
+	}
+
+	#[inline]
+	pub fn server(self) -> bool {
+		matches!(self, RealmFilter::Any | RealmFilter::Server)
+	}
+
+	#[inline]
+	pub fn client(self) -> bool {
+		matches!(self, RealmFilter::Any | RealmFilter::Client)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallArg {
+	/// `AddCSLuaFile()`
+	None,
+
+	/// `include("path/to/file.lua")`
+	Literal(String),
+
+	/// The argument isn't a string literal, so we can't follow it
+	Dynamic
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LuaCall {
+	pub kind: CallKind,
+	pub arg: CallArg,
+	pub realm: RealmFilter,
+	pub line: usize
+}
+
+/// Finds every `include` and `AddCSLuaFile` call in a Lua file.
+///
+/// Scanning stops at the first syntax error the lexer encounters.
+pub fn scan_calls(src: &[u8]) -> Vec<LuaCall> {
+	let tokens = Lexer::new(src).map_while(Result::ok).collect::<Vec<Token>>();
+
+	struct Block {
+		realm: RealmFilter,
+
+		/// The realm of the condition of the current `if`/`elseif` branch, so that `else` can invert it
+		condition: RealmFilter,
+
+		/// `repeat` blocks are closed by `until`, everything else by `end`
+		repeat: bool
+	}
+
+	/// Works out the realm a condition such as `SERVER`, `not CLIENT` or `!SERVER` restricts to
+	fn condition_realm(condition: &[Token]) -> RealmFilter {
+		let (negated, condition) = match condition {
+			[not, rest @ ..] if not.is("not") || not.is("!") => (true, rest),
+			_ => (false, condition)
+		};
+		let realm = match condition {
+			[realm] if realm.is("SERVER") => RealmFilter::Server,
+			[realm] if realm.is("CLIENT") => RealmFilter::Client,
+			_ => return RealmFilter::Any
+		};
+		if negated { realm.invert() } else { realm }
+	}
+
+	let mut calls = vec![];
+	let mut blocks: Vec<Block> = vec![];
+
+	// The realm of the file's top level, narrowed by `if CLIENT then return end` and the like
+	let mut chunk_realm = RealmFilter::Any;
+	let current_realm = |chunk_realm: RealmFilter, blocks: &[Block]| blocks.iter().fold(chunk_realm, |realm, block| realm.and(block.realm));
+
+	let mut i = 0;
+	while i < tokens.len() {
+		let token = &tokens[i];
+		match token.kind {
+			TokenKind::Keyword => match token.text {
+				b"if" | b"elseif" => {
+					let then = tokens[i..].iter().position(|token| token.is("then")).map(|then| i + then).unwrap_or(tokens.len());
+					let condition = condition_realm(&tokens[i + 1..then]);
+
+					// `if CLIENT then return end` means the rest of the block only runs on the server
+					let guard_end = match (tokens.get(then + 1), tokens.get(then + 2), tokens.get(then + 3)) {
+						(Some(ret), Some(end), _) if ret.is("return") && end.is("end") => Some(then + 2),
+						(Some(ret), Some(semicolon), Some(end)) if ret.is("return") && semicolon.is(";") && end.is("end") => Some(then + 3),
+						_ => None
+					};
+					if let (true, Some(guard_end)) = (token.is("if"), guard_end) {
+						match blocks.last_mut() {
+							Some(block) => block.realm = block.realm.and(condition.invert()),
+							None => chunk_realm = chunk_realm.and(condition.invert())
+						}
+						i = guard_end + 1;
+						continue;
+					}
+
+					if token.is("if") {
+						blocks.push(Block { realm: condition, condition, repeat: false });
+					} else if let Some(block) = blocks.last_mut() {
+						block.realm = condition;
+						block.condition = condition;
+					}
+					i = then;
+				},
+				b"else" => if let Some(block) = blocks.last_mut() {
+					block.realm = block.condition.invert();
+				},
+				b"do" | b"function" => blocks.push(Block { realm: RealmFilter::Any, condition: RealmFilter::Any, repeat: false }),
+				b"repeat" => blocks.push(Block { realm: RealmFilter::Any, condition: RealmFilter::Any, repeat: true }),
+				b"end" if blocks.last().map(|block| !block.repeat).unwrap_or(false) => {
+					blocks.pop();
+				},
+				b"until" if blocks.last().map(|block| block.repeat).unwrap_or(false) => {
+					blocks.pop();
+				},
+				_ => {}
+			},
+
+			TokenKind::Name if token.is("include") || token.is("AddCSLuaFile") => {
+				// Skip method calls and fields (`foo.include`, `foo:include`), but not `_G.include`
+				let is_field = i > 0 && (tokens[i - 1].is(".") || tokens[i - 1].is(":")) && !(i > 1 && tokens[i - 2].is("_G") && tokens[i - 1].is("."));
+				if !is_field {
+					let kind = if token.is("include") { CallKind::Include } else { CallKind::AddCSLuaFile };
+					let arg = match (tokens.get(i + 1), tokens.get(i + 2), tokens.get(i + 3)) {
+						(Some(open), Some(close), _) if open.is("(") && close.is(")") => Some(CallArg::None),
+						(Some(open), Some(string), Some(close)) if open.is("(") && close.is(")") && matches!(string.kind, TokenKind::String | TokenKind::LongString) => {
+							string.string_value().map(|path| CallArg::Literal(String::from_utf8_lossy(&path).into_owned()))
+						},
+						(Some(string), _, _) if matches!(string.kind, TokenKind::String | TokenKind::LongString) => {
+							string.string_value().map(|path| CallArg::Literal(String::from_utf8_lossy(&path).into_owned()))
+						},
+						(Some(open), _, _) if open.is("(") => Some(CallArg::Dynamic),
+						_ => None
+					};
+					if let Some(arg) = arg {
+						calls.push(LuaCall {
+							kind,
+							arg,
+							realm: current_realm(chunk_realm, &blocks),
+							line: token.line
+						});
+					}
+				}
+			},
+
+			_ => {}
+		}
+		i += 1;
+	}
+
+	calls
+}
+
+/// Normalizes `.` and `..` components of a `/`-separated path.
+fn normalize(path: &str) -> Option<String> {
+	let mut components: Vec<&str> = vec![];
+	for component in path.split(['/', '\\']) {
+		match component {
+			"" | "." => {},
+			".." => {
+				components.pop()?;
+			},
+			component => components.push(component)
+		}
+	}
+	Some(components.join("/"))
+}
+
+/// A resolved `include`/`AddCSLuaFile` call from one Lua file to another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edge {
+	pub kind: CallKind,
+	pub target: String,
+	pub realm: RealmFilter,
+	pub line: usize
+}
+
+/// A call whose argument couldn't be resolved to a Lua file in the addon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnresolvedCall {
+	pub file: String,
+	pub line: usize,
+	pub kind: CallKind,
+
+	/// `None` if the argument isn't a string literal
+	pub arg: Option<String>
+}
+
+/// The `include`/`AddCSLuaFile` graph of an addon's Lua files.
+#[derive(Debug, Clone, Default)]
+pub struct IncludeGraph {
+	pub edges: BTreeMap<String, Vec<Edge>>,
+	pub unresolved: Vec<UnresolvedCall>
+}
+impl IncludeGraph {
+	/// Builds the graph from Lua files (paths relative to `lua/`) and their contents.
+	///
+	/// Like Garry's Mod, paths are resolved relative to the calling file first, then relative to `lua/`.
+	pub fn build<'a, I: IntoIterator<Item = (&'a str, &'a [u8])>>(files: I) -> IncludeGraph {
+		let files = files.into_iter().collect::<BTreeMap<&str, &[u8]>>();
+
+		let resolve = |from: &str, path: &str| -> Option<String> {
+			let dir = from.rfind('/').map(|slash| &from[..slash]).unwrap_or("");
+			[format!("{}/{}", dir, path), path.to_string()].iter()
+				.filter_map(|candidate| normalize(candidate))
+				.find(|candidate| files.contains_key(candidate.as_str()))
+		};
+
+		let mut graph = IncludeGraph::default();
+		for (path, contents) in files.iter() {
+			let mut edges = vec![];
+			for call in scan_calls(contents) {
+				let target = match &call.arg {
+					CallArg::None => Some(path.to_string()),
+					CallArg::Literal(arg) => resolve(path, arg),
+					CallArg::Dynamic => None
+				};
+				match target {
+					Some(target) => edges.push(Edge {
+						kind: call.kind,
+						target,
+						realm: call.realm,
+						line: call.line
+					}),
+					None => graph.unresolved.push(UnresolvedCall {
+						file: path.to_string(),
+						line: call.line,
+						kind: call.kind,
+						arg: match call.arg {
+							CallArg::Literal(arg) => Some(arg),
+							_ => None
+						}
+					})
+				}
+			}
+			graph.edges.insert(path.to_string(), edges);
+		}
+		graph
+	}
+
+	/// Works out where each file runs, starting from the entry files of each realm.
+	pub fn propagate(&self, sv_entry_files: &[String], cl_entry_files: &[String], sh_entry_files: &[String]) -> BTreeMap<String, FileRealms> {
+		let mut realms: BTreeMap<String, FileRealms> = BTreeMap::new();
+		let mut queue = VecDeque::new();
+
+		let mut seed = |files: &[String], seeded: FileRealms| {
+			for file in files {
+				let realms = realms.entry(file.clone()).or_default();
+				if realms.merge(seeded) {
+					queue.push_back(file.clone());
+				}
+			}
+		};
+		seed(sv_entry_files, FileRealms { server: true, client: false, sent: false });
+		seed(cl_entry_files, FileRealms { server: false, client: true, sent: true });
+		seed(sh_entry_files, FileRealms { server: true, client: true, sent: true });
+
+		while let Some(file) = queue.pop_front() {
+			let file_realms = realms[&file];
+			for edge in self.edges.get(&file).map(|edges| edges.as_slice()).unwrap_or_default() {
+				let server = file_realms.server && edge.realm.server();
+				let client = file_realms.client && edge.realm.client();
+				let reached = match edge.kind {
+					CallKind::Include => FileRealms { server, client, sent: false },
+					// AddCSLuaFile only does something on the server
+					CallKind::AddCSLuaFile => FileRealms { server: false, client: false, sent: server }
+				};
+				if realms.entry(edge.target.clone()).or_default().merge(reached) {
+					queue.push_back(edge.target.clone());
+				}
+			}
+		}
+
+		realms.retain(|_, realms| realms.reached());
+		realms
+	}
+}
+
+/// Where a Lua file runs, according to [`IncludeGraph::propagate`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileRealms {
+	/// The file is included on the server
+	pub server: bool,
+
+	/// The file is included on the client
+	pub client: bool,
+
+	/// The file is sent to the client with `AddCSLuaFile`
+	pub sent: bool
+}
+impl FileRealms {
+	/// Returns true if anything changed.
+	fn merge(&mut self, other: FileRealms) -> bool {
+		let merged = FileRealms {
+			server: self.server || other.server,
+			client: self.client || other.client,
+			sent: self.sent || other.sent
+		};
+		let changed = merged != *self;
+		*self = merged;
+		changed
+	}
+
+	#[inline]
+	pub fn reached(&self) -> bool {
+		self.server || self.client || self.sent
+	}
+
+	/// The realm gluapack should pack this file in.
+	///
+	/// Files are only packed clientside if they're sent with `AddCSLuaFile`, as clients can't include anything else. Server-only code is never sent to clients, even if it's also included clientside.
+	pub fn realm(&self) -> Option<&'static str> {
+		match (self.server, self.sent) {
+			(true, true) => Some("sh"),
+			(true, false) => Some("sv"),
+			(false, true) => Some("cl"),
+			(false, false) => None
+		}
+	}
+}
@@ -0,0 +1,187 @@
+// Incremental build cache, stored in the addon's .gluapack-cache/ directory
+
+use std::{collections::{BTreeMap, BTreeSet, HashSet}, path::{Path, PathBuf}, time::UNIX_EPOCH};
+use sha2::Digest;
+
+use crate::{config::Config, format, pack::ChunkEncodingReport, util};
+
+pub const CACHE_DIR: &str = ".gluapack-cache";
+
+/// A realm's packed files, as of the last build.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct CachedRealm {
+	/// Hash of the paths and contents of the Lua files in this realm
+	pub hash: String,
+
+	/// Sizes of the packed files written for this realm, in order
+	pub chunks: Vec<u64>,
+
+	/// Clientside Lua cache manifest hashes of the chunks (cl/sh only)
+	pub manifest_hashes: Vec<String>,
+
+	/// The Lua files in each chunk (cl/sh only)
+	#[serde(default)]
+	pub chunk_map: Vec<Vec<String>>,
+
+	/// Size of the chunks with each chunk encoding (cl/sh only)
+	#[serde(default)]
+	pub chunk_encoding: ChunkEncodingReport
+}
+
+/// A non-Lua file copied to the output directory, as of the last build.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CopiedFile {
+	pub len: u64,
+	pub modified: Option<(u64, u32)>,
+	pub hash: String,
+
+	/// Modification time of the copy in the output directory, so that copies changed since can be replaced
+	#[serde(default)]
+	pub copy_modified: Option<(u64, u32)>
+}
+
+fn modified(metadata: &std::fs::Metadata) -> Option<(u64, u32)> {
+	metadata.modified().ok()
+		.and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+		.map(|modified| (modified.as_secs(), modified.subsec_nanos()))
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct BuildCache {
+	/// Hash of everything besides the addon's files that affects the output
+	pub key: String,
+
+	pub unique_id: Option<String>,
+
+	pub sv: Option<CachedRealm>,
+	pub cl: Option<CachedRealm>,
+	pub sh: Option<CachedRealm>,
+
+	/// Files copied to the output directory, relative to the addon root
+	pub copied: BTreeMap<String, CopiedFile>
+}
+impl BuildCache {
+	pub fn new(config: &Config, no_copy: bool) -> BuildCache {
+		let mut sha256 = sha2::Sha256::new();
+		sha256.update(env!("CARGO_PKG_VERSION"));
+		sha256.update([format::FORMAT_VERSION, no_copy as u8]);
+		sha256.update(serde_json::to_vec(config).expect("Failed to serialize config"));
+
+		BuildCache {
+			key: format!("{:x}", sha256.finalize()),
+			..Default::default()
+		}
+	}
+
+	/// Where the cache of packing `dir` into `out_dir` is stored. Each output directory gets its own cache.
+	pub fn path(dir: &Path, out_dir: &Path) -> PathBuf {
+		let mut sha256 = sha2::Sha256::new();
+		sha256.update(out_dir.to_string_lossy().as_bytes());
+		dir.join(CACHE_DIR).join(format!("{}.json", &format!("{:x}", sha256.finalize())[0..16]))
+	}
+
+	/// Loads the cache at `path`, if it exists and was built with the same key.
+	pub fn load(path: &Path, key: &str) -> Option<BuildCache> {
+		let cache: BuildCache = serde_json::from_slice(&std::fs::read(path).ok()?).ok()?;
+		if cache.key == key {
+			Some(cache)
+		} else {
+			None
+		}
+	}
+
+	pub async fn save(&self, path: PathBuf) -> Result<(), std::io::Error> {
+		let cache_dir = path.parent().unwrap();
+		tokio::fs::create_dir_all(cache_dir).await?;
+
+		// Keep the cache out of version control
+		let gitignore = cache_dir.join(".gitignore");
+		if !gitignore.is_file() {
+			tokio::fs::write(gitignore, "*\n").await?;
+		}
+
+		tokio::fs::write(path, serde_json::to_vec(self).expect("Failed to serialize build cache")).await
+	}
+}
+
+/// Hashes the paths and contents of a realm's Lua files, and the file each one refers to if it was deduplicated.
+pub fn realm_hash<'a, I: IntoIterator<Item = (&'a str, &'a [u8], Option<&'a str>)>>(lua_files: I) -> String {
+	let mut sha256 = sha2::Sha256::new();
+	for (path, contents, duplicate_of) in lua_files {
+		sha256.update(path.as_bytes());
+		sha256.update([0]);
+		sha256.update((contents.len() as u64).to_le_bytes());
+		sha256.update(contents);
+		if let Some(duplicate_of) = duplicate_of {
+			sha256.update(duplicate_of.as_bytes());
+		}
+		sha256.update([0]);
+	}
+	format!("{:x}", sha256.finalize())
+}
+
+/// Returns the sizes of a realm's packed files in `gluapack_dir`, or `None` if any are missing.
+pub fn chunk_sizes(gluapack_dir: &Path, realm: &str, chunks: usize) -> Option<Vec<u64>> {
+	(1..=chunks).map(|i| {
+		let file_name = if realm == "sv" { "gluapack.sv.lua".to_string() } else { format!("gluapack.{}.{}.lua", i, realm) };
+		gluapack_dir.join(file_name).metadata().ok().map(|metadata| metadata.len())
+	}).collect()
+}
+
+/// Copies the addon at `from` to `to`, skipping hidden files, gluapack.json and `skip` (paths relative to the addon root).
+///
+/// Files whose size, modification time or contents haven't changed since `copied` was recorded are not copied again, unless their copy in the output directory was changed since. Previously copied files that no longer exist are deleted.
+pub fn copy_addon_incremental(from: &Path, to: &Path, skip: &HashSet<String>, copied: &BTreeMap<String, CopiedFile>) -> Result<BTreeMap<String, CopiedFile>, std::io::Error> {
+	let files = util::walk_addon(from, &mut HashSet::new())?.into_iter().filter(|entry| !entry.is_dir).map(|entry| (entry.path, entry.relative));
+
+	let mut now_copied = BTreeMap::new();
+	for (src, rel) in files {
+		if skip.contains(&rel) {
+			continue;
+		}
+
+		let dest = to.join(&rel);
+		let metadata = src.metadata()?;
+		let modified = modified(&metadata);
+
+		let previous = copied.get(&rel).filter(|previous| {
+			// The copy in the output directory must still be the one we wrote
+			dest.metadata().map(|dest| dest.len() == previous.len && previous.copy_modified.is_some() && self::modified(&dest) == previous.copy_modified).unwrap_or(false)
+		});
+
+		if let Some(previous) = previous {
+			if previous.len == metadata.len() && previous.modified.is_some() && previous.modified == modified {
+				now_copied.insert(rel, previous.clone());
+				continue;
+			}
+		}
+
+		let contents = std::fs::read(&src)?;
+		let hash = format!("{:x}", sha2::Sha256::digest(&contents));
+
+		if previous.map(|previous| previous.hash != hash).unwrap_or(true) {
+			if let Some(parent) = dest.parent() {
+				std::fs::create_dir_all(parent)?;
+			}
+			std::fs::write(&dest, &contents)?;
+		}
+
+		let copy_modified = self::modified(&dest.metadata()?);
+		now_copied.insert(rel, CopiedFile { len: metadata.len(), modified, hash, copy_modified });
+	}
+
+	// Delete files that were removed from the addon (or are now packed)
+	let mut check_empty = BTreeSet::new();
+	for rel in copied.keys().filter(|rel| !now_copied.contains_key(*rel)) {
+		let path = to.join(rel);
+		if path.is_file() {
+			std::fs::remove_file(&path)?;
+		}
+		check_empty.extend(path.ancestors().skip(1).take_while(|ancestor| *ancestor != to).map(Path::to_path_buf));
+	}
+	for dir in check_empty.into_iter().rev() {
+		std::fs::remove_dir(dir).ok();
+	}
+
+	Ok(now_copied)
+}
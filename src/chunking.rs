@@ -0,0 +1,392 @@
+// Splits clientside and shared packs into chunks small enough for Garry's Mod to network
+
+use std::{io::Cursor, ops::Range};
+
+use crate::{config::ChunkEncoding, format::{PackHeader, FLAG_ESCAPED_LINES}, pack::PackingError, unpack::UnpackingError};
+
+/// Lua comment
+pub const COMMENT_START: &[u8; 2] = b"--";
+
+/// Added after `--` to lines that start with `[` or `-`.
+///
+/// `--[[` or `--[==[` would start a long comment, and any code after its closing bracket on a later line would run when the chunk is compiled. Lines starting with `-` are escaped too so the escape can be told apart from the line's own contents.
+const ESCAPE: u8 = b'-';
+
+#[inline]
+fn is_line_break(byte: u8) -> bool {
+	matches!(byte, b'\n' | b'\r')
+}
+
+#[inline]
+fn needs_escape(byte: u8) -> bool {
+	matches!(byte, b'[' | b'-')
+}
+
+/// The most `byte` can add to a chunk after encoding, given the byte before it in the same chunk (if any).
+///
+/// With [`ChunkEncoding::Lines`], a `\r` followed by `\n` doesn't get a `--` of its own, so this can overestimate by 2 bytes per `\r\n`.
+#[inline]
+fn cost(encoding: ChunkEncoding, prev: Option<u8>, byte: u8) -> usize {
+	if encoding == ChunkEncoding::LongString {
+		return 1;
+	}
+
+	let mut cost = 1;
+	if is_line_break(byte) {
+		cost += COMMENT_START.len();
+	}
+	if needs_escape(byte) && prev.map(is_line_break).unwrap_or(true) {
+		cost += 1;
+	}
+	cost
+}
+
+/// Comments out every line in the byte vector so that the chunk compiles to nothing.
+///
+/// Every line is prefixed with `--` (plus an escape if the line starts with `[` or `-`). Lua also ends lines at a bare `\r`, so these are treated as line breaks too.
+pub fn commentify(bytes: &[u8]) -> Vec<u8> {
+	let mut escaped = Vec::with_capacity(bytes.len() + 2);
+	let mut line_start = true;
+	for (i, byte) in bytes.iter().copied().enumerate() {
+		if line_start {
+			escaped.extend_from_slice(COMMENT_START);
+			if needs_escape(byte) {
+				escaped.push(ESCAPE);
+			}
+		}
+
+		escaped.push(byte);
+
+		// A \r followed by \n is a single line break
+		line_start = byte == b'\n' || (byte == b'\r' && bytes.get(i + 1) != Some(&b'\n'));
+	}
+	if line_start {
+		escaped.extend_from_slice(COMMENT_START);
+	}
+	escaped
+}
+
+/// Reverses [`commentify`].
+///
+/// Chunks written before lines were escaped (packs without [`format::FLAG_ESCAPED_LINES`](crate::format::FLAG_ESCAPED_LINES)) must set `escaped` to false.
+pub fn uncommentify(chunk: &[u8], escaped: bool) -> Vec<u8> {
+	let mut bytes = Vec::with_capacity(chunk.len());
+	let mut i = 0;
+	let mut line_start = true;
+	while i < chunk.len() {
+		if line_start && chunk[i..].starts_with(COMMENT_START) {
+			i += COMMENT_START.len();
+			if escaped && chunk.get(i) == Some(&ESCAPE) {
+				i += 1;
+			}
+			line_start = false;
+			continue;
+		}
+
+		let byte = chunk[i];
+		bytes.push(byte);
+		line_start = byte == b'\n' || (escaped && byte == b'\r');
+		i += 1;
+	}
+	bytes
+}
+
+/// The lowest long bracket level that `payload` can't close early, i.e. `payload` contains no `]==]` (with that many `=`s) and doesn't end with `]==`.
+fn long_bracket_level(payload: &[u8]) -> usize {
+	let mut used = vec![];
+	for (i, _) in payload.iter().enumerate().filter(|(_, byte)| **byte == b']') {
+		let level = payload[i + 1..].iter().take_while(|byte| **byte == b'=').count();
+		if matches!(payload.get(i + 1 + level), None | Some(b']')) {
+			if used.len() <= level {
+				used.resize(level + 1, false);
+			}
+			used[level] = true;
+		}
+	}
+	used.iter().position(|used| !used).unwrap_or(used.len())
+}
+
+/// Wraps the payload in a Lua long comment (`--[==[payload]==]`), with a bracket level the payload can't close.
+pub fn long_comment(payload: &[u8]) -> Vec<u8> {
+	let level = long_bracket_level(payload);
+	let mut chunk = Vec::with_capacity(payload.len() + 6 + level * 2);
+	chunk.extend_from_slice(COMMENT_START);
+	chunk.push(b'[');
+	chunk.resize(chunk.len() + level, b'=');
+	chunk.push(b'[');
+	chunk.extend_from_slice(payload);
+	chunk.push(b']');
+	chunk.resize(chunk.len() + level, b'=');
+	chunk.push(b']');
+	chunk
+}
+
+/// Returns the level of the long comment `chunk` starts with, if it starts with one.
+fn long_comment_level(chunk: &[u8]) -> Option<usize> {
+	let level = chunk.strip_prefix(b"--[")?.iter().take_while(|byte| **byte == b'=').count();
+	if chunk.get(3 + level) == Some(&b'[') {
+		Some(level)
+	} else {
+		None
+	}
+}
+
+/// Encodes a chunk so that it compiles to nothing.
+pub fn encode(payload: &[u8], encoding: ChunkEncoding) -> Vec<u8> {
+	match encoding {
+		ChunkEncoding::Lines => commentify(payload),
+		ChunkEncoding::LongString => long_comment(payload)
+	}
+}
+
+/// How the chunks of a pack were encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkFormat {
+	/// Every line is prefixed with `--`, written before lines were escaped
+	Unescaped,
+
+	/// [`ChunkEncoding::Lines`]
+	Lines,
+
+	/// [`ChunkEncoding::LongString`]
+	LongString
+}
+impl ChunkFormat {
+	/// Works out how a pack's chunks were encoded from its first chunk.
+	pub fn detect(first_chunk: &[u8]) -> Result<ChunkFormat, UnpackingError> {
+		if long_comment_level(first_chunk).is_some() {
+			return Ok(ChunkFormat::LongString);
+		}
+
+		// The header is the first line of the first chunk, which reads the same however the lines were commented
+		let header = PackHeader::read(&mut Cursor::new(uncommentify(first_chunk, false)))?;
+		Ok(if header.has_flag(FLAG_ESCAPED_LINES) { ChunkFormat::Lines } else { ChunkFormat::Unescaped })
+	}
+
+	/// Returns the payload of a chunk.
+	pub fn decode(self, chunk: &[u8]) -> Vec<u8> {
+		match self {
+			ChunkFormat::Unescaped => uncommentify(chunk, false),
+			ChunkFormat::Lines => uncommentify(chunk, true),
+			ChunkFormat::LongString => match long_comment_level(chunk) {
+				Some(level) if chunk.len() >= 6 + level * 2 => chunk[4 + level..chunk.len() - 2 - level].to_vec(),
+				_ => vec![]
+			}
+		}
+	}
+}
+
+/// Returns true if `chunk` contains nothing but comments, so compiling it can't run any code.
+pub fn is_inert(chunk: &[u8]) -> bool {
+	crate::lexer::Lexer::new(chunk).next().is_none()
+}
+
+/// The size of a chunk's encoding besides its payload, if its long bracket level (if any) is at most `level`.
+#[inline]
+fn overhead(encoding: ChunkEncoding, level: usize) -> usize {
+	match encoding {
+		ChunkEncoding::Lines => COMMENT_START.len(),
+		ChunkEncoding::LongString => COMMENT_START.len() + 4 + level * 2
+	}
+}
+
+/// The most `bytes` can add to a chunk after encoding, not counting the [`overhead`].
+#[inline]
+fn encoded_len(encoding: ChunkEncoding, bytes: &[u8]) -> usize {
+	let mut prev = None;
+	bytes.iter().map(|byte| {
+		let cost = cost(encoding, prev, *byte);
+		prev = Some(*byte);
+		cost
+	}).sum()
+}
+
+/// Estimates the total size of the chunks `bytes` would be split into with `encoding`, without laying them out or encoding them.
+///
+/// Every line break and escape is charged for [`ChunkEncoding::Lines`], and every chunk gets the bracket level of the whole pack for [`ChunkEncoding::LongString`], so this can be a little high.
+pub fn estimate_size(bytes: &[u8], chunk_size: usize, encoding: ChunkEncoding) -> usize {
+	if bytes.is_empty() {
+		return 0;
+	}
+
+	let overhead = overhead(encoding, match encoding {
+		ChunkEncoding::Lines => 0,
+		ChunkEncoding::LongString => long_bracket_level(bytes)
+	});
+	let payload = encoded_len(encoding, bytes);
+	let chunks = payload.div_ceil(chunk_size.saturating_sub(overhead).max(1));
+	payload + chunks * overhead
+}
+
+/// A packed Lua file's entry in a pack.
+pub struct PackedEntry {
+	pub path: String,
+
+	/// Range of the entry (including its path, length and hash) in the pack
+	pub range: Range<usize>
+}
+
+/// The chunks of a networked pack.
+#[derive(Debug, Default)]
+pub struct Chunks {
+	/// Contents of each chunk file, already encoded
+	pub chunks: Vec<Vec<u8>>,
+
+	/// Paths of the Lua files in each chunk. Files split across chunks appear in each of them.
+	pub map: Vec<Vec<String>>
+}
+
+/// Returns how many bytes from the start of `bytes` fit in a chunk that already holds `used` encoded bytes.
+fn fit(encoding: ChunkEncoding, bytes: &[u8], mut used: usize, capacity: usize) -> usize {
+	let mut fits = 0;
+	let mut prev = None;
+	for byte in bytes {
+		let cost = cost(encoding, prev, *byte);
+		if used + cost > capacity {
+			break;
+		}
+		used += cost;
+		fits += 1;
+		prev = Some(*byte);
+	}
+	fits
+}
+
+#[derive(Default)]
+struct Bin {
+	pieces: Vec<Range<usize>>,
+	paths: Vec<String>,
+
+	/// Encoded size of the pieces
+	used: usize,
+
+	/// The last piece continues in the next chunk, so nothing else can be added to this one
+	closed: bool
+}
+
+/// Encodes the chunks laid out by `layout`, which fills bins with up to `capacity` encoded bytes.
+///
+/// Long comments need a higher bracket level (and so more bytes) for some payloads, which `layout` can't know in advance. If a chunk ends up over `chunk_size`, the pack is laid out again with room for a higher level. Fails if that doesn't help, or if a chunk with any other encoding ends up over `chunk_size`.
+fn split(bytes: &[u8], entries: &[PackedEntry], chunk_size: usize, encoding: ChunkEncoding, layout: fn(&[u8], &[PackedEntry], usize, ChunkEncoding) -> Vec<Bin>) -> Result<Chunks, PackingError> {
+	if bytes.is_empty() {
+		return Ok(Chunks::default());
+	}
+
+	let mut level = 0;
+	loop {
+		let capacity = match chunk_size.checked_sub(overhead(encoding, level)) {
+			Some(capacity) if capacity != 0 => capacity,
+			_ => return Err(error!(PackingError::ChunkOverflow((encoding, overhead(encoding, level), chunk_size))))
+		};
+		let bins = layout(bytes, entries, capacity, encoding);
+
+		let mut chunks = Chunks::default();
+		for bin in bins {
+			let mut payload = Vec::with_capacity(bin.pieces.iter().map(|piece| piece.len()).sum());
+			for piece in bin.pieces {
+				payload.extend_from_slice(&bytes[piece]);
+			}
+
+			chunks.chunks.push(encode(&payload, encoding));
+			chunks.map.push(bin.paths);
+		}
+
+		let largest = chunks.chunks.iter().map(Vec::len).max().unwrap_or(0);
+		if largest <= chunk_size {
+			return Ok(chunks);
+		}
+
+		// Only a long comment's bracket level can be raised to make room
+		if encoding != ChunkEncoding::LongString {
+			return Err(error!(PackingError::ChunkOverflow((encoding, largest, chunk_size))));
+		}
+		level += 1;
+	}
+}
+
+/// Slices the pack into chunks of up to `chunk_size` bytes, regardless of where files start and end.
+///
+/// The pack is sliced before it is encoded, and every chunk is encoded separately, so a chunk never starts in the middle of a line's `--` and the encoding never pushes a chunk over `chunk_size`.
+pub fn stream(bytes: &[u8], entries: &[PackedEntry], chunk_size: usize, encoding: ChunkEncoding) -> Result<Chunks, PackingError> {
+	split(bytes, entries, chunk_size, encoding, |bytes, entries, capacity, encoding| {
+		let mut bins: Vec<Bin> = vec![];
+		let mut start = 0;
+		while start < bytes.len() {
+			let end = start + fit(encoding, &bytes[start..], 0, capacity);
+			debug_assert!(end > start);
+			bins.push(Bin { pieces: std::iter::once(start..end).collect(), ..Default::default() });
+			start = end;
+		}
+
+		for entry in entries {
+			let first = bins.partition_point(|bin| bin.pieces[0].end <= entry.range.start);
+			let last = bins.partition_point(|bin| bin.pieces[0].end < entry.range.end);
+			for bin in &mut bins[first..=last] {
+				bin.paths.push(entry.path.clone());
+			}
+		}
+
+		bins
+	})
+}
+
+/// Packs whole files into as few chunks as possible (first-fit decreasing), only splitting files that are larger than a chunk.
+///
+/// Like [`stream`] chunks, every chunk is encoded separately and the decoded chunks form the pack again when joined, so the loader and unpacker read both the same way.
+pub fn file_aligned(bytes: &[u8], entries: &[PackedEntry], chunk_size: usize, encoding: ChunkEncoding) -> Result<Chunks, PackingError> {
+	split(bytes, entries, chunk_size, encoding, |bytes, entries, capacity, encoding| {
+		let len = |range: &Range<usize>| encoded_len(encoding, &bytes[range.clone()]);
+
+		// The pack header and file count must come first
+		let prefix = 0..entries.first().map(|entry| entry.range.start).unwrap_or(bytes.len());
+		let mut bins = vec![Bin {
+			used: len(&prefix),
+			pieces: vec![prefix],
+			..Default::default()
+		}];
+
+		let (large, mut small): (Vec<&PackedEntry>, Vec<&PackedEntry>) = entries.iter().partition(|entry| len(&entry.range) > capacity);
+
+		// Files larger than a chunk go first, split across consecutive chunks right after the header prefix. The last of their chunks stays open for smaller files.
+		for entry in large {
+			let mut range = entry.range.clone();
+			loop {
+				let bin = bins.last_mut().unwrap();
+
+				// Take as many bytes as fit in this chunk
+				let fits = fit(encoding, &bytes[range.clone()], bin.used, capacity);
+				if fits != 0 {
+					bin.used += len(&(range.start..range.start + fits));
+					bin.pieces.push(range.start..range.start + fits);
+					bin.paths.push(entry.path.clone());
+					range.start += fits;
+				}
+
+				if range.is_empty() {
+					break;
+				}
+
+				bin.closed = true;
+				bins.push(Bin::default());
+			}
+		}
+
+		// Then the rest of the files, largest first
+		small.sort_by_cached_key(|entry| (std::cmp::Reverse(len(&entry.range)), entry.path.clone()));
+		for entry in small {
+			let len = len(&entry.range);
+			let bin = match bins.iter_mut().position(|bin| !bin.closed && bin.used + len <= capacity) {
+				Some(i) => &mut bins[i],
+				None => {
+					bins.push(Bin::default());
+					bins.last_mut().unwrap()
+				}
+			};
+			bin.pieces.push(entry.range.clone());
+			bin.paths.push(entry.path.clone());
+			bin.used += len;
+		}
+
+		bins
+	})
+}
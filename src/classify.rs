@@ -0,0 +1,173 @@
+// Decides which realm each of an addon's Lua files is packed in, for both packing and explaining
+
+use std::collections::BTreeMap;
+
+use crate::{analysis::{FileRealms, IncludeGraph, UnresolvedCall}, config::{Config, GlobPattern}, pack::PACKED_FILES};
+
+pub const REALMS: [&str; 3] = ["sv", "cl", "sh"];
+
+/// What packing does with a Lua file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Decision {
+	/// The file is packed
+	Packed,
+
+	/// The file matches an `exclude` pattern (or is a packed file of a previous build), so it's copied as it is
+	Excluded,
+
+	/// The clientside or shared file can't be reached from any entry file, so `prune_unreachable` leaves it unpacked
+	Pruned,
+
+	/// The file matches more than one realm and `realm_conflicts` is `error`, so packing fails
+	Conflict,
+
+	/// The file matches no include or entry pattern, so it's copied as it is
+	Untouched
+}
+
+/// A Lua file of an addon, and the realm it's packed in.
+#[derive(Debug, Clone)]
+pub struct ClassifiedFile {
+	/// Path of the file, relative to `lua/`.
+	pub path: String,
+
+	pub decision: Decision,
+
+	/// The realm the file is packed in (or would be, if it was pruned). For conflicts, every realm the file matched.
+	pub realms: Vec<&'static str>,
+
+	/// The realms the loader runs this file in as an entry file.
+	pub entry: Vec<&'static str>,
+
+	/// `auto_realms` couldn't classify the file, so it fell back to the `include_*` patterns.
+	pub unclassified: bool,
+
+	/// Every realm the file matched, if `realm_conflicts` picked one of them.
+	pub resolved_conflict: Vec<&'static str>,
+
+	/// How `auto_realms` and `realm_conflicts` affected the file, if they did.
+	pub notes: Vec<String>
+}
+
+/// The result of [`classify`].
+#[derive(Debug, Clone, Default)]
+pub struct Classification {
+	/// Every Lua file of the addon, ordered by path.
+	pub files: Vec<ClassifiedFile>,
+
+	/// `include`/`AddCSLuaFile` calls that `auto_realms` couldn't follow.
+	pub unresolved: Vec<UnresolvedCall>
+}
+impl Classification {
+	/// Returns the paths of a realm's packed entry files, ordered by the first of `patterns` they match, then by path.
+	pub fn entry_files(&self, realm: &str, patterns: &[GlobPattern]) -> Vec<String> {
+		let mut entry_files = self.files.iter()
+			.filter(|file| file.decision == Decision::Packed && file.entry.contains(&realm))
+			.filter_map(|file| patterns.iter().position(|pattern| pattern.matches(&file.path)).map(|pattern_index| (pattern_index, file.path.clone())))
+			.collect::<Vec<_>>();
+
+		entry_files.sort_unstable();
+
+		entry_files.into_iter().map(|(_, path)| path).collect()
+	}
+}
+
+/// Classifies an addon's Lua files (paths relative to `lua/` and their contents) into realms according to its config.
+///
+/// Without `auto_realms`, a file is collected into every realm whose `include_*` or `entry_*` patterns it matches. With it, files are classified by following `include` and `AddCSLuaFile` calls from the entry files, falling back to the `include_*` patterns. Files that end up in more than one realm are resolved by `realm_conflicts`. Unreachable files aren't pruned here, see [`find_unreachable`].
+pub fn classify(lua_files: &BTreeMap<String, Vec<u8>>, config: &Config) -> Classification {
+	let packed_files = PACKED_FILES.iter().map(|pattern| GlobPattern::new(pattern)).collect::<Vec<_>>();
+
+	let matching = |patterns: &[GlobPattern], path: &str| patterns.iter().any(|pattern| pattern.matches(path));
+	let realms_matching = |patterns: [&Vec<GlobPattern>; 3], path: &str| REALMS.iter().zip(patterns).filter(|(_, patterns)| matching(patterns, path)).map(|(realm, _)| *realm).collect::<Vec<_>>();
+
+	let mut files = lua_files.keys().map(|path| {
+		let excluded = matching(&config.exclude, path) || matching(&packed_files, path);
+		ClassifiedFile {
+			path: path.clone(),
+			decision: if excluded { Decision::Excluded } else { Decision::Untouched },
+			realms: vec![],
+			entry: if excluded { vec![] } else { realms_matching([&config.entry_sv, &config.entry_cl, &config.entry_sh], path) },
+			unclassified: false,
+			resolved_conflict: vec![],
+			notes: vec![]
+		}
+	}).collect::<Vec<_>>();
+
+	let (classified, unresolved) = if config.auto_realms {
+		let entry_files = |realm: &str| files.iter().filter(|file| file.entry.contains(&realm)).map(|file| file.path.clone()).collect::<Vec<_>>();
+		let graph = IncludeGraph::build(files.iter().filter(|file| file.decision != Decision::Excluded).map(|file| (file.path.as_str(), lua_files[&file.path].as_slice())));
+		(Some(graph.propagate(&entry_files("sv"), &entry_files("cl"), &entry_files("sh"))), graph.unresolved)
+	} else {
+		(None, vec![])
+	};
+
+	let includes = [&config.include_sv, &config.include_cl, &config.include_sh];
+	for file in files.iter_mut().filter(|file| file.decision != Decision::Excluded) {
+		let mut realms = match &classified {
+			Some(classified) => match classified.get(&file.path).and_then(FileRealms::realm) {
+				Some(realm) => {
+					file.notes.push(format!("auto_realms classified it as {} by following include() and AddCSLuaFile() calls from the entry files", realm));
+					vec![realm]
+				},
+				None => {
+					// Analysis was inconclusive, fall back to the include patterns
+					let realms = realms_matching(includes, &file.path);
+					match realms.as_slice() {
+						[] => file.notes.push("auto_realms couldn't classify it, and it matches no include pattern".to_string()),
+						realms => file.notes.push(format!("auto_realms couldn't classify it, falling back to include_{}", realms.join(" and include_")))
+					}
+					file.unclassified = true;
+					realms
+				}
+			},
+			None => {
+				let mut realms = realms_matching(includes, &file.path);
+				for realm in file.entry.iter() {
+					if !realms.contains(realm) {
+						realms.push(realm);
+					}
+				}
+				realms.sort_by_key(|realm| REALMS.iter().position(|other| other == realm));
+				realms
+			}
+		};
+
+		if realms.len() > 1 {
+			match config.realm_conflicts.resolve(&realms) {
+				Some(realm) => {
+					file.notes.push(format!("it matches {}, realm_conflicts packs it as {}", realms.join(" and "), realm));
+					file.resolved_conflict = std::mem::replace(&mut realms, vec![realm]);
+				},
+				None => {
+					file.decision = Decision::Conflict;
+					file.realms = realms;
+					continue;
+				}
+			}
+		}
+
+		if let [realm] = realms.as_slice() {
+			file.decision = Decision::Packed;
+			if *realm == "sv" {
+				// Clients won't have it
+				file.entry.retain(|entry| *entry == "sv");
+			}
+		}
+		file.realms = realms;
+	}
+
+	Classification { files, unresolved }
+}
+
+/// Returns the sorted paths of the packed Lua files that can't be reached from any entry file, and the graph of their calls.
+pub fn find_unreachable<'a, I: Iterator<Item = (&'a str, &'a [u8])> + Clone>(lua_files: I, sv_entry_files: &[String], cl_entry_files: &[String], sh_entry_files: &[String]) -> (Vec<String>, IncludeGraph) {
+	let graph = IncludeGraph::build(lua_files.clone());
+	let reached = graph.propagate(sv_entry_files, cl_entry_files, sh_entry_files);
+
+	let mut unreachable = lua_files.filter(|(path, _)| !reached.contains_key(*path)).map(|(path, _)| path.to_owned()).collect::<Vec<_>>();
+	unreachable.sort_unstable();
+
+	(unreachable, graph)
+}
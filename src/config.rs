@@ -1,8 +1,10 @@
-use std::{fs::File, path::Path};
+use std::path::{Path, PathBuf};
 
 use serde::de::{Unexpected, Visitor};
 
-use crate::pack::PackingError;
+use crate::{pack::PackingError, syntax::{Diagnostic, SyntaxError}, MAX_LUA_SIZE};
+
+mod parse;
 
 macro_rules! impl_default {
 	{ Config { $($field:ident: $ty:ty = $default:expr),* } } => {
@@ -92,8 +94,85 @@ impl serde::Serialize for GlobPattern {
     }
 }
 
-#[derive(serde::Deserialize, serde::Serialize, Debug)]
+/// Which realms' Lua files are minified before packing.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct MinifyConfig {
+	pub sv: bool,
+	pub cl: bool,
+	pub sh: bool
+}
+
+/// How clientside and shared packs are split into chunks.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkingStrategy {
+	/// Slice the pack every `chunk_size` bytes. Files can straddle chunks.
+	Stream,
+
+	/// Pack whole files into chunks, only splitting files that don't fit in a single chunk.
+	FileAligned
+}
+
+/// How clientside and shared chunks are commented out, so that compiling them doesn't run any code.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkEncoding {
+	/// Prefix every line with `--`.
+	Lines,
+
+	/// Wrap the whole chunk in a long comment (`--[==[ ... ]==]`).
+	LongString
+}
+
+/// What to do with Lua files that no `include_*` or `entry_*` pattern covers (and that aren't excluded), which are copied unpacked.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Uncovered {
+	/// Don't report them.
+	Ignore,
+
+	/// List them when packing.
+	Warn,
+
+	/// Fail with [`PackingError::Uncovered`], listing every uncovered file.
+	Error
+}
+
+/// What to do with a Lua file that matches the `include_*` patterns of more than one realm.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RealmConflicts {
+	/// Fail with [`PackingError::RealmConflict`], listing every conflicting file.
+	Error,
+
+	/// Pack the file as shared.
+	PromoteShared,
+
+	/// Pack the file as serverside, or as shared if it didn't match the serverside realm.
+	PreferSv,
+
+	/// Pack the file as clientside, or as shared if it didn't match the clientside realm.
+	PreferCl
+}
+impl RealmConflicts {
+	/// Returns the realm a file matching all of `realms` is packed in, or `None` if conflicts are errors.
+	pub fn resolve(self, realms: &[&'static str]) -> Option<&'static str> {
+		match self {
+			RealmConflicts::Error => None,
+			RealmConflicts::PromoteShared => Some("sh"),
+			RealmConflicts::PreferSv => Some(if realms.contains(&"sv") { "sv" } else { "sh" }),
+			RealmConflicts::PreferCl => Some(if realms.contains(&"cl") { "cl" } else { "sh" })
+		}
+	}
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
 pub struct Config {
+	/// A base config file (relative to this one) or built-in preset (`preset:<name>`) that this config extends.
+	#[serde(default)]
+	pub extends: Option<String>,
+
 	#[serde(default = "include_sh")]
 	pub include_sh: Vec<GlobPattern>,
 
@@ -117,19 +196,411 @@ pub struct Config {
 
 	#[serde(default)]
 	pub unique_id: Option<String>,
+
+	#[serde(default = "auto_realms")]
+	pub auto_realms: bool,
+
+	#[serde(default = "prune_unreachable")]
+	pub prune_unreachable: bool,
+
+	#[serde(default = "realm_conflicts")]
+	pub realm_conflicts: RealmConflicts,
+
+	#[serde(default = "uncovered")]
+	pub uncovered: Uncovered,
+
+	#[serde(default = "minify")]
+	pub minify: MinifyConfig,
+
+	#[serde(default = "chunk_size")]
+	pub chunk_size: usize,
+
+	#[serde(default = "chunking")]
+	pub chunking: ChunkingStrategy,
+
+	#[serde(default = "chunk_encoding")]
+	pub chunk_encoding: ChunkEncoding,
+
+	#[serde(default = "dedup")]
+	pub dedup: bool,
 }
 impl Config {
+	/// The names of the config file in an addon's root, in the order they're looked for.
+	pub const FILE_NAMES: [&'static str; 6] = ["gluapack.json", "gluapack.jsonc", "gluapack.json5", "gluapack.toml", "gluapack.yaml", "gluapack.yml"];
+
+	/// Finds the config file in an addon's root.
+	pub fn find<P: AsRef<Path>>(dir: P) -> Option<PathBuf> {
+		Config::FILE_NAMES.iter().map(|file_name| dir.as_ref().join(file_name)).find(|path| path.is_file())
+	}
+
+	/// The names of the built-in presets, which can be extended with `"extends": "preset:<name>"`.
+	pub const PRESETS: [&'static str; 2] = ["default", "darkrp-module"];
+
+	/// Returns a built-in preset. Presets are the default config with some additions.
+	pub fn preset(name: &str) -> Option<Config> {
+		let default = Config::default();
+		match name {
+			"default" => Some(default),
+
+			// The darkrpmodification addon. DarkRP includes its modules, custom things and language files itself.
+			"darkrp-module" => Some(Config {
+				include_sh: default.include_sh.iter().cloned().chain(vec![GlobPattern::new("darkrp_customthings/*.lua"), GlobPattern::new("darkrp_language/*.lua"), GlobPattern::new("darkrp_config/settings.lua"), GlobPattern::new("darkrp_config/disabled_defaults.lua")]).collect(),
+				include_sv: default.include_sv.iter().cloned().chain(vec![GlobPattern::new("darkrp_config/mysql.lua")]).collect(),
+				..default
+			}),
+
+			_ => None
+		}
+	}
+
+	/// Reads a config file. `.toml`, `.yaml` and `.yml` files are read as TOML and YAML, anything else as JSON5, which allows comments and trailing commas.
+	///
+	/// If the config `extends` another config, it's merged over it: arrays (such as `exclude`) are appended to the base's, objects (such as `minify`) are merged key by key and any other value replaces the base's.
 	pub fn read<P: AsRef<Path>>(path: P) -> Result<Config, PackingError> {
-		let mut f = File::open(path)?;
-		Ok(serde_json::from_reader(&mut f)?)
+		read_extended::<Config>(path.as_ref(), &mut vec![], |error| error!(PackingError::ConfigError(error)))
+	}
+
+	/// The `include_*`, `exclude` and `entry_*` patterns, by option name.
+	pub fn patterns(&self) -> [(&'static str, &[GlobPattern]); 7] {
+		[
+			("include_sh", &self.include_sh), ("include_cl", &self.include_cl), ("include_sv", &self.include_sv), ("exclude", &self.exclude),
+			("entry_cl", &self.entry_cl), ("entry_sh", &self.entry_sh), ("entry_sv", &self.entry_sv)
+		]
 	}
 
 	pub fn dump_json(&self) {
 		println!("{}", serde_json::to_string_pretty(&self).unwrap());
 	}
 }
+
+/// A `gluapack-workspace.json`, which packs several addons into a single pack with a single loader.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+pub struct Workspace {
+	/// The addons' roots, relative to the workspace. Their entry files are run in this order.
+	pub addons: Vec<PathBuf>,
+
+	/// Options for the combined pack. Which of an addon's files are packed, their realms and its entry files come from the addon's own gluapack.json.
+	#[serde(flatten)]
+	pub config: Config
+}
+impl Workspace {
+	pub const FILE_NAME: &'static str = "gluapack-workspace.json";
+
+	pub fn read<P: AsRef<Path>>(path: P) -> Result<Workspace, PackingError> {
+		read_extended::<Workspace>(path.as_ref(), &mut vec![], |error| error!(PackingError::WorkspaceError(error)))
+	}
+}
+
+/// How a config option's value is written on the command line and in environment variables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionKind {
+	/// A string, or an empty string for `null`
+	String,
+
+	/// `true` or `false` (or `1`/`0`, `yes`/`no`, `on`/`off`)
+	Bool,
+
+	Integer,
+
+	/// One of the option's snake_case values
+	Enum,
+
+	/// A list of glob patterns, which can be appended to or replaced
+	Patterns,
+
+	/// A comma-separated list of realms (`sv,cl,sh`), or `none`
+	Realms
+}
+
+/// A config option that can be overridden from the command line (`--<flag>`) or environment (`GLUAPACK_<FIELD>`).
+#[derive(Debug, Clone, Copy)]
+pub struct ConfigOption {
+	pub field: &'static str,
+	pub kind: OptionKind
+}
+impl ConfigOption {
+	/// The option's command line flag, e.g. `unique-id`. Lists are appended to with `--<flag>` and replaced with `--set-<flag>`.
+	pub fn flag(&self) -> String {
+		self.field.replace('_', "-")
+	}
+
+	/// The option's environment variable, e.g. `GLUAPACK_UNIQUE_ID`. Lists are appended to with `GLUAPACK_<FIELD>` and replaced with `GLUAPACK_SET_<FIELD>`.
+	pub fn env(&self) -> String {
+		format!("GLUAPACK_{}", self.field.to_uppercase())
+	}
+}
+
+/// Every config option, except `extends`, which can only be set in config files.
+pub const OPTIONS: [ConfigOption; 17] = [
+	ConfigOption { field: "unique_id", kind: OptionKind::String },
+	ConfigOption { field: "include_sh", kind: OptionKind::Patterns },
+	ConfigOption { field: "include_cl", kind: OptionKind::Patterns },
+	ConfigOption { field: "include_sv", kind: OptionKind::Patterns },
+	ConfigOption { field: "exclude", kind: OptionKind::Patterns },
+	ConfigOption { field: "entry_cl", kind: OptionKind::Patterns },
+	ConfigOption { field: "entry_sh", kind: OptionKind::Patterns },
+	ConfigOption { field: "entry_sv", kind: OptionKind::Patterns },
+	ConfigOption { field: "auto_realms", kind: OptionKind::Bool },
+	ConfigOption { field: "prune_unreachable", kind: OptionKind::Bool },
+	ConfigOption { field: "realm_conflicts", kind: OptionKind::Enum },
+	ConfigOption { field: "uncovered", kind: OptionKind::Enum },
+	ConfigOption { field: "minify", kind: OptionKind::Realms },
+	ConfigOption { field: "chunk_size", kind: OptionKind::Integer },
+	ConfigOption { field: "chunking", kind: OptionKind::Enum },
+	ConfigOption { field: "chunk_encoding", kind: OptionKind::Enum },
+	ConfigOption { field: "dedup", kind: OptionKind::Bool }
+];
+
+#[derive(Debug, Clone)]
+struct Override {
+	/// The flag or environment variable the override came from
+	source: String,
+
+	field: &'static str,
+	value: serde_json::Value,
+
+	/// Whether the value is appended to a list, rather than replacing it
+	append: bool
+}
+
+/// Config options overridden from the command line or environment, which take precedence over config files.
+///
+/// Overrides are applied in the order they were added, so command line overrides should be added after [`ConfigOverrides::from_env`].
+#[derive(Debug, Clone, Default)]
+pub struct ConfigOverrides {
+	overrides: Vec<Override>
+}
+impl ConfigOverrides {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Reads overrides from `GLUAPACK_<FIELD>` environment variables (see [`OPTIONS`]). Lists of patterns are comma-separated.
+	pub fn from_env() -> Result<ConfigOverrides, PackingError> {
+		let mut overrides = ConfigOverrides::new();
+		for option in OPTIONS.iter() {
+			let env = option.env();
+			let set_env = format!("GLUAPACK_SET_{}", option.field.to_uppercase());
+			for (env, append) in [(set_env, false), (env, option.kind == OptionKind::Patterns)] {
+				let value = match std::env::var_os(&env) {
+					Some(value) => value.into_string().map_err(|_| error!(PackingError::InvalidOverride(format!("{}: not valid UTF-8", env))))?,
+					None => continue
+				};
+				let values = match option.kind {
+					OptionKind::Patterns => value.split(',').map(str::trim).filter(|pattern| !pattern.is_empty()).collect(),
+					_ => vec![value.as_str()]
+				};
+				if append {
+					overrides.append(env.clone(), option.field, &values)?;
+				} else {
+					overrides.set(env.clone(), option.field, &values)?;
+				}
+			}
+		}
+		Ok(overrides)
+	}
+
+	/// Overrides an option, replacing lists of patterns with `values`. Other options take a single value.
+	///
+	/// `source` is the flag or environment variable the override came from, for errors.
+	pub fn set<S: Into<String>>(&mut self, source: S, field: &str, values: &[&str]) -> Result<(), PackingError> {
+		self.add(source.into(), field, values, false)
+	}
+
+	/// Appends `values` to a list of patterns.
+	pub fn append<S: Into<String>>(&mut self, source: S, field: &str, values: &[&str]) -> Result<(), PackingError> {
+		self.add(source.into(), field, values, true)
+	}
+
+	fn add(&mut self, source: String, field: &str, values: &[&str], append: bool) -> Result<(), PackingError> {
+		let invalid = |message: String| error!(PackingError::InvalidOverride(format!("{}: {}", source, message)));
+
+		let option = match OPTIONS.iter().find(|option| option.field == field) {
+			Some(option) => option,
+			None => return Err(invalid(format!("unknown config option `{}`", field)))
+		};
+		if append && option.kind != OptionKind::Patterns {
+			return Err(invalid(format!("`{}` isn't a list", field)));
+		}
+		if option.kind != OptionKind::Patterns && option.kind != OptionKind::Realms && values.len() != 1 {
+			return Err(invalid(format!("`{}` takes a single value", field)));
+		}
+
+		use serde_json::Value;
+		let value = match option.kind {
+			OptionKind::String if values[0].is_empty() => Value::Null,
+			OptionKind::String | OptionKind::Enum => Value::String(values[0].to_string()),
+			OptionKind::Bool => match values[0].to_ascii_lowercase().as_str() {
+				"true" | "1" | "yes" | "on" => Value::Bool(true),
+				"false" | "0" | "no" | "off" => Value::Bool(false),
+				value => return Err(invalid(format!("expected true or false, found `{}`", value)))
+			},
+			OptionKind::Integer => match values[0].trim().parse::<u64>() {
+				Ok(integer) => Value::from(integer),
+				Err(_) => return Err(invalid(format!("expected a number, found `{}`", values[0])))
+			},
+			OptionKind::Patterns => Value::Array(values.iter().map(|pattern| Value::String(pattern.to_string())).collect()),
+			OptionKind::Realms => {
+				let mut realms = serde_json::Map::new();
+				for realm in ["sv", "cl", "sh"] {
+					realms.insert(realm.to_string(), Value::Bool(false));
+				}
+				for realm in values.iter().flat_map(|values| values.split(',')).map(str::trim).filter(|realm| !realm.is_empty() && *realm != "none") {
+					match realms.get_mut(realm) {
+						Some(minify) => *minify = Value::Bool(true),
+						None => return Err(invalid(format!("unknown realm `{}`, expected sv, cl, sh or none", realm)))
+					}
+				}
+				Value::Object(realms)
+			}
+		};
+
+		let change = Override { source, field: option.field, value, append };
+
+		// Check the value is valid for the option now, so that the error points to the flag or environment variable
+		let mut config = serde_json::to_value(Config::default()).unwrap();
+		change.apply(&mut config);
+		if let Err(error) = serde_json::from_value::<Config>(config) {
+			return Err(error!(PackingError::InvalidOverride(format!("{}: {}", change.source, error))));
+		}
+
+		self.overrides.push(change);
+		Ok(())
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.overrides.is_empty()
+	}
+
+	/// The flags and environment variables the overrides came from, in the order they're applied.
+	pub fn sources(&self) -> impl Iterator<Item = &str> {
+		self.overrides.iter().map(|change| change.source.as_str())
+	}
+
+	/// Applies the overrides to a config.
+	pub fn apply(&self, config: Config) -> Result<Config, PackingError> {
+		if self.overrides.is_empty() {
+			return Ok(config);
+		}
+
+		let mut config = serde_json::to_value(config).unwrap();
+		for change in self.overrides.iter() {
+			change.apply(&mut config);
+		}
+		serde_json::from_value(config).map_err(|error| error!(PackingError::InvalidOverride(error.to_string())))
+	}
+}
+impl Override {
+	fn apply(&self, config: &mut serde_json::Value) {
+		let value = &mut config[self.field];
+		match (value, &self.value) {
+			(serde_json::Value::Array(patterns), serde_json::Value::Array(appended)) if self.append => {
+				for pattern in appended {
+					if !patterns.contains(pattern) {
+						patterns.push(pattern.clone());
+					}
+				}
+			},
+			(value, new_value) => *value = new_value.clone()
+		}
+	}
+}
+
+/// Reads a config file as a `T`, merged over the config it `extends`, if any. `chain` is the files extending it, to detect circular extends.
+fn read_extended<T: serde::de::DeserializeOwned>(path: &Path, chain: &mut Vec<PathBuf>, config_error: fn(Diagnostic) -> PackingError) -> Result<T, PackingError> {
+	let config = read_extends::<T>(path, chain, config_error)?;
+	serde_json::from_value(config).map_err(|error| config_error(Diagnostic { path: path.display().to_string(), message: error.to_string(), line: 1, column: 1, excerpt: String::new() }))
+}
+
+/// Reads a config file as JSON, merged over the config it `extends`.
+fn read_extends<T: serde::de::DeserializeOwned>(path: &Path, chain: &mut Vec<PathBuf>, config_error: fn(Diagnostic) -> PackingError) -> Result<serde_json::Value, PackingError> {
+	let (config, src) = read_file::<T>(path, config_error)?;
+
+	let extends = match config.get("extends") {
+		Some(serde_json::Value::String(extends)) => extends.clone(),
+		_ => return Ok(config)
+	};
+
+	// Errors about the base config point to `extends`
+	let extends_error = |message: String| {
+		let offset = src.find("extends").unwrap_or(0);
+		let (line, column) = parse::line_column(&src, offset);
+		config_error(Diagnostic::new(path.display().to_string(), src.as_bytes(), SyntaxError { message, line, column }))
+	};
+
+	let base = match extends.strip_prefix("preset:") {
+		Some(preset) => match Config::preset(preset) {
+			Some(preset) => serde_json::to_value(preset).unwrap(),
+			None => return Err(extends_error(format!("unknown preset `{}`, the presets are: {}", preset, Config::PRESETS.join(", "))))
+		},
+		None => {
+			let canonical = crate::util::canonicalize(path);
+			if chain.contains(&canonical) {
+				let circle = chain.iter().chain(std::iter::once(&canonical)).map(|path| path.display().to_string()).collect::<Vec<_>>();
+				return Err(extends_error(format!("circular extends: {}", circle.join(" -> "))));
+			}
+
+			let base_path = path.parent().unwrap_or_else(|| Path::new("")).join(&extends);
+			if !base_path.is_file() {
+				return Err(extends_error(format!("couldn't find `{}` to extend", base_path.display())));
+			}
+
+			chain.push(canonical);
+			let base = read_extends::<Config>(&base_path, chain, config_error)?;
+			chain.pop();
+			base
+		}
+	};
+
+	Ok(merge(base, config))
+}
+
+/// Merges a config over the config it extends.
+fn merge(base: serde_json::Value, config: serde_json::Value) -> serde_json::Value {
+	use serde_json::Value;
+	match (base, config) {
+		(Value::Object(mut base), Value::Object(config)) => {
+			for (key, value) in config {
+				let value = match base.remove(&key) {
+					Some(base) => merge(base, value),
+					None => value
+				};
+				base.insert(key, value);
+			}
+			Value::Object(base)
+		},
+		(Value::Array(mut base), Value::Array(config)) => {
+			for value in config {
+				if !base.contains(&value) {
+					base.push(value);
+				}
+			}
+			Value::Array(base)
+		},
+		(_, config) => config
+	}
+}
+
+/// Reads a JSON5, TOML or YAML file (depending on its extension) as JSON, checking it's a valid `T` so that errors point to the line and column in the file. Returns the JSON and the file's contents.
+fn read_file<T: serde::de::DeserializeOwned>(path: &Path, config_error: fn(Diagnostic) -> PackingError) -> Result<(serde_json::Value, String), PackingError> {
+	let src = std::fs::read_to_string(path)?;
+
+	let parsed = match path.extension().and_then(|extension| extension.to_str()) {
+		Some("toml") => parse::toml::<T>(&src),
+		Some("yaml") | Some("yml") => parse::yaml::<T>(&src),
+		_ => parse::json5::<T>(&src)
+	};
+
+	match parsed {
+		Ok(config) => Ok((config, src)),
+		Err(error) => Err(config_error(Diagnostic::new(path.display().to_string(), src.as_bytes(), error)))
+	}
+}
+
 impl_default! {
 	Config {
+		extends: Option<String> = None,
+
 		include_sh: Vec<GlobPattern> = vec![GlobPattern::new("**/sh_*.lua"), GlobPattern::new("**/*.sh.lua")],
 		include_cl: Vec<GlobPattern> = vec![GlobPattern::new("**/cl_*.lua"), GlobPattern::new("**/*.cl.lua"), GlobPattern::new("vgui/*.lua"), GlobPattern::new("skins/*.lua"), GlobPattern::new("postprocess/*.lua")],
 		include_sv: Vec<GlobPattern> = vec![GlobPattern::new("**/sv_*.lua"), GlobPattern::new("**/*.sv.lua")],
@@ -139,6 +610,19 @@ impl_default! {
 		entry_sh: Vec<GlobPattern> = vec![GlobPattern::new("autorun/*.lua")],
 		entry_sv: Vec<GlobPattern> = vec![GlobPattern::new("autorun/server/*.lua")],
 
-		unique_id: Option<String> = None
+		unique_id: Option<String> = None,
+
+		auto_realms: bool = false,
+		prune_unreachable: bool = false,
+		realm_conflicts: RealmConflicts = RealmConflicts::Error,
+		uncovered: Uncovered = Uncovered::Warn,
+
+		minify: MinifyConfig = MinifyConfig::default(),
+
+		chunk_size: usize = MAX_LUA_SIZE,
+		chunking: ChunkingStrategy = ChunkingStrategy::Stream,
+		chunk_encoding: ChunkEncoding = ChunkEncoding::Lines,
+
+		dedup: bool = true
 	}
 }
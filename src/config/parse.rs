@@ -0,0 +1,57 @@
+// Config files are parsed as JSON5, TOML or YAML into JSON, with errors pointing into the original file.
+// Each file is deserialized as the type it's read as first, so that invalid values are reported where they are in the file too.
+
+use serde::de::DeserializeOwned;
+
+use crate::syntax::SyntaxError;
+
+/// Returns the 1-based line and column (in bytes) of a byte offset.
+pub(super) fn line_column(src: &str, offset: usize) -> (usize, usize) {
+	let before = &src.as_bytes()[..offset.min(src.len())];
+	let line_start = before.iter().rposition(|byte| *byte == b'\n').map(|i| i + 1).unwrap_or(0);
+	(before.iter().filter(|byte| **byte == b'\n').count() + 1, before.len() - line_start + 1)
+}
+
+/// An error at a byte offset into the config file.
+fn error_at(src: &str, offset: usize, message: &str) -> SyntaxError {
+	let (line, column) = line_column(src, offset);
+	SyntaxError { message: message.to_string(), line, column }
+}
+
+/// Parses JSON5, which is also how `.json` and `.jsonc` files are read.
+pub(super) fn json5<T: DeserializeOwned>(src: &str) -> Result<serde_json::Value, SyntaxError> {
+	let error = |error: json5::Error| {
+		let message = error.to_string();
+		match error.position() {
+			Some(position) => SyntaxError {
+				message: message.strip_suffix(&format!(" at {}", position)).unwrap_or(&message).to_string(),
+				line: position.line + 1,
+				column: position.column + 1
+			},
+			None => error_at(src, 0, &message)
+		}
+	};
+
+	json5::from_str::<T>(src).map_err(error)?;
+	json5::from_str(src).map_err(error)
+}
+
+pub(super) fn toml<T: DeserializeOwned>(src: &str) -> Result<serde_json::Value, SyntaxError> {
+	let error = |error: toml::de::Error| {
+		error_at(src, error.span().map(|span| span.start).unwrap_or(0), error.message().trim_end())
+	};
+
+	toml::from_str::<T>(src).map_err(error)?;
+	toml::from_str(src).map_err(error)
+}
+
+pub(super) fn yaml<T: DeserializeOwned>(src: &str) -> Result<serde_json::Value, SyntaxError> {
+	let error = |error: serde_norway::Error| {
+		let message = error.to_string();
+		let message = message.rfind(" at line ").map(|end| &message[..end]).unwrap_or(&message);
+		error_at(src, error.location().map(|location| location.index()).unwrap_or(0), message)
+	};
+
+	serde_norway::from_str::<T>(src).map_err(error)?;
+	serde_norway::from_str(src).map_err(error)
+}
@@ -0,0 +1,297 @@
+use std::{collections::BTreeMap, path::{Path, PathBuf}, time::Duration};
+
+use crate::unpack::{EntryFiles, PackDir, UnpackingError};
+
+/// Options for [`Differ::diff`].
+#[derive(Debug, Clone)]
+pub struct DiffOptions {
+	patch: bool,
+	context: usize
+}
+impl Default for DiffOptions {
+	fn default() -> Self {
+		DiffOptions {
+			patch: false,
+			context: 3
+		}
+	}
+}
+impl DiffOptions {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Includes a unified diff of every modified file in the report.
+	pub fn patch(mut self, patch: bool) -> Self {
+		self.patch = patch;
+		self
+	}
+
+	/// Sets the number of unchanged lines shown around each change in unified diffs.
+	///
+	/// Defaults to 3.
+	pub fn context(mut self, context: usize) -> Self {
+		self.context = context;
+		self
+	}
+}
+
+/// A Lua file that differs between two packed addons.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct FileDiff {
+	/// Path of the file, relative to `lua/`.
+	pub path: String,
+
+	/// The realm of the file in the old addon, or `None` if it was added.
+	pub old_realm: Option<&'static str>,
+
+	/// The realm of the file in the new addon, or `None` if it was removed.
+	pub new_realm: Option<&'static str>,
+
+	/// Whether the file's contents changed. Always false for added and removed files.
+	pub modified: bool,
+
+	/// Unified diff of the file's contents, if it was modified and [`DiffOptions::patch`] is set.
+	pub patch: Option<String>
+}
+impl FileDiff {
+	pub fn is_added(&self) -> bool {
+		self.old_realm.is_none()
+	}
+
+	pub fn is_removed(&self) -> bool {
+		self.new_realm.is_none()
+	}
+
+	/// Whether the file is in both addons, but in different realms.
+	pub fn is_moved(&self) -> bool {
+		matches!((self.old_realm, self.new_realm), (Some(old_realm), Some(new_realm)) if old_realm != new_realm)
+	}
+}
+
+/// An entry file that was added to or removed from a realm's entry files.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct EntryFileDiff {
+	pub realm: &'static str,
+	pub path: String,
+	pub added: bool
+}
+
+/// The result of [`Differ::diff`].
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct DiffReport {
+	/// Lua files that were added, removed, modified or moved to another realm, sorted by path.
+	pub files: Vec<FileDiff>,
+
+	/// Changes to the entry files listed in the loaders, by realm (sv, cl, sh) then path.
+	pub entry_files: Vec<EntryFileDiff>,
+
+	#[serde(skip)]
+	pub elapsed: Duration
+}
+impl DiffReport {
+	pub fn is_empty(&self) -> bool {
+		self.files.is_empty() && self.entry_files.is_empty()
+	}
+}
+
+/// The packed Lua files of an addon and its entry files.
+struct PackedAddon {
+	files: BTreeMap<String, (&'static str, Vec<u8>)>,
+	entry_files: EntryFiles
+}
+impl PackedAddon {
+	fn read(dir: &Path) -> Result<PackedAddon, UnpackingError> {
+		let mut addon = PackedAddon {
+			files: BTreeMap::new(),
+			entry_files: EntryFiles::default()
+		};
+
+		for pack_dir in PackDir::discover(&dir.join("lua"))? {
+			if let Some(loader) = &pack_dir.loader {
+				let entry_files = EntryFiles::read_loader(loader)?;
+				addon.entry_files.sv.extend(entry_files.sv);
+				addon.entry_files.cl.extend(entry_files.cl);
+				addon.entry_files.sh.extend(entry_files.sh);
+			}
+
+			let (sv, cl, sh) = pack_dir.read_packs()?;
+			for (realm, pack) in [("sv", sv), ("cl", Some(cl)), ("sh", Some(sh))] {
+				for entry in pack.into_iter().flat_map(|pack| pack.entries) {
+					addon.files.insert(entry.path, (realm, entry.contents));
+				}
+			}
+		}
+
+		Ok(addon)
+	}
+}
+
+pub struct Differ;
+impl Differ {
+	/// Compares the Lua files and entry files of two packed addons.
+	pub async fn diff(old: PathBuf, new: PathBuf, options: DiffOptions) -> Result<DiffReport, UnpackingError> {
+		tokio::task::spawn_blocking(move || {
+			let started = std::time::Instant::now();
+
+			let (old, new) = (PackedAddon::read(&old)?, PackedAddon::read(&new)?);
+
+			let mut report = DiffReport::default();
+
+			for (path, (old_realm, old_contents)) in old.files.iter() {
+				match new.files.get(path) {
+					Some((new_realm, new_contents)) => {
+						let modified = old_contents != new_contents;
+						if modified || old_realm != new_realm {
+							report.files.push(FileDiff {
+								path: path.clone(),
+								old_realm: Some(old_realm),
+								new_realm: Some(new_realm),
+								modified,
+								patch: if modified && options.patch { Some(unified_diff(path, old_contents, new_contents, options.context)) } else { None }
+							});
+						}
+					},
+					None => report.files.push(FileDiff { path: path.clone(), old_realm: Some(old_realm), new_realm: None, modified: false, patch: None })
+				}
+			}
+			for (path, (new_realm, _)) in new.files.iter().filter(|(path, _)| !old.files.contains_key(*path)) {
+				report.files.push(FileDiff { path: path.clone(), old_realm: None, new_realm: Some(new_realm), modified: false, patch: None });
+			}
+			report.files.sort_by(|a, b| a.path.cmp(&b.path));
+
+			for (realm, old_entry_files, new_entry_files) in [("sv", &old.entry_files.sv, &new.entry_files.sv), ("cl", &old.entry_files.cl, &new.entry_files.cl), ("sh", &old.entry_files.sh, &new.entry_files.sh)] {
+				let mut entry_files = old_entry_files.iter().filter(|path| !new_entry_files.contains(path)).map(|path| EntryFileDiff { realm, path: path.clone(), added: false })
+					.chain(new_entry_files.iter().filter(|path| !old_entry_files.contains(path)).map(|path| EntryFileDiff { realm, path: path.clone(), added: true }))
+					.collect::<Vec<_>>();
+				entry_files.sort_by(|a, b| a.path.cmp(&b.path));
+				report.entry_files.extend(entry_files);
+			}
+
+			report.elapsed = started.elapsed();
+			Ok(report)
+		}).await.expect("Failed to join thread")
+	}
+}
+
+/// A line of the old or new file in a line diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+	/// Indices of a line that's in both files
+	Equal(usize, usize),
+	Delete(usize),
+	Insert(usize)
+}
+
+/// Finds the shortest line diff between `old` and `new` with Myers' algorithm.
+fn edits<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Edit> {
+	// Most changes are small, so skip the common prefix and suffix before searching
+	let prefix = old.iter().zip(new.iter()).take_while(|(old, new)| old == new).count();
+	let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(old, new)| old == new).count();
+	let (a, b) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+	let (n, m) = (a.len() as isize, b.len() as isize);
+	let offset = n + m + 1;
+	let index = |k: isize| (k + offset) as usize;
+
+	// The furthest x reached on each diagonal k = x - y, saved before every round for backtracking
+	let mut v = vec![0isize; (2 * offset + 1) as usize];
+	let mut trace = vec![];
+	'search: for d in 0..=(n + m) {
+		trace.push(v.clone());
+		for k in (-d..=d).step_by(2) {
+			let mut x = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) { v[index(k + 1)] } else { v[index(k - 1)] + 1 };
+			let mut y = x - k;
+			while x < n && y < m && a[x as usize] == b[y as usize] {
+				x += 1;
+				y += 1;
+			}
+			v[index(k)] = x;
+			if x >= n && y >= m {
+				break 'search;
+			}
+		}
+	}
+
+	let mut middle = vec![];
+	let (mut x, mut y) = (n, m);
+	for (d, v) in trace.iter().enumerate().rev() {
+		let (d, k) = (d as isize, x - y);
+		let prev_k = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) { k + 1 } else { k - 1 };
+		let prev_x = v[index(prev_k)];
+		let prev_y = prev_x - prev_k;
+
+		while x > prev_x && y > prev_y {
+			x -= 1;
+			y -= 1;
+			middle.push(Edit::Equal(x as usize + prefix, y as usize + prefix));
+		}
+		if d > 0 {
+			if x == prev_x {
+				middle.push(Edit::Insert(prev_y as usize + prefix));
+			} else {
+				middle.push(Edit::Delete(prev_x as usize + prefix));
+			}
+		}
+		x = prev_x;
+		y = prev_y;
+	}
+
+	(0..prefix).map(|i| Edit::Equal(i, i))
+		.chain(middle.into_iter().rev())
+		.chain((0..suffix).map(|i| Edit::Equal(old.len() - suffix + i, new.len() - suffix + i)))
+		.collect()
+}
+
+/// Returns a unified diff of two versions of a file, with `context` unchanged lines around each change.
+pub fn unified_diff(path: &str, old: &[u8], new: &[u8], context: usize) -> String {
+	let (old_lines, new_lines) = (old.split_inclusive(|byte| *byte == b'\n').collect::<Vec<_>>(), new.split_inclusive(|byte| *byte == b'\n').collect::<Vec<_>>());
+	let edits = edits(&old_lines, &new_lines);
+
+	// Group changes that are close enough for their context to overlap
+	let mut hunks: Vec<std::ops::Range<usize>> = vec![];
+	for (i, _) in edits.iter().enumerate().filter(|(_, edit)| !matches!(edit, Edit::Equal(..))) {
+		let (start, end) = (i.saturating_sub(context), (i + 1 + context).min(edits.len()));
+		match hunks.last_mut() {
+			Some(hunk) if start <= hunk.end => hunk.end = end,
+			_ => hunks.push(start..end)
+		}
+	}
+
+	let mut diff = format!("--- a/{}\n+++ b/{}\n", path, path);
+
+	// Where each edit starts in the old and new file
+	let mut positions = Vec::with_capacity(edits.len());
+	let (mut old_line, mut new_line) = (0, 0);
+	for edit in edits.iter() {
+		positions.push((old_line, new_line));
+		match edit {
+			Edit::Equal(..) => { old_line += 1; new_line += 1; },
+			Edit::Delete(_) => old_line += 1,
+			Edit::Insert(_) => new_line += 1
+		}
+	}
+
+	for hunk in hunks {
+		let edits = &edits[hunk.clone()];
+		let old_len = edits.iter().filter(|edit| !matches!(edit, Edit::Insert(_))).count();
+		let new_len = edits.iter().filter(|edit| !matches!(edit, Edit::Delete(_))).count();
+		let (old_start, new_start) = positions[hunk.start];
+		diff.push_str(&format!("@@ -{},{} +{},{} @@\n", old_start + if old_len == 0 { 0 } else { 1 }, old_len, new_start + if new_len == 0 { 0 } else { 1 }, new_len));
+
+		for edit in edits {
+			let (prefix, line) = match *edit {
+				Edit::Equal(i, _) => (' ', old_lines[i]),
+				Edit::Delete(i) => ('-', old_lines[i]),
+				Edit::Insert(i) => ('+', new_lines[i])
+			};
+			diff.push(prefix);
+			diff.push_str(&String::from_utf8_lossy(line));
+			if !line.ends_with(b"\n") {
+				diff.push_str("\n\\ No newline at end of file\n");
+			}
+		}
+	}
+
+	diff
+}
@@ -0,0 +1,226 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use crate::{util, classify, config::{Config, ConfigOverrides, GlobPattern, Uncovered, Workspace}, pack::{Packer, PackingError, PACKED_FILES}};
+
+pub use crate::classify::Decision;
+
+/// Options for [`Explainer::explain`].
+#[derive(Debug, Clone, Default)]
+pub struct ExplainOptions {
+	config: Option<Config>,
+	overrides: ConfigOverrides
+}
+impl ExplainOptions {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Explains this config, rather than the addon's config file.
+	pub fn config(mut self, config: Config) -> Self {
+		self.config = Some(config);
+		self
+	}
+
+	/// Overrides options of the config, as [`PackOptions::overrides`](crate::PackOptions::overrides) would when packing.
+	pub fn overrides(mut self, overrides: ConfigOverrides) -> Self {
+		self.overrides = overrides;
+		self
+	}
+}
+
+/// A config pattern that matched a Lua file.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct MatchedPattern {
+	/// The config option the pattern is in, such as `include_sv`.
+	pub option: &'static str,
+
+	pub pattern: String
+}
+
+/// A Lua file and why it lands in its realm.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ExplainedFile {
+	/// Path of the file, relative to `lua/`.
+	pub path: String,
+
+	/// The addon the file is in, when explaining a workspace.
+	pub addon: Option<String>,
+
+	/// Every pattern that matched the file, in config order.
+	pub matched: Vec<MatchedPattern>,
+
+	pub decision: Decision,
+
+	/// The realm the file is packed in (or would be, if it was pruned). For conflicts, every realm the file matched.
+	pub realms: Vec<&'static str>,
+
+	/// The realms the loader runs this file in as an entry file.
+	pub entry: Vec<&'static str>,
+
+	/// How `auto_realms`, `realm_conflicts` and `prune_unreachable` affected the file, if they did.
+	pub notes: Vec<String>
+}
+impl ExplainedFile {
+	/// The realm the file is packed in, if it's packed.
+	pub fn realm(&self) -> Option<&'static str> {
+		match self.decision {
+			Decision::Packed => self.realms.first().copied(),
+			_ => None
+		}
+	}
+
+	/// Describes the decision, e.g. `packed sv, entry file`.
+	pub fn describe(&self) -> String {
+		match self.decision {
+			Decision::Packed => {
+				let realm = self.realms.join(" and ");
+				match self.entry.as_slice() {
+					[] => format!("packed {}", realm),
+					entry if entry == self.realms.as_slice() => format!("packed {}, entry file", realm),
+					entry => format!("packed {}, entry file of {}", realm, entry.join(" and "))
+				}
+			},
+			Decision::Excluded => "excluded, copied as it is".to_string(),
+			Decision::Pruned => format!("not packed, pruned from {} by prune_unreachable", self.realms.join(" and ")),
+			Decision::Conflict => format!("realm conflict between {}, packing fails as realm_conflicts is \"error\"", self.realms.join(" and ")),
+			Decision::Untouched => "left untouched, copied as it is".to_string()
+		}
+	}
+}
+
+/// The result of [`Explainer::explain`].
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct ExplainReport {
+	/// Every Lua file in the addon (or in each addon of a workspace), ordered by path.
+	pub files: Vec<ExplainedFile>
+}
+impl ExplainReport {
+	/// Finds a file by its path relative to `lua/`.
+	pub fn file(&self, path: &str) -> Option<&ExplainedFile> {
+		self.files.iter().find(|file| file.path == path)
+	}
+
+	pub fn count(&self, decision: Decision) -> usize {
+		self.files.iter().filter(|file| file.decision == decision).count()
+	}
+}
+
+/// An addon's Lua files (paths relative to `lua/` and their contents) and its own config.
+struct Addon {
+	/// The addon's path in the workspace, or `None` when explaining a single addon.
+	name: Option<String>,
+
+	lua_files: BTreeMap<String, Vec<u8>>,
+	config: Config
+}
+
+pub struct Explainer;
+impl Explainer {
+	/// Explains which of the config's patterns match each Lua file of an addon, and what packing does with it.
+	///
+	/// Files are collected exactly as [`Packer::pack`] would collect them, including `auto_realms`, `realm_conflicts` and `prune_unreachable`, but nothing is written.
+	pub async fn explain(dir: PathBuf, options: ExplainOptions) -> Result<ExplainReport, PackingError> {
+		let config = match options.config {
+			Some(config) => config,
+			None => Packer::read_config(&dir, true)?
+		};
+		let config = options.overrides.apply(config)?;
+
+		tokio::task::spawn_blocking(move || {
+			let lua_files = util::read_lua_files(&dir.join("lua"))?;
+			Ok(Explainer::explain_files(vec![Addon { name: None, lua_files, config: config.clone() }], &config))
+		}).await.expect("Failed to join thread")
+	}
+
+	/// Explains the Lua files of every addon in the `gluapack-workspace.json` in `dir`, as [`Packer::pack_workspace`] would pack them.
+	///
+	/// Each addon's own config decides which of its files are packed, and `prune_unreachable` comes from the workspace (or [`ExplainOptions::config`] if set).
+	pub async fn explain_workspace(dir: PathBuf, options: ExplainOptions) -> Result<ExplainReport, PackingError> {
+		let workspace = Workspace::read(dir.join(Workspace::FILE_NAME))?;
+
+		let mut sources = Vec::with_capacity(workspace.addons.len());
+		for addon in workspace.addons.iter() {
+			let addon_dir = dir.join(addon);
+			if !addon_dir.join("lua").is_dir() {
+				return Err(error!(PackingError::InvalidWorkspaceAddon(addon_dir)));
+			}
+			let addon_config = options.overrides.apply(Packer::read_config(&addon_dir, true)?)?;
+			sources.push((addon.to_string_lossy().replace('\\', "/"), addon_dir, addon_config));
+		}
+
+		let config = options.overrides.apply(options.config.unwrap_or(workspace.config))?;
+
+		tokio::task::spawn_blocking(move || {
+			let mut addons = Vec::with_capacity(sources.len());
+			for (name, addon_dir, addon_config) in sources {
+				addons.push(Addon { name: Some(name), lua_files: util::read_lua_files(&addon_dir.join("lua"))?, config: addon_config });
+			}
+			Ok(Explainer::explain_files(addons, &config))
+		}).await.expect("Failed to join thread")
+	}
+
+	/// Explains the Lua files of each addon with its own config. `config` is the config of the pack, which decides `prune_unreachable`.
+	fn explain_files(addons: Vec<Addon>, config: &Config) -> ExplainReport {
+		let packed_files = PACKED_FILES.iter().map(|pattern| GlobPattern::new(pattern)).collect::<Vec<_>>();
+
+		let mut files = vec![];
+		let mut classified = Vec::with_capacity(addons.len());
+		for addon in addons.iter() {
+			let classification = classify::classify(&addon.lua_files, &addon.config);
+			let options = addon.config.patterns();
+
+			for file in classification.files.iter() {
+				let path = &file.path;
+				let mut matched = options.iter()
+					.flat_map(|(option, patterns)| patterns.iter().filter(|pattern| pattern.matches(path)).map(move |pattern| MatchedPattern { option, pattern: pattern.as_str().to_string() }))
+					.collect::<Vec<_>>();
+				matched.extend(packed_files.iter().filter(|pattern| pattern.matches(path)).map(|pattern| MatchedPattern { option: "exclude", pattern: pattern.as_str().to_string() }));
+
+				let mut notes = file.notes.clone();
+				if file.decision == Decision::Untouched && addon.config.uncovered == Uncovered::Error {
+					notes.push("packing fails as uncovered is \"error\"".to_string());
+				}
+
+				files.push(ExplainedFile {
+					path: path.clone(),
+					addon: addon.name.clone(),
+					matched,
+					decision: file.decision,
+					realms: file.realms.clone(),
+					entry: file.entry.clone(),
+					notes
+				});
+			}
+
+			classified.push((addon, classification));
+		}
+
+		// Unreachable files, from the packed files of every addon
+		let entry_files = |realm: &str| classified.iter().flat_map(|(addon, classification)| {
+			let patterns = match realm {
+				"sv" => &addon.config.entry_sv,
+				"cl" => &addon.config.entry_cl,
+				_ => &addon.config.entry_sh
+			};
+			classification.entry_files(realm, patterns)
+		}).collect::<Vec<_>>();
+		let packed = classified.iter().flat_map(|(addon, classification)| {
+			classification.files.iter().filter(|file| file.decision == Decision::Packed).map(move |file| (file.path.as_str(), addon.lua_files[&file.path].as_slice()))
+		});
+		let (unreachable, _) = classify::find_unreachable(packed, &entry_files("sv"), &entry_files("cl"), &entry_files("sh"));
+
+		for file in files.iter_mut().filter(|file| file.decision == Decision::Packed && unreachable.binary_search(&file.path).is_ok()) {
+			if config.prune_unreachable && file.realm() != Some("sv") {
+				file.decision = Decision::Pruned;
+				file.notes.push("it can't be reached from any entry file".to_string());
+			} else {
+				file.notes.push("it can't be reached from any entry file (see prune_unreachable)".to_string());
+			}
+		}
+
+		// Keeps the addons of a workspace in order for files at the same path
+		files.sort_by(|a, b| a.path.cmp(&b.path));
+
+		ExplainReport { files }
+	}
+}
@@ -0,0 +1,164 @@
+//! The gluapack pack format header.
+//!
+//! Every serverside pack and the first chunk of every clientside/shared pack starts with:
+//!
+//! `GLUAPACK` `<version as 2 hex digits>` `<flags as 2 hex digits>` `\n`
+//!
+//! The header is plain ASCII (no NUL bytes) so that it survives being networked to clients. Packs without a header were produced by gluapack 0.3.0 or older and are treated as version 0.
+//!
+//! If [`FLAG_CHECKSUMS`] is set, the header is followed by the number of files in the pack and every entry carries a [`FileHash`] of its contents.
+//!
+//! If [`FLAG_ESCAPED_LINES`] is set, the pack's chunks were commented out with [`chunking::commentify`](crate::chunking::commentify)'s escapes and must be read with [`chunking::uncommentify`](crate::chunking::uncommentify). If [`FLAG_LONG_STRING_CHUNKS`] is set, each chunk is a [`chunking::long_comment`](crate::chunking::long_comment) instead, which starts with `--[`.
+//!
+//! If [`FLAG_DEDUP`] is set, entries whose contents are identical to another packed file's may be stored as a reference to that file instead. In clientside/shared packs the length field of a reference is `*<path>`, and in serverside packs it is [`DEDUP_SV_LEN`] followed by the hash and then the NUL-terminated path. References only ever point to files the loader unpacks on the same side: shared or clientside files, or for serverside packs any file in the same `gluapack/<unique_id>/` directory.
+
+use std::{convert::TryInto, io::{Read, Seek, SeekFrom}};
+use sha2::Digest;
+
+use crate::unpack::UnpackingError;
+
+/// Magic bytes at the start of every pack.
+pub const MAGIC: &[u8; 8] = b"GLUAPACK";
+
+/// The pack format version written by this version of gluapack.
+pub const FORMAT_VERSION: u8 = 1;
+
+/// The pack records its file count and a hash of every file.
+pub const FLAG_CHECKSUMS: u8 = 0x01;
+
+/// Lines of the pack's chunks that start with `[` or `-` are escaped, and bare `\r` line breaks are commented out too (clientside/shared packs only).
+pub const FLAG_ESCAPED_LINES: u8 = 0x02;
+
+/// The pack's chunks are each wrapped in a long comment rather than commented line by line (clientside/shared packs only).
+pub const FLAG_LONG_STRING_CHUNKS: u8 = 0x04;
+
+/// Some of the pack's entries are references to another packed file with identical contents.
+pub const FLAG_DEDUP: u8 = 0x08;
+
+/// Bitmask of every flag understood by this version of gluapack.
+pub const SUPPORTED_FLAGS: u8 = FLAG_CHECKSUMS | FLAG_ESCAPED_LINES | FLAG_LONG_STRING_CHUNKS | FLAG_DEDUP;
+
+/// Marks the length field of a clientside/shared entry as a reference. The referenced path follows it.
+pub const DEDUP_MARKER: u8 = b'*';
+
+/// The length field of a serverside entry that is a reference.
+pub const DEDUP_SV_LEN: u32 = u32::MAX;
+
+/// Truncated SHA-256 hash of a packed file's contents.
+pub type FileHash = [u8; 20];
+
+pub fn hash_file(contents: &[u8]) -> FileHash {
+	let mut sha256 = sha2::Sha256::new();
+	sha256.update(contents);
+	sha256.finalize()[0..20].try_into().unwrap()
+}
+
+/// The name Garry's Mod gives a networked Lua file in the client's Lua cache (`cache/lua/<hash>.lua`), as recorded in `manifest.lua`.
+pub fn hash_chunk(chunk: &[u8]) -> FileHash {
+	let mut sha256 = sha2::Sha256::new();
+	sha256.update(chunk);
+	sha256.update([0u8]);
+	sha256.finalize()[0..20].try_into().unwrap()
+}
+
+pub fn hash_to_hex(hash: &[u8]) -> String {
+	let mut hex = String::with_capacity(hash.len() * 2);
+	for byte in hash {
+		hex.push_str(&format!("{:02x}", byte));
+	}
+	hex
+}
+
+pub fn hash_from_hex(hex: &[u8]) -> Option<FileHash> {
+	if hex.len() != 40 {
+		return None;
+	}
+	let mut hash = [0u8; 20];
+	for (byte, hex) in hash.iter_mut().zip(hex.chunks(2)) {
+		*byte = u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?;
+	}
+	Some(hash)
+}
+
+/// Length of the header in bytes.
+pub const HEADER_LEN: usize = MAGIC.len() + 2 + 2 + 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackHeader {
+	pub version: u8,
+	pub flags: u8
+}
+impl Default for PackHeader {
+	fn default() -> Self {
+		PackHeader {
+			version: FORMAT_VERSION,
+			flags: 0
+		}
+	}
+}
+impl PackHeader {
+	/// The header of packs produced before the pack format was versioned.
+	pub const LEGACY: PackHeader = PackHeader { version: 0, flags: 0 };
+
+	pub fn with_flags(flags: u8) -> Self {
+		PackHeader {
+			version: FORMAT_VERSION,
+			flags
+		}
+	}
+
+	#[inline]
+	pub fn has_flag(&self, flag: u8) -> bool {
+		self.flags & flag == flag
+	}
+
+	pub fn to_bytes(self) -> [u8; HEADER_LEN] {
+		let mut header = [0u8; HEADER_LEN];
+		header[..MAGIC.len()].copy_from_slice(MAGIC);
+		header[MAGIC.len()..HEADER_LEN - 1].copy_from_slice(format!("{:02x}{:02x}", self.version, self.flags).as_bytes());
+		header[HEADER_LEN - 1] = b'\n';
+		header
+	}
+
+	/// Reads the header at the current position of `r`.
+	///
+	/// If there is no header, the reader is rewound and [`PackHeader::LEGACY`] is returned.
+	pub fn read<R: Read + Seek>(r: &mut R) -> Result<PackHeader, UnpackingError> {
+		let start = r.stream_position()?;
+
+		let mut header = [0u8; HEADER_LEN];
+		let mut read = 0;
+		while read < HEADER_LEN {
+			match r.read(&mut header[read..])? {
+				0 => break,
+				n => read += n
+			}
+		}
+
+		if read < MAGIC.len() || &header[..MAGIC.len()] != MAGIC {
+			r.seek(SeekFrom::Start(start))?;
+			return Ok(PackHeader::LEGACY);
+		}
+
+		let fields = &header[MAGIC.len()..HEADER_LEN - 1];
+		if read < HEADER_LEN || header[HEADER_LEN - 1] != b'\n' || !fields.iter().all(u8::is_ascii_hexdigit) {
+			return Err(error!(UnpackingError::InvalidHeader));
+		}
+
+		// Only ASCII hex digits, so every byte is a char
+		let fields = std::str::from_utf8(fields)?;
+		let header = PackHeader {
+			version: u8::from_str_radix(&fields[0..2], 16)?,
+			flags: u8::from_str_radix(&fields[2..4], 16)?
+		};
+
+		if header.version > FORMAT_VERSION {
+			return Err(error!(UnpackingError::UnsupportedFormatVersion(header.version)));
+		}
+		if header.flags & !SUPPORTED_FLAGS != 0 {
+			return Err(error!(UnpackingError::UnsupportedFormatFlags(header.flags)));
+		}
+
+		Ok(header)
+	}
+}
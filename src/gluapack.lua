@@ -3,19 +3,23 @@
 -- If you want to contribute changes to this loader, please do so here:
 -- https://github.com/WilliamVenner/gluapack
 
+local ENTRY_FILES_SH = {ENTRY_FILES_SH}
+local ENTRY_FILES_CL = {ENTRY_FILES_CL}
+local ENTRY_FILES_SV = {ENTRY_FILES_SV}
+
 local function includeEntryFiles()
-	for _, v in ipairs({ENTRY_FILES_SH}) do
+	for _, v in ipairs(ENTRY_FILES_SH) do
 		AddCSLuaFile(v)
 		include(v)
 	end
 
 	if CLIENT then
-		for _, v in ipairs({ENTRY_FILES_CL}) do
+		for _, v in ipairs(ENTRY_FILES_CL) do
 			AddCSLuaFile(v)
 			include(v)
 		end
 	else
-		for _, v in ipairs({ENTRY_FILES_SV}) do
+		for _, v in ipairs(ENTRY_FILES_SV) do
 			include(v)
 		end
 	end
@@ -94,8 +98,103 @@ local clientsideFiles = {}
 local GLUAPACK_CURRENT_CHUNK
 local GLUAPACK_IS_CHUNK_NETWORKED = CLIENT and true or nil
 local TERMINATOR_HACK = string.byte("|")
+local FORMAT_MAGIC = "GLUAPACK"
+local FORMAT_VERSION = {FORMAT_VERSION}
+local FORMAT_SUPPORTED_FLAGS = {FORMAT_SUPPORTED_FLAGS}
+local FORMAT_FLAG_CHECKSUMS = {FORMAT_FLAG_CHECKSUMS}
+local FORMAT_FLAG_ESCAPED_LINES = {FORMAT_FLAG_ESCAPED_LINES}
+local FORMAT_FLAG_DEDUP = {FORMAT_FLAG_DEDUP}
+local DEDUP_SV_LEN = 4294967295
+
+-- Deduplicated files of the current pack directory and the files they refer to, copied once all of its packs are unpacked
+local duplicates = {}
+
+-- How the current pack's chunks were commented out ("unescaped", "lines" or "long_string"), decided by its first chunk
+local chunkFormat
+
+-- Strips the comments from a networked chunk
+local function uncommentChunk(chunk)
+	if chunkFormat == nil then
+		if chunk:find("^%-%-%[=*%[") then
+			chunkFormat = "long_string"
+		else
+			local flags = chunk:match("^%-%-" .. FORMAT_MAGIC .. "%x%x(%x%x)\n")
+			chunkFormat = (flags ~= nil and bit.band(tonumber(flags, 16), FORMAT_FLAG_ESCAPED_LINES) ~= 0) and "lines" or "unescaped"
+		end
+	end
+
+	if chunkFormat == "long_string" then
+		-- The whole chunk is wrapped in --[==[ ]==]
+		local level = #chunk:match("^%-%-%[(=*)%[")
+		return chunk:sub(level + 5, #chunk - level - 2)
+	elseif chunkFormat == "lines" then
+		-- Lines start with -- plus an escape if the line starts with [ or -, and bare \r line breaks are commented too
+		return (("\n" .. chunk):gsub("([\r\n])%-%-%-?", "%1"):sub(2))
+	else
+		return (chunk:sub(3):gsub("\n%-%-", "\n"))
+	end
+end
+
+local function readHeader()
+	local magic = GLUAPACK_CURRENT_CHUNK:Read(#FORMAT_MAGIC)
+	if magic ~= FORMAT_MAGIC then
+		GLUAPACK_CURRENT_CHUNK:Seek(0)
+		return 0, 0
+	end
+
+	local version = tonumber(GLUAPACK_CURRENT_CHUNK:Read(2), 16)
+	local flags = tonumber(GLUAPACK_CURRENT_CHUNK:Read(2), 16)
+	GLUAPACK_CURRENT_CHUNK:Skip(1) -- \n
+	return version, flags
+end
+
+-- Reads bytes up to (and discards) the next TERMINATOR_HACK, yielding for the next chunk if necessary
+local function readTerminated()
+	local bytes = {}
+	while true do
+		local byte = GLUAPACK_CURRENT_CHUNK:ReadByte()
+		if byte == TERMINATOR_HACK then
+			break
+		else
+			bytes[#bytes + 1] = string.char(byte)
+		end
+		if GLUAPACK_CURRENT_CHUNK:EndOfFile() then
+			coroutine.yield()
+		end
+	end
+	return table.concat(bytes)
+end
+
 local function processChunk()
-	while not GLUAPACK_CURRENT_CHUNK:EndOfFile() do
+	local version, flags = readHeader()
+	if version == nil or flags == nil then
+		ErrorNoHalt("gluapack: corrupt pack header, skipping pack\n")
+		return
+	elseif version > FORMAT_VERSION then
+		ErrorNoHalt(("gluapack: pack format version %d is newer than this loader supports (%d), skipping pack\n"):format(version, FORMAT_VERSION))
+		return
+	elseif bit.band(flags, bit.bnot(FORMAT_SUPPORTED_FLAGS)) ~= 0 then
+		ErrorNoHalt(("gluapack: pack uses unsupported format flags (%02x), skipping pack\n"):format(flags))
+		return
+	end
+
+	local checksums = bit.band(flags, FORMAT_FLAG_CHECKSUMS) ~= 0
+	local dedup = bit.band(flags, FORMAT_FLAG_DEDUP) ~= 0
+	if checksums then
+		-- Skip the file count, it's only used by `gluapack verify`
+		if GLUAPACK_IS_CHUNK_NETWORKED then
+			readTerminated()
+		else
+			GLUAPACK_CURRENT_CHUNK:ReadULong()
+		end
+	end
+
+	while true do
+		-- Chunks can end between two files, in which case the next file starts in the next chunk
+		if GLUAPACK_CURRENT_CHUNK:EndOfFile() then
+			coroutine.yield()
+		end
+
 		local terminator = GLUAPACK_IS_CHUNK_NETWORKED and TERMINATOR_HACK or 0
 
 		-- Read path
@@ -122,35 +221,52 @@ local function processChunk()
 
 		file.CreateDir((path:gsub("/[^/]-$", "")))
 
-		local remaining
+		local remaining, duplicateOf
 		if GLUAPACK_IS_CHUNK_NETWORKED then
-			remaining = {}
-			while true do
-				local byte = GLUAPACK_CURRENT_CHUNK:ReadByte()
-				if byte == TERMINATOR_HACK then
-					break
-				else
-					remaining[#remaining + 1] = string.char(byte)
-				end
-				if GLUAPACK_CURRENT_CHUNK:EndOfFile() then
-					coroutine.yield()
-				end
+			local len = readTerminated()
+			if dedup and len:sub(1, 1) == "*" then
+				duplicateOf = len:sub(2)
+			else
+				remaining = tonumber(len, 16)
+			end
+			if checksums then
+				-- Skip the file's hash, it's only used by `gluapack verify`
+				readTerminated()
 			end
-			remaining = tonumber(table.concat(remaining), 16)
 		else
 			remaining = GLUAPACK_CURRENT_CHUNK:ReadULong()
+			if checksums then
+				GLUAPACK_CURRENT_CHUNK:Skip(20)
+			end
+			if dedup and remaining == DEDUP_SV_LEN then
+				duplicateOf = {}
+				while true do
+					local byte = GLUAPACK_CURRENT_CHUNK:ReadByte()
+					if byte == 0 then
+						break
+					else
+						duplicateOf[#duplicateOf + 1] = string.char(byte)
+					end
+				end
+				duplicateOf = table.concat(duplicateOf)
+			end
 		end
-		while true do
-			local readBytes = math.min(remaining, GLUAPACK_CURRENT_CHUNK:Size() - GLUAPACK_CURRENT_CHUNK:Tell())
-			file.Append(path, GLUAPACK_CURRENT_CHUNK:Read(readBytes))
-			remaining = remaining - readBytes
 
-			if GLUAPACK_CURRENT_CHUNK:EndOfFile() then
-				coroutine.yield()
-			elseif remaining <= 0 then
-				assert(remaining == 0)
-				break
+		if duplicateOf then
+			-- The file it refers to may not have been unpacked yet
+			duplicates[#duplicates + 1] = { path, ("gluapack/vfs/%s.txt"):format(duplicateOf) }
+		else
+			file.Write(path, "")
+			while remaining > 0 do
+				if GLUAPACK_CURRENT_CHUNK:EndOfFile() then
+					coroutine.yield()
+				end
+
+				local readBytes = math.min(remaining, GLUAPACK_CURRENT_CHUNK:Size() - GLUAPACK_CURRENT_CHUNK:Tell())
+				file.Append(path, GLUAPACK_CURRENT_CHUNK:Read(readBytes))
+				remaining = remaining - readBytes
 			end
+			assert(remaining == 0)
 		end
 	end
 end
@@ -181,7 +297,7 @@ if CLIENT then
 		chunk = util.Decompress(chunk)
 
 		-- Strip comments
-		chunk = chunk:sub(3, #chunk - 1):gsub("\n%-%-", "\n")
+		chunk = uncommentChunk(chunk:sub(1, #chunk - 1))
 
 		-- Write to our temp file "buffer"
 		file.Write("gluapack-temp.dat", chunk)
@@ -203,7 +319,7 @@ else
 			local chunk = file_Read(path, "LUA")
 
 			-- Strip comments
-			chunk = chunk:sub(3):gsub("\n%-%-", "\n")
+			chunk = uncommentChunk(chunk)
 
 			-- Write to our temp file "buffer"
 			file.Write("gluapack-temp.dat", chunk)
@@ -227,6 +343,7 @@ end
 
 local function resetUnpacker()
 	co = coroutine.create(processChunk)
+	chunkFormat = nil
 	if GLUAPACK_CURRENT_CHUNK then
 		GLUAPACK_CURRENT_CHUNK:Close()
 		GLUAPACK_CURRENT_CHUNK = nil
@@ -288,6 +405,11 @@ local function gluaunpack(path)
 			resetUnpacker()
 		end
 	end
+
+	for _, duplicate in ipairs(duplicates) do
+		file.Write(duplicate[1], file_Read(duplicate[2], "DATA") or "")
+	end
+	duplicates = {}
 end
 for _, d in ipairs(select(2, file_Find("gluapack/*", "LUA"))) do
 	gluaunpack(("gluapack/%s/"):format(d))
@@ -0,0 +1,236 @@
+// Proposes a gluapack.json for an existing addon from Garry's Mod's conventions
+
+use std::{collections::BTreeMap, path::{Path, PathBuf}};
+
+use crate::{util, analysis::{CallKind, IncludeGraph}, config::Config, pack::PackingError};
+
+const REALMS: [&str; 3] = ["sv", "cl", "sh"];
+
+/// Why [`Initializer::init`] put a Lua file in its realm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+	/// The file is in a folder Garry's Mod runs the files of, such as `autorun/client/`, so it's an entry file
+	EntryFolder,
+
+	/// The file is named after its realm, such as `cl_hud.lua` or `hud.cl.lua`
+	Name,
+
+	/// The file is reached from the entry files by `include` and `AddCSLuaFile` calls
+	Included,
+
+	/// The file sends itself to clients with `AddCSLuaFile()`, so it's shared
+	AddCSLuaFile
+}
+impl Reason {
+	fn describe(self) -> &'static str {
+		match self {
+			Reason::EntryFolder => "run by Garry's Mod from this folder",
+			Reason::Name => "named after their realm",
+			Reason::Included => "included from the entry files",
+			Reason::AddCSLuaFile => "sent to clients by their own AddCSLuaFile()"
+		}
+	}
+}
+
+/// A Lua file found by [`Initializer::init`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct InitFile {
+	/// Path of the file, relative to `lua/`.
+	pub path: String,
+
+	/// The realm the file was put in, or `None` if it couldn't be classified.
+	pub realm: Option<&'static str>,
+
+	/// Whether the file is proposed as an entry file of its realm.
+	pub entry: bool,
+
+	pub reason: Option<Reason>
+}
+
+/// A pattern proposed by [`Initializer::init`].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ProposedPattern {
+	pub pattern: String,
+	pub reason: Reason,
+
+	/// Number of Lua files the pattern matches.
+	pub files: usize
+}
+
+/// The result of [`Initializer::init`].
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct InitReport {
+	/// Every Lua file in the addon, ordered by path.
+	pub files: Vec<InitFile>,
+
+	pub include_sh: Vec<ProposedPattern>,
+	pub include_cl: Vec<ProposedPattern>,
+	pub include_sv: Vec<ProposedPattern>,
+	pub entry_cl: Vec<ProposedPattern>,
+	pub entry_sh: Vec<ProposedPattern>,
+	pub entry_sv: Vec<ProposedPattern>
+}
+impl InitReport {
+	/// The Lua files that couldn't be classified, which the proposed patterns don't cover.
+	pub fn unclassified(&self) -> impl Iterator<Item = &InitFile> {
+		self.files.iter().filter(|file| file.realm.is_none())
+	}
+
+	/// Returns the number of Lua files classified into a realm.
+	pub fn files_in(&self, realm: &str) -> usize {
+		self.files.iter().filter(|file| file.realm == Some(realm)).count()
+	}
+
+	/// Renders the proposed patterns as a commented `gluapack.json`. Every other option is left at its default.
+	pub fn config_json(&self) -> String {
+		let mut json = String::new();
+		json.push_str("{\n");
+		json.push_str("    // Generated by `gluapack init`. Every other option is left at its default, see the README for the full list.\n");
+		json.push_str("    // Each pattern is followed by the number of Lua files it matched and why they're in that realm.\n");
+
+		let groups = [
+			("Lua files packed into each realm.", [("include_sh", &self.include_sh), ("include_cl", &self.include_cl), ("include_sv", &self.include_sv)]),
+			("Entry files - these files will be executed immediately after being unpacked.", [("entry_cl", &self.entry_cl), ("entry_sh", &self.entry_sh), ("entry_sv", &self.entry_sv)])
+		];
+		for (i, (comment, fields)) in groups.iter().enumerate() {
+			json.push_str(&format!("\n    // {}\n", comment));
+			for (j, (field, patterns)) in fields.iter().enumerate() {
+				let comma = if i + 1 == groups.len() && j + 1 == fields.len() { "" } else { "," };
+				if patterns.is_empty() {
+					json.push_str(&format!("    \"{}\": []{}\n", field, comma));
+					continue;
+				}
+				json.push_str(&format!("    \"{}\": [\n", field));
+				for (k, pattern) in patterns.iter().enumerate() {
+					let pattern_comma = if k + 1 == patterns.len() { "" } else { "," };
+					json.push_str(&format!("        {}{} // {} file(s), {}\n", serde_json::to_string(&pattern.pattern).unwrap(), pattern_comma, pattern.files, pattern.reason.describe()));
+				}
+				json.push_str(&format!("    ]{}\n", comma));
+			}
+		}
+
+		let mut unclassified = self.unclassified().peekable();
+		if unclassified.peek().is_some() {
+			json.push_str("\n    // These Lua files couldn't be classified, so they aren't packed and will be copied as they are.\n");
+			json.push_str("    // Add them to the include_* patterns of their realm, or to \"exclude\":\n");
+			for file in unclassified {
+				json.push_str(&format!("    //   {}\n", file.path));
+			}
+		}
+
+		json.push_str("}\n");
+		json
+	}
+}
+
+pub struct Initializer;
+impl Initializer {
+	/// Proposes `include_*` and `entry_*` patterns for an addon without a config, by looking for Garry's Mod's conventions in its Lua files.
+	///
+	/// Files in the folders Garry's Mod runs (such as `autorun/server/`) are entry files of that realm. Other files are classified by their name (`sv_`, `cl_` and `sh_` prefixes, or `.sv.lua`, `.cl.lua` and `.sh.lua` suffixes), then by following `include` and `AddCSLuaFile` calls from the entry files, and finally as shared if they call `AddCSLuaFile()` on themselves.
+	///
+	/// The conventional patterns are only proposed if every file they match is in their realm, otherwise the files are listed by folder or path.
+	pub async fn init(dir: PathBuf) -> Result<InitReport, PackingError> {
+		tokio::task::spawn_blocking(move || Initializer::scan(&dir.join("lua"))).await.expect("Failed to join thread")
+	}
+
+	fn scan(dir: &Path) -> Result<InitReport, PackingError> {
+		let lua_files = util::read_lua_files(dir)?;
+
+		let default = Config::default();
+		let entry_patterns = [&default.entry_sv, &default.entry_cl, &default.entry_sh];
+		let include_patterns = [&default.include_sv, &default.include_cl, &default.include_sh];
+
+		let mut files = lua_files.keys().map(|path| InitFile { path: path.clone(), realm: None, entry: false, reason: None }).collect::<Vec<_>>();
+
+		// Entry folders, then names
+		for file in files.iter_mut() {
+			if let Some(realm) = entry_patterns.iter().position(|patterns| patterns.iter().any(|pattern| pattern.matches(&file.path))) {
+				file.realm = Some(REALMS[realm]);
+				file.entry = true;
+				file.reason = Some(Reason::EntryFolder);
+				continue;
+			}
+
+			let named = include_patterns.iter().enumerate()
+				.filter(|(_, patterns)| patterns.iter().any(|pattern| pattern.matches(&file.path)))
+				.map(|(realm, _)| REALMS[realm])
+				.collect::<Vec<_>>();
+			if let [realm] = named.as_slice() {
+				file.realm = Some(realm);
+				file.reason = Some(Reason::Name);
+			}
+		}
+
+		// Follow include() and AddCSLuaFile() calls from the entry files
+		let entry_files = |realm: &str| files.iter().filter(|file| file.entry && file.realm == Some(realm)).map(|file| file.path.clone()).collect::<Vec<_>>();
+		let (sv_entry_files, cl_entry_files, sh_entry_files) = (entry_files("sv"), entry_files("cl"), entry_files("sh"));
+		let graph = IncludeGraph::build(lua_files.iter().map(|(path, contents)| (path.as_str(), contents.as_slice())));
+		let reached = graph.propagate(&sv_entry_files, &cl_entry_files, &sh_entry_files);
+
+		for file in files.iter_mut().filter(|file| file.realm.is_none()) {
+			if let Some(realm) = reached.get(&file.path).and_then(|realms| realms.realm()) {
+				file.realm = Some(realm);
+				file.reason = Some(Reason::Included);
+			} else if graph.edges.get(&file.path).map(|edges| edges.iter().any(|edge| edge.kind == CallKind::AddCSLuaFile && edge.target == file.path)).unwrap_or(false) {
+				file.realm = Some("sh");
+				file.reason = Some(Reason::AddCSLuaFile);
+			}
+		}
+
+		let mut proposed: [Vec<ProposedPattern>; 3] = Default::default();
+		let mut proposed_entries: [Vec<ProposedPattern>; 3] = Default::default();
+
+		// Conventional patterns, if they don't match any file of another realm
+		let mut covered = vec![false; files.len()];
+		for (realm, (entry_patterns, include_patterns)) in entry_patterns.iter().zip(include_patterns.iter()).enumerate() {
+			for (patterns, reason, proposed) in [(entry_patterns, Reason::EntryFolder, &mut proposed_entries[realm]), (include_patterns, Reason::Name, &mut proposed[realm])] {
+				for pattern in patterns.iter() {
+					let matched = files.iter().enumerate().filter(|(_, file)| pattern.matches(&file.path)).map(|(i, _)| i).collect::<Vec<_>>();
+					if matched.is_empty() || matched.iter().any(|i| files[*i].realm != Some(REALMS[realm])) {
+						continue;
+					}
+					for i in matched.iter() {
+						covered[*i] = true;
+					}
+					proposed.push(ProposedPattern { pattern: pattern.as_str().to_string(), reason, files: matched.len() });
+				}
+			}
+		}
+
+		// Everything else is listed by folder if all of the folder's Lua files are in the same realm for the same reason, otherwise by path
+		let folder = |path: &str| path.rfind('/').map(|slash| path[..slash].to_string());
+		let mut folders: BTreeMap<Option<String>, Vec<usize>> = BTreeMap::new();
+		for (i, file) in files.iter().enumerate() {
+			folders.entry(folder(&file.path)).or_default().push(i);
+		}
+		for (folder, in_folder) in folders {
+			let uncovered = in_folder.iter().copied().filter(|i| !covered[*i] && files[*i].realm.is_some()).collect::<Vec<_>>();
+			let first = match uncovered.first() {
+				Some(first) => &files[*first],
+				None => continue
+			};
+			let realm = REALMS.iter().position(|realm| Some(*realm) == first.realm).unwrap();
+
+			let same = in_folder.iter().all(|i| files[*i].realm == first.realm) && uncovered.iter().all(|i| files[*i].reason == first.reason);
+			if same && uncovered.len() > 1 {
+				let pattern = match &folder {
+					Some(folder) => format!("{}/*.lua", glob::Pattern::escape(folder)),
+					None => "*.lua".to_string()
+				};
+				proposed[realm].push(ProposedPattern { pattern, reason: first.reason.unwrap(), files: in_folder.len() });
+			} else {
+				for i in uncovered {
+					let file = &files[i];
+					let realm = REALMS.iter().position(|realm| Some(*realm) == file.realm).unwrap();
+					proposed[realm].push(ProposedPattern { pattern: glob::Pattern::escape(&file.path), reason: file.reason.unwrap(), files: 1 });
+				}
+			}
+		}
+
+		let [include_sv, include_cl, include_sh] = proposed;
+		let [entry_sv, entry_cl, entry_sh] = proposed_entries;
+		Ok(InitReport { files, include_sh, include_cl, include_sv, entry_cl, entry_sh, entry_sv })
+	}
+}
@@ -0,0 +1,134 @@
+use std::path::{Path, PathBuf};
+
+use crate::unpack::{EntryFiles, Pack, PackDir, UnpackingError};
+
+/// A file stored in a packed addon.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PackedFile {
+	/// The pack (`lua/gluapack/<unique_id>/`) the file is stored in.
+	pub unique_id: String,
+
+	/// Path of the file, relative to `lua/`.
+	pub path: String,
+
+	pub realm: &'static str,
+	pub size: usize,
+
+	/// The packed files (relative to `lua/`) the file is stored in.
+	pub chunks: Vec<String>,
+
+	/// Whether the loader executes this file after unpacking.
+	pub entry: bool,
+
+	/// The file this file's contents are shared with, if it was stored as a reference by `dedup`.
+	pub duplicate_of: Option<String>
+}
+
+/// The result of [`Inspector::inspect`].
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct InspectReport {
+	pub files: Vec<PackedFile>
+}
+impl InspectReport {
+	/// Renders the files as a table for each pack, followed by the total number of files and bytes.
+	pub fn table(&self) -> String {
+		// gluapack/<unique_id>/gluapack.N.<realm>.lua -> N (or "sv")
+		fn chunk_number(chunk: &str) -> &str {
+			chunk.rsplit('/').next().and_then(|file_name| file_name.split('.').nth(1)).unwrap_or(chunk)
+		}
+
+		let rows = self.files.iter().map(|file| {
+			let chunks = match (file.chunks.first(), file.chunks.last()) {
+				(Some(first), Some(last)) if first != last => format!("{}-{}", chunk_number(first), chunk_number(last)),
+				(Some(first), _) => chunk_number(first).to_string(),
+				_ => "?".to_string()
+			};
+			(file, chunks)
+		}).collect::<Vec<_>>();
+
+		let size_width = rows.iter().map(|(file, _)| file.size.to_string().len()).max().unwrap_or(0).max("SIZE".len());
+		let chunks_width = rows.iter().map(|(_, chunks)| chunks.len()).max().unwrap_or(0).max("CHUNKS".len());
+
+		let mut table = String::new();
+		let mut unique_id = None;
+		for (file, chunks) in rows {
+			if unique_id != Some(&file.unique_id) {
+				unique_id = Some(&file.unique_id);
+				table.push_str(&format!("gluapack/{}/\n", file.unique_id));
+				table.push_str(&format!("  REALM  {:>size_width$}  {:<chunks_width$}  ENTRY  PATH\n", "SIZE", "CHUNKS", size_width = size_width, chunks_width = chunks_width));
+			}
+			let duplicate_of = file.duplicate_of.as_ref().map(|duplicate_of| format!(" (duplicate of {})", duplicate_of)).unwrap_or_default();
+			table.push_str(&format!("  {:<5}  {:>size_width$}  {:<chunks_width$}  {:<5}  {}{}\n", file.realm, file.size, chunks, if file.entry { "yes" } else { "" }, file.path, duplicate_of, size_width = size_width, chunks_width = chunks_width));
+		}
+
+		table.push_str(&format!("\n{} packed file(s), {} byte(s)\n", self.files.len(), self.files.iter().map(|file| file.size).sum::<usize>()));
+		table
+	}
+
+	/// Renders the files as pretty-printed JSON.
+	pub fn json(&self) -> String {
+		serde_json::to_string_pretty(self).unwrap()
+	}
+}
+
+pub struct Inspector {
+	pub dir: PathBuf,
+	report: InspectReport
+}
+impl Inspector {
+	/// Lists the contents of a packed addon without unpacking it.
+	pub async fn inspect(dir: PathBuf) -> Result<InspectReport, UnpackingError> {
+		tokio::task::spawn_blocking(move || {
+			let mut inspector = Inspector {
+				dir: dir.join("lua"),
+				report: InspectReport::default()
+			};
+
+			for pack_dir in PackDir::discover(&inspector.dir)? {
+				let entry_files = match &pack_dir.loader {
+					Some(loader) => EntryFiles::read_loader(loader)?,
+					None => EntryFiles::default()
+				};
+
+				let mut packs = vec![];
+				if let Some(sv) = &pack_dir.sv {
+					packs.push(("sv", std::slice::from_ref(sv), Pack::read_sv(sv)?));
+				}
+				if !pack_dir.cl.is_empty() {
+					packs.push(("cl", pack_dir.cl.as_slice(), Pack::read_chunks(&pack_dir.cl)?));
+				}
+				if !pack_dir.sh.is_empty() {
+					packs.push(("sh", pack_dir.sh.as_slice(), Pack::read_chunks(&pack_dir.sh)?));
+				}
+
+				// Deduplicated files are listed with the size of the file they refer to
+				Pack::resolve_duplicates(packs.iter_mut().map(|(_, _, pack)| pack));
+
+				for (realm, chunk_files, pack) in packs {
+					inspector.add_pack(&pack_dir, realm, chunk_files, pack, &entry_files);
+				}
+			}
+
+			Ok(inspector.report)
+		}).await.expect("Failed to join thread")
+	}
+
+	fn relative(&self, path: &Path) -> String {
+		path.strip_prefix(&self.dir).unwrap_or(path).to_string_lossy().replace('\\', "/")
+	}
+
+	fn add_pack(&mut self, pack_dir: &PackDir, realm: &'static str, chunk_files: &[PathBuf], pack: Pack, entry_files: &EntryFiles) {
+		for entry in pack.entries {
+			let chunks = chunk_files.get(entry.chunks.clone()).unwrap_or_default().iter().map(|chunk| self.relative(chunk)).collect();
+			self.report.files.push(PackedFile {
+				unique_id: pack_dir.unique_id.clone(),
+				realm,
+				size: entry.contents.len(),
+				chunks,
+				entry: entry_files.contains(realm, &entry.path),
+				path: entry.path,
+				duplicate_of: entry.duplicate_of
+			});
+		}
+	}
+}
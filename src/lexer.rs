@@ -0,0 +1,399 @@
+// A tokenizer for Garry's Mod Lua (Lua 5.1 + LuaJIT + GLua extensions)
+
+/// Lua 5.1 keywords, plus GLua's `continue` and LuaJIT's `goto`
+pub const KEYWORDS: &[&str] = &[
+	"and", "break", "continue", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in",
+	"local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while"
+];
+
+/// Multi-byte symbols, longest first. Includes GLua's `!=`, `&&` and `||`
+const SYMBOLS: &[&[u8]] = &[b"...", b"..", b"==", b"~=", b"<=", b">=", b"!=", b"&&", b"||", b"::"];
+
+/// Counts the line breaks (`\n`, `\r`, `\r\n` or `\n\r`) in `bytes`.
+pub fn line_breaks(bytes: &[u8]) -> usize {
+	let mut count = 0;
+	let mut i = 0;
+	while i < bytes.len() {
+		if let first @ (b'\n' | b'\r') = bytes[i] {
+			count += 1;
+			if matches!(bytes.get(i + 1), Some(second @ (b'\n' | b'\r')) if *second != first) {
+				i += 1;
+			}
+		}
+		i += 1;
+	}
+	count
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+	Name,
+	Keyword,
+
+	/// A quoted string, including its quotes
+	String,
+
+	/// A long bracket string (`[[...]]`, `[==[...]==]`), including its brackets
+	LongString,
+
+	Number,
+	Symbol
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'a> {
+	pub kind: TokenKind,
+
+	/// The exact source bytes of this token
+	pub text: &'a [u8],
+
+	/// Byte offset of this token in the source
+	pub start: usize,
+
+	/// 1-based line number
+	pub line: usize,
+
+	/// 1-based column (in bytes)
+	pub column: usize
+}
+impl<'a> Token<'a> {
+	#[inline]
+	pub fn is(&self, text: &str) -> bool {
+		self.text == text.as_bytes()
+	}
+
+	#[inline]
+	pub fn end(&self) -> usize {
+		self.start + self.text.len()
+	}
+
+	/// The line this token ends on. Only differs from `line` for long strings and strings with escaped line breaks.
+	pub fn end_line(&self) -> usize {
+		self.line + line_breaks(self.text)
+	}
+
+	/// Returns the value of a string literal, with escape sequences resolved.
+	pub fn string_value(&self) -> Option<Vec<u8>> {
+		match self.kind {
+			TokenKind::LongString => {
+				let level = self.text.iter().skip(1).take_while(|byte| **byte == b'=').count();
+				let mut value = &self.text[level + 2..self.text.len() - level - 2];
+				// A newline immediately following the opening bracket is skipped
+				if value.starts_with(b"\r\n") {
+					value = &value[2..];
+				} else if value.starts_with(b"\n") || value.starts_with(b"\r") {
+					value = &value[1..];
+				}
+				Some(value.to_vec())
+			},
+
+			TokenKind::String => {
+				let mut value = Vec::with_capacity(self.text.len() - 2);
+				let mut bytes = self.text[1..self.text.len() - 1].iter().copied().peekable();
+				while let Some(byte) = bytes.next() {
+					if byte != b'\\' {
+						value.push(byte);
+						continue;
+					}
+					match bytes.next()? {
+						b'n' => value.push(b'\n'),
+						b't' => value.push(b'\t'),
+						b'r' => value.push(b'\r'),
+						b'a' => value.push(0x07),
+						b'b' => value.push(0x08),
+						b'f' => value.push(0x0C),
+						b'v' => value.push(0x0B),
+						b'z' => while let Some(b' ' | b'\t' | b'\n' | b'\r' | 0x0B | 0x0C) = bytes.peek() {
+							bytes.next();
+						},
+						b'x' => {
+							let hex = [bytes.next()?, bytes.next()?];
+							value.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+						},
+						b'u' if bytes.peek() == Some(&b'{') => {
+							bytes.next();
+							let hex = std::iter::from_fn(|| bytes.next()).take_while(|byte| *byte != b'}').collect::<Vec<u8>>();
+							let codepoint = char::from_u32(u32::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?)?;
+							value.extend_from_slice(codepoint.encode_utf8(&mut [0; 4]).as_bytes());
+						},
+						digit @ b'0'..=b'9' => {
+							let mut n = (digit - b'0') as u32;
+							for _ in 0..2 {
+								match bytes.peek() {
+									Some(digit @ b'0'..=b'9') => {
+										n = n * 10 + (digit - b'0') as u32;
+										bytes.next();
+									},
+									_ => break
+								}
+							}
+							value.push(n.min(255) as u8);
+						},
+						byte => value.push(byte)
+					}
+				}
+				Some(value)
+			},
+
+			_ => None
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexError {
+	pub message: &'static str,
+	pub line: usize,
+	pub column: usize
+}
+
+pub struct Lexer<'a> {
+	src: &'a [u8],
+	pos: usize,
+	line: usize,
+	line_start: usize,
+	failed: bool
+}
+impl<'a> Lexer<'a> {
+	pub fn new(src: &'a [u8]) -> Self {
+		let mut lexer = Lexer {
+			src,
+			pos: 0,
+			line: 1,
+			line_start: 0,
+			failed: false
+		};
+
+		// Skip the shebang line, if any
+		if src.starts_with(b"#") {
+			while lexer.pos < src.len() && !matches!(src[lexer.pos], b'\n' | b'\r') {
+				lexer.pos += 1;
+			}
+		}
+
+		lexer
+	}
+
+	#[inline]
+	fn peek(&self, offset: usize) -> Option<u8> {
+		self.src.get(self.pos + offset).copied()
+	}
+
+	fn error(&mut self, message: &'static str, line: usize, column: usize) -> Option<Result<Token<'a>, LexError>> {
+		self.failed = true;
+		Some(Err(LexError { message, line, column }))
+	}
+
+	/// Consumes a newline (`\n`, `\r`, `\r\n` or `\n\r`) at the current position.
+	fn newline(&mut self) {
+		let first = self.src[self.pos];
+		self.pos += 1;
+		if let Some(second) = self.peek(0) {
+			if matches!(second, b'\n' | b'\r') && second != first {
+				self.pos += 1;
+			}
+		}
+		self.line += 1;
+		self.line_start = self.pos;
+	}
+
+	/// If there is a long bracket opener (`[[`, `[=[`, ...) at the current position, returns its level.
+	fn long_bracket_level(&self) -> Option<usize> {
+		if self.peek(0) != Some(b'[') {
+			return None;
+		}
+		let level = self.src[self.pos + 1..].iter().take_while(|byte| **byte == b'=').count();
+		if self.peek(level + 1) == Some(b'[') {
+			Some(level)
+		} else {
+			None
+		}
+	}
+
+	/// Consumes a long bracket of the given level. Returns false if it is unterminated.
+	fn long_bracket(&mut self, level: usize) -> bool {
+		self.pos += level + 2;
+		while self.pos < self.src.len() {
+			match self.src[self.pos] {
+				b']' if self.src[self.pos + 1..].iter().take(level).all(|byte| *byte == b'=') && self.peek(level + 1) == Some(b']') => {
+					self.pos += level + 2;
+					return true;
+				},
+				b'\n' | b'\r' => self.newline(),
+				_ => self.pos += 1
+			}
+		}
+		false
+	}
+
+	/// Skips whitespace and comments. Returns an error for unterminated block comments.
+	fn skip_trivia(&mut self) -> Result<(), LexError> {
+		while let Some(byte) = self.peek(0) {
+			match byte {
+				b'\n' | b'\r' => self.newline(),
+				b' ' | b'\t' | 0x0B | 0x0C => self.pos += 1,
+
+				b'-' if self.peek(1) == Some(b'-') => {
+					let (line, column) = (self.line, self.pos - self.line_start + 1);
+					self.pos += 2;
+					if let Some(level) = self.long_bracket_level() {
+						if !self.long_bracket(level) {
+							return Err(LexError { message: "unfinished long comment", line, column });
+						}
+					} else {
+						self.skip_line();
+					}
+				},
+
+				b'/' if self.peek(1) == Some(b'/') => self.skip_line(),
+
+				b'/' if self.peek(1) == Some(b'*') => {
+					let (line, column) = (self.line, self.pos - self.line_start + 1);
+					self.pos += 2;
+					loop {
+						match self.peek(0) {
+							None => return Err(LexError { message: "unfinished block comment", line, column }),
+							Some(b'*') if self.peek(1) == Some(b'/') => {
+								self.pos += 2;
+								break;
+							},
+							Some(b'\n') | Some(b'\r') => self.newline(),
+							Some(_) => self.pos += 1
+						}
+					}
+				},
+
+				_ => break
+			}
+		}
+		Ok(())
+	}
+
+	fn skip_line(&mut self) {
+		while self.pos < self.src.len() && !matches!(self.src[self.pos], b'\n' | b'\r') {
+			self.pos += 1;
+		}
+	}
+}
+impl<'a> Iterator for Lexer<'a> {
+	type Item = Result<Token<'a>, LexError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.failed {
+			return None;
+		}
+
+		if let Err(error) = self.skip_trivia() {
+			self.failed = true;
+			return Some(Err(error));
+		}
+
+		let byte = self.peek(0)?;
+		let start = self.pos;
+		let (line, column) = (self.line, self.pos - self.line_start + 1);
+
+		let kind = match byte {
+			b'a'..=b'z' | b'A'..=b'Z' | b'_' | 0x80..=0xFF => {
+				while let Some(b'a'..=b'z') | Some(b'A'..=b'Z') | Some(b'0'..=b'9') | Some(b'_') | Some(0x80..=0xFF) = self.peek(0) {
+					self.pos += 1;
+				}
+				let name = std::str::from_utf8(&self.src[start..self.pos]).unwrap_or_default();
+				if KEYWORDS.contains(&name) {
+					TokenKind::Keyword
+				} else {
+					TokenKind::Name
+				}
+			},
+
+			b'0'..=b'9' => {
+				self.number();
+				TokenKind::Number
+			},
+			b'.' if matches!(self.peek(1), Some(b'0'..=b'9')) => {
+				self.number();
+				TokenKind::Number
+			},
+
+			b'"' | b'\'' => {
+				self.pos += 1;
+				loop {
+					match self.peek(0) {
+						None | Some(b'\n') | Some(b'\r') => return self.error("unfinished string", line, column),
+						Some(b'\\') => {
+							self.pos += 1;
+							match self.peek(0) {
+								None => return self.error("unfinished string", line, column),
+								Some(b'\n') | Some(b'\r') => self.newline(),
+								Some(b'z') => {
+									// `\z` skips the following whitespace, including line breaks
+									self.pos += 1;
+									while let Some(byte) = self.peek(0) {
+										match byte {
+											b'\n' | b'\r' => self.newline(),
+											b' ' | b'\t' | 0x0B | 0x0C => self.pos += 1,
+											_ => break
+										}
+									}
+								},
+								Some(_) => self.pos += 1
+							}
+						},
+						Some(quote) if quote == byte => {
+							self.pos += 1;
+							break;
+						},
+						Some(_) => self.pos += 1
+					}
+				}
+				TokenKind::String
+			},
+
+			b'[' if self.long_bracket_level().is_some() => {
+				let level = self.long_bracket_level().unwrap();
+				if !self.long_bracket(level) {
+					return self.error("unfinished long string", line, column);
+				}
+				TokenKind::LongString
+			},
+
+			b'[' if self.peek(1) == Some(b'=') => return self.error("invalid long string delimiter", line, column),
+
+			_ => {
+				if let Some(symbol) = SYMBOLS.iter().find(|symbol| self.src[self.pos..].starts_with(symbol)) {
+					self.pos += symbol.len();
+				} else if b"+-*/%^#<>=(){}[];:,.!&|~".contains(&byte) {
+					// `&`, `|` and `~` alone are not valid, but are left for the parser to reject
+					self.pos += 1;
+				} else {
+					return self.error("unexpected symbol", line, column);
+				}
+				TokenKind::Symbol
+			}
+		};
+
+		Some(Ok(Token {
+			kind,
+			text: &self.src[start..self.pos],
+			start,
+			line,
+			column
+		}))
+	}
+}
+impl<'a> Lexer<'a> {
+	/// Consumes a number, including hex numbers, exponents and LuaJIT suffixes (`ULL`, `LL`, `i`)
+	fn number(&mut self) {
+		let hex = self.peek(0) == Some(b'0') && matches!(self.peek(1), Some(b'x') | Some(b'X'));
+		if hex {
+			self.pos += 2;
+		}
+		while let Some(byte) = self.peek(0) {
+			match byte {
+				b'e' | b'E' if !hex && matches!(self.peek(1), Some(b'+') | Some(b'-')) => self.pos += 2,
+				b'p' | b'P' if hex && matches!(self.peek(1), Some(b'+') | Some(b'-')) => self.pos += 2,
+				b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' | b'_' | b'.' => self.pos += 1,
+				_ => break
+			}
+		}
+	}
+}
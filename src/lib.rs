@@ -0,0 +1,57 @@
+#![cfg_attr(all(debug_assertions, feature = "nightly"), feature(backtrace))]
+
+#[macro_use]
+extern crate lazy_static;
+
+#[macro_use]
+mod util;
+
+pub mod pack;
+pub mod unpack;
+pub mod config;
+pub mod format;
+pub mod lexer;
+pub mod analysis;
+pub mod minify;
+pub mod syntax;
+pub mod verify;
+pub mod inspect;
+pub mod diff;
+pub mod init;
+pub mod explain;
+pub mod watch;
+pub mod chunking;
+mod cache;
+mod classify;
+
+pub use pack::{Packer, PackOptions, PackReport, MinifyReport, ChunkEncodingReport, DedupReport, RealmConflict, UnusedPattern, PathCollision, PackingError};
+pub use unpack::{Unpacker, UnpackOptions, UnpackReport, UnpackingError};
+pub use verify::{Verifier, VerifyReport};
+pub use inspect::{Inspector, InspectReport};
+pub use diff::{Differ, DiffOptions, DiffReport};
+pub use init::{Initializer, InitReport};
+pub use explain::{Explainer, ExplainOptions, ExplainReport};
+pub use watch::Watcher;
+pub use config::Config;
+
+/// The maximum size of a chunk.
+///
+/// This should be 64 KiB as Garry's Mod will not network a Lua file larger than this.
+pub const MAX_LUA_SIZE: usize = 65535;
+
+/// The minimum `chunk_size` allowed in the config.
+pub const MIN_CHUNK_SIZE: usize = 256;
+
+pub const MEM_PREALLOCATE_MAX: usize = 1024 * 1024 * 1024;
+#[allow(clippy::char_lit_as_u8)]
+pub const TERMINATOR_HACK: u8 = '|' as u8;
+
+/// File and chunk counts of a single realm (sv, cl or sh) after packing or unpacking.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RealmReport {
+	/// Number of Lua files in this realm.
+	pub files: usize,
+
+	/// Number of packed files (chunks) in this realm.
+	pub chunks: usize
+}
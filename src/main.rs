@@ -1,24 +1,42 @@
 #![cfg_attr(all(debug_assertions, feature = "nightly"), feature(backtrace))]
 
 #[macro_use]
-extern crate lazy_static;
+extern crate gluapack;
 
-#[macro_use]
-mod util;
-
-mod pack;
-mod unpack;
-mod config;
+use gluapack::{Packer, PackOptions, PackReport, PackingError, Unpacker, UnpackOptions, Verifier, Inspector, Differ, DiffOptions, Initializer, Explainer, ExplainOptions, Watcher, config::{self, Config, ConfigOverrides, OptionKind, Workspace}};
 
-use pack::Packer;
-use unpack::Unpacker;
-
-/// The maximum size of a chunk.
-///
-/// This should be 64 KiB as Garry's Mod will not network a Lua file larger than this.
-pub const MAX_LUA_SIZE: usize = 65535;
-pub const MEM_PREALLOCATE_MAX: usize = 1024 * 1024 * 1024;
-pub const TERMINATOR_HACK: u8 = '|' as u8;
+/// Prints the outcome of packing. Returns false if packing failed.
+fn print_pack_result(quiet: bool, result: Result<PackReport, PackingError>) -> bool {
+	match (quiet, result) {
+		(true, Ok(_)) => true,
+		(false, Ok(report)) => {
+			println!();
+			let (unpacked_files, packed_files) = (report.unpacked_files(), report.packed_files());
+			let pct_change = (((unpacked_files as f64) - (packed_files as f64)) / (unpacked_files as f64)) * 100.;
+			let sign = if pct_change == 0. { "" } else if pct_change > 0. { "-" } else { "+" };
+			println!("Successfully PACKED {} file(s) -> {} files ({}{:.2}%)", unpacked_files, packed_files, sign, pct_change.abs());
+			if report.minified.total() != 0 {
+				println!("Minifying saved {} byte(s) (sv: {}, cl: {}, sh: {})", report.minified.total(), report.minified.sv, report.minified.cl, report.minified.sh);
+			}
+			let encoding = report.chunk_encoding;
+			if encoding.lines != 0 {
+				let (difference, comparison) = if encoding.long_string <= encoding.lines { (encoding.lines - encoding.long_string, "smaller") } else { (encoding.long_string - encoding.lines, "larger") };
+				println!("Chunk encodings: lines {} byte(s), long_string {} byte(s) (long_string is {} byte(s) {})", encoding.lines, encoding.long_string, difference, comparison);
+			}
+			println!("Took {:?}", report.elapsed);
+			true
+		},
+		(_, Err(error)) => {
+			if !quiet {
+				println!();
+			}
+			eprintln!("ERROR: {}", error);
+			#[cfg(all(feature = "nightly", debug_assertions))]
+			eprintln!("{:#?}", error.backtrace());
+			false
+		},
+	}
+}
 
 #[tokio::main(flavor = "multi_thread")]
 async fn main() {
@@ -28,6 +46,32 @@ async fn main() {
 	#[cfg(all(debug_assertions, feature = "nightly"))]
 	use std::error::Error;
 
+	// Every config option can be overridden when packing, lists of patterns can be appended to with --<flag> or replaced with --set-<flag>
+	let override_flags = config::OPTIONS.iter().map(|option| {
+		let flag = option.flag();
+		let help = match option.kind {
+			OptionKind::Patterns => format!("Appends a pattern to {} in the config", option.field),
+			OptionKind::Realms => format!("Overrides {} in the config with a comma-separated list of realms (sv, cl, sh) or none", option.field),
+			_ => format!("Overrides {} in the config", option.field)
+		};
+		(option, format!("set-{}", flag), format!("Replaces {} in the config with these patterns", option.field), flag, help)
+	}).collect::<Vec<_>>();
+	let override_args = override_flags.iter().flat_map(|(option, set_flag, set_help, flag, help)| {
+		let value_name = match option.kind {
+			OptionKind::Patterns => "PATTERN",
+			OptionKind::Realms => "REALMS",
+			_ => "VALUE"
+		};
+		let arg = Arg::with_name(flag).help(help).long(flag).takes_value(true).value_name(value_name);
+		match option.kind {
+			OptionKind::Patterns => vec![
+				arg.multiple(true).number_of_values(1),
+				Arg::with_name(set_flag).help(set_help).long(set_flag).takes_value(true).value_name(value_name).multiple(true).number_of_values(1)
+			],
+			_ => vec![arg.multiple(false)]
+		}
+	}).collect::<Vec<_>>();
+
 	let stdin = App::new("gluapack")
 		.version(env!("CARGO_PKG_VERSION"))
 		.setting(AppSettings::VersionlessSubcommands)
@@ -41,11 +85,31 @@ async fn main() {
 			.about("Packs an addon")
 			.arg(
 				Arg::with_name("path")
-					.help("Path to addon root (directory containing lua/ folder)")
+					.help("Path to addon root (directory containing lua/ folder), or to a directory containing gluapack-workspace.json")
 					.takes_value(true)
 					.required(true)
 					.index(1)
 			)
+			.arg(
+				Arg::with_name("watch")
+					.help("Keeps running and repacks the addon whenever its Lua files or config file change")
+					.long("watch")
+					.short("w")
+					.multiple(false)
+			)
+			.arg(
+				Arg::with_name("no-validate")
+					.help("Skips checking the syntax of Lua files before packing them")
+					.long("no-validate")
+					.multiple(false)
+			)
+			.arg(
+				Arg::with_name("cache")
+					.help("Keeps a build cache in the addon's .gluapack-cache/ folder, so unchanged realms and files aren't packed or copied again")
+					.long("cache")
+					.multiple(false)
+			)
+			.args(&override_args)
 		)
 		.subcommand(
 			App::new("unpack")
@@ -60,6 +124,118 @@ async fn main() {
 					.index(1)
 			)
 		)
+		.subcommand(
+			App::new("verify")
+			.setting(AppSettings::TrailingVarArg)
+			.setting(AppSettings::AllowLeadingHyphen)
+			.about("Checks that a packed addon's files match the checksums recorded when it was packed")
+			.arg(
+				Arg::with_name("path")
+					.help("Path to packed addon root (directory containing lua/ folder)")
+					.takes_value(true)
+					.required(true)
+					.index(1)
+			)
+		)
+		.subcommand(
+			App::new("inspect")
+			.setting(AppSettings::TrailingVarArg)
+			.setting(AppSettings::AllowLeadingHyphen)
+			.about("Lists the contents of a packed addon without unpacking it")
+			.arg(
+				Arg::with_name("path")
+					.help("Path to packed addon root (directory containing lua/ folder)")
+					.takes_value(true)
+					.required(true)
+					.index(1)
+			)
+			.arg(
+				Arg::with_name("json")
+					.help("Prints the contents as JSON")
+					.long("json")
+					.multiple(false)
+			)
+		)
+		.subcommand(
+			App::new("init")
+			.setting(AppSettings::TrailingVarArg)
+			.setting(AppSettings::AllowLeadingHyphen)
+			.about("Generates a gluapack.json for an existing addon from its Lua files")
+			.arg(
+				Arg::with_name("path")
+					.help("Path to addon root (directory containing lua/ folder)")
+					.takes_value(true)
+					.required(true)
+					.index(1)
+			)
+			.arg(
+				Arg::with_name("yes")
+					.help("Writes gluapack.json without asking for confirmation")
+					.long("yes")
+					.short("y")
+					.multiple(false)
+			)
+		)
+		.subcommand(
+			App::new("explain")
+			.setting(AppSettings::TrailingVarArg)
+			.setting(AppSettings::AllowLeadingHyphen)
+			.about("Shows which config patterns match each Lua file of an addon, and what packing does with it")
+			.arg(
+				Arg::with_name("path")
+					.help("Path to addon root (directory containing lua/ folder), or to a directory containing gluapack-workspace.json")
+					.takes_value(true)
+					.required(true)
+					.index(1)
+			)
+			.arg(
+				Arg::with_name("file")
+					.help("Only explains this Lua file (relative to lua/)")
+					.takes_value(true)
+					.required(false)
+					.index(2)
+			)
+			.arg(
+				Arg::with_name("json")
+					.help("Prints the explanation as JSON")
+					.long("json")
+					.multiple(false)
+			)
+			.args(&override_args)
+		)
+		.subcommand(
+			App::new("diff")
+			.setting(AppSettings::TrailingVarArg)
+			.setting(AppSettings::AllowLeadingHyphen)
+			.about("Lists the Lua files that changed between two packed addons")
+			.arg(
+				Arg::with_name("old")
+					.help("Path to the old packed addon root (directory containing lua/ folder)")
+					.takes_value(true)
+					.required(true)
+					.index(1)
+			)
+			.arg(
+				Arg::with_name("new")
+					.help("Path to the new packed addon root (directory containing lua/ folder)")
+					.takes_value(true)
+					.required(true)
+					.index(2)
+			)
+			.arg(
+				Arg::with_name("patch")
+					.help("Shows a unified diff of every modified file")
+					.long("patch")
+					.short("p")
+					.multiple(false)
+			)
+			.arg(
+				Arg::with_name("json")
+					.help("Prints the changes as JSON")
+					.long("json")
+					.multiple(false)
+			)
+		)
 		.arg(
 			Arg::with_name("in-place")
 				.global(true)
@@ -102,8 +278,9 @@ async fn main() {
 		.get_matches();
 
 	macro_rules! addon_path {
-		($args:ident) => {{
-			let path = PathBuf::from($args.value_of("path").unwrap());
+		($args:ident) => { addon_path!($args, "path") };
+		($args:ident, $arg:literal) => {{
+			let path = PathBuf::from($args.value_of($arg).unwrap());
 			if !path.join("lua").is_dir() {
 				eprintln!("ERROR: Couldn't find an addon at this path containing a lua/ folder.");
 				abort!();
@@ -113,49 +290,113 @@ async fn main() {
 		}}
 	}
 
-	macro_rules! out_path {
-		($args:ident, $path:ident, $in_place:ident, $suffix_to:literal, $suffix_from:literal) => {
-			if !$in_place {
-				Some(match $args.value_of("out") {
-					Some(out_dir) => {
-						let out_dir = PathBuf::from(out_dir);
-						if out_dir.is_absolute() {
-							if out_dir == $path {
-								eprintln!("ERROR: Output directory cannot be the same as the addon directory!");
-								abort!();
-							}
-							out_dir
-						} else {
-							$path.parent().unwrap_or_else(|| $path.as_path()).join(out_dir)
-						}
-					},
-					None => {
-						let path = $path.file_name().unwrap().to_string_lossy();
-						$path.parent().unwrap_or_else(|| $path.as_path()).join(format!(concat!("{}-", $suffix_to), path.strip_suffix(concat!("-", $suffix_from)).unwrap_or_else(|| &path)))
-					}
-				})
-			} else {
-				None
+	// Command line overrides take precedence over environment variables
+	let read_overrides = |args: &ArgMatches| -> std::result::Result<ConfigOverrides, PackingError> {
+		let mut overrides = ConfigOverrides::from_env()?;
+		for (option, set_flag, _, flag, _) in override_flags.iter() {
+			if let Some(patterns) = args.values_of(set_flag) {
+				overrides.set(format!("--{}", set_flag), option.field, &patterns.collect::<Vec<_>>())?;
+			}
+			if let Some(values) = args.values_of(flag) {
+				let values = values.collect::<Vec<_>>();
+				if option.kind == OptionKind::Patterns {
+					overrides.append(format!("--{}", flag), option.field, &values)?;
+				} else {
+					overrides.set(format!("--{}", flag), option.field, &values)?;
+				}
 			}
 		}
-	}
+		Ok(overrides)
+	};
 
 	match stdin.subcommand() {
 		("pack", Some(args)) => {
+			let workspace = PathBuf::from(args.value_of("path").unwrap());
+			let workspace = if workspace.join(Workspace::FILE_NAME).is_file() { Some(dunce::canonicalize(&workspace).unwrap_or(workspace)) } else { None };
+			let path = match &workspace {
+				Some(workspace) => workspace.clone(),
+				None => addon_path!(args)
+			};
+			let quiet = args.is_present("quiet");
+
+			let mut options = PackOptions::new()
+				.in_place(args.is_present("in-place"))
+				.no_copy(args.is_present("no-copy"))
+				.cache(args.is_present("cache"))
+				.validate(!args.is_present("no-validate"))
+				.quiet(quiet);
+
+			if let Some(out_dir) = args.value_of("out") {
+				options = options.out_dir(out_dir);
+			}
+
+			match read_overrides(args) {
+				Ok(overrides) => options = options.overrides(overrides),
+				Err(error) => {
+					eprintln!("ERROR: {}", error);
+					abort!();
+				}
+			}
+
+			if !args.is_present("watch") {
+				let result = match workspace {
+					Some(_) => Packer::pack_workspace(path, options).await,
+					None => Packer::pack(path, options).await
+				};
+				if !print_pack_result(quiet, result) {
+					abort!();
+				}
+				return;
+			}
+
+			if workspace.is_some() {
+				eprintln!("ERROR: --watch can't be used with a workspace");
+				abort!();
+			}
+
+			if args.is_present("in-place") {
+				// Packing in-place would change the files we're watching
+				eprintln!("ERROR: --watch can't be used with --in-place");
+				abort!();
+			}
+
+			let mut watcher = Watcher::new(path.clone()).await;
+			loop {
+				print_pack_result(quiet, Packer::pack(path.clone(), options.clone()).await);
+
+				quietln!(quiet);
+				quietln!(quiet, "Watching for changes... (press Ctrl+C to stop)");
+
+				let changed = watcher.changed().await;
+
+				quietln!(quiet);
+				quietln!(quiet, "{} file(s) changed, repacking...", changed);
+				quietln!(quiet);
+			}
+		},
+
+		("unpack", Some(args)) => {
 			let path = addon_path!(args);
-			let in_place = args.is_present("in-place");
-			let out_dir = out_path!(args, path, in_place, "packed", "unpacked");
-			let no_copy = args.is_present("no-copy");
 			let quiet = args.is_present("quiet");
 
-			match (quiet, Packer::pack(path, out_dir, no_copy, quiet).await) {
+			let mut options = UnpackOptions::new()
+				.in_place(args.is_present("in-place"))
+				.no_copy(args.is_present("no-copy"))
+				.quiet(quiet);
+
+			if let Some(out_dir) = args.value_of("out") {
+				options = options.out_dir(out_dir);
+			}
+
+			match (quiet, Unpacker::unpack(path, options).await) {
 				(true, Ok(_)) => {},
-				(false, Ok((unpacked_files, packed_files, elapsed))) => {
+				(false, Ok(report)) => {
 					println!();
-					let pct_change = (((unpacked_files as f64) - (packed_files as f64)) / (unpacked_files as f64)) * 100.;
+					let (packed_files, unpacked_files) = (report.packed_files(), report.unpacked_files());
+					let pct_change = (((packed_files as f64) - (unpacked_files as f64)) / (packed_files as f64)) * 100.;
 					let sign = if pct_change == 0. { "" } else if pct_change > 0. { "-" } else { "+" };
-					println!("Successfully PACKED {} file(s) -> {} files ({}{:.2}%)", unpacked_files, packed_files, sign, pct_change.abs());
-					println!("Took {:?}", elapsed);
+					println!("Successfully UNPACKED {} files -> {} file(s) ({}{:.2}%)", packed_files, unpacked_files, sign, pct_change.abs());
+					println!("Took {:?}", report.elapsed);
 				},
 				(_, Err(error)) => {
 					if !quiet {
@@ -169,26 +410,28 @@ async fn main() {
 			}
 		},
 
-		("unpack", Some(args)) => {
+		("verify", Some(args)) => {
 			let path = addon_path!(args);
-			let in_place = args.is_present("in-place");
-			let out_dir = out_path!(args, path, in_place, "unpacked", "packed");
-			let no_copy = args.is_present("no-copy");
 			let quiet = args.is_present("quiet");
 
-			match (quiet, Unpacker::unpack(path, out_dir, no_copy, quiet).await) {
-				(true, Ok(_)) => {},
-				(false, Ok((packed_files, unpacked_files, elapsed))) => {
-					println!();
-					let pct_change = (((unpacked_files as f64) - (packed_files as f64)) / (unpacked_files as f64)) * 100.;
-					let sign = if pct_change == 0. { "" } else if pct_change > 0. { "-" } else { "+" };
-					println!("Successfully UNPACKED {} files -> {} file(s) ({}{:.2}%)", unpacked_files, packed_files, sign, pct_change.abs());
-					println!("Took {:?}", elapsed);
-				},
-				(_, Err(error)) => {
-					if !quiet {
-						println!();
+			quietln!(quiet, "Addon Path: {}", path.display());
+			quietln!(quiet);
+
+			match Verifier::verify(path).await {
+				Ok(report) => {
+					for problem in report.problems.iter() {
+						eprintln!("MISMATCH {}: {}", problem.path, problem.mismatch);
 					}
+					if report.is_ok() {
+						quietln!(quiet, "Successfully VERIFIED {} file(s) in {} packed file(s)", report.files, report.chunks);
+						quietln!(quiet, "Took {:?}", report.elapsed);
+					} else {
+						quietln!(quiet);
+						eprintln!("ERROR: Verification FAILED with {} problem(s)", report.problems.len());
+						abort!();
+					}
+				},
+				Err(error) => {
 					eprintln!("ERROR: {}", error);
 					#[cfg(all(feature = "nightly", debug_assertions))]
 					eprintln!("{:#?}", error.backtrace());
@@ -197,6 +440,211 @@ async fn main() {
 			}
 		},
 
+		("inspect", Some(args)) => {
+			let path = addon_path!(args);
+
+			let report = match Inspector::inspect(path).await {
+				Ok(report) => report,
+				Err(error) => {
+					eprintln!("ERROR: {}", error);
+					#[cfg(all(feature = "nightly", debug_assertions))]
+					eprintln!("{:#?}", error.backtrace());
+					abort!();
+				}
+			};
+
+			if args.is_present("json") {
+				println!("{}", report.json());
+			} else {
+				print!("{}", report.table());
+			}
+		},
+
+		("init", Some(args)) => {
+			let path = addon_path!(args);
+			let quiet = args.is_present("quiet");
+
+			if let Some(config) = Config::find(&path) {
+				eprintln!("ERROR: {} already exists", config.display());
+				abort!();
+			}
+
+			let report = match Initializer::init(path.clone()).await {
+				Ok(report) => report,
+				Err(error) => {
+					eprintln!("ERROR: {}", error);
+					#[cfg(all(feature = "nightly", debug_assertions))]
+					eprintln!("{:#?}", error.backtrace());
+					abort!();
+				}
+			};
+
+			let config = report.config_json();
+			let unclassified = report.unclassified().count();
+			quietln!(quiet, "{}", config);
+			quietln!(quiet, "Found {} Lua file(s): {} sv, {} cl, {} sh, {} couldn't be classified", report.files.len(), report.files_in("sv"), report.files_in("cl"), report.files_in("sh"), unclassified);
+			if unclassified != 0 {
+				eprintln!("WARNING: {} Lua file(s) couldn't be classified and won't be packed:", unclassified);
+				for file in report.unclassified() {
+					eprintln!("  {}", file.path);
+				}
+			}
+
+			let config_path = path.join(Config::FILE_NAMES[0]);
+			if !args.is_present("yes") {
+				use std::io::Write;
+
+				print!("Write {}? [y/N] ", config_path.display());
+				std::io::stdout().flush().ok();
+
+				let mut answer = String::new();
+				std::io::stdin().read_line(&mut answer).ok();
+				if !matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes") {
+					println!("Nothing was written. Use --yes to write it without asking.");
+					return;
+				}
+			}
+
+			if let Err(error) = std::fs::write(&config_path, config) {
+				eprintln!("ERROR: Couldn't write {}: {}", config_path.display(), error);
+				abort!();
+			}
+			quietln!(quiet, "Wrote {}", config_path.display());
+		},
+
+		("explain", Some(args)) => {
+			let workspace = PathBuf::from(args.value_of("path").unwrap());
+			let workspace = if workspace.join(Workspace::FILE_NAME).is_file() { Some(dunce::canonicalize(&workspace).unwrap_or(workspace)) } else { None };
+			let path = match &workspace {
+				Some(workspace) => workspace.clone(),
+				None => addon_path!(args)
+			};
+
+			let overrides = match read_overrides(args) {
+				Ok(overrides) => overrides,
+				Err(error) => {
+					eprintln!("ERROR: {}", error);
+					abort!();
+				}
+			};
+
+			let options = ExplainOptions::new().overrides(overrides);
+			let result = match workspace {
+				Some(_) => Explainer::explain_workspace(path.clone(), options).await,
+				None => Explainer::explain(path.clone(), options).await
+			};
+			let report = match result {
+				Ok(report) => report,
+				Err(error) => {
+					eprintln!("ERROR: {}", error);
+					#[cfg(all(feature = "nightly", debug_assertions))]
+					eprintln!("{:#?}", error.backtrace());
+					abort!();
+				}
+			};
+
+			let files = match args.value_of("file") {
+				Some(file) => {
+					// Accept paths relative to lua/, to the addon or to the working directory
+					let file = PathBuf::from(file);
+					let file = dunce::canonicalize(&file).ok().and_then(|file| file.strip_prefix(path.join("lua")).ok().map(|file| file.to_path_buf())).unwrap_or(file);
+					let file = file.to_string_lossy().replace('\\', "/");
+					match report.file(&file).or_else(|| file.strip_prefix("lua/").and_then(|file| report.file(file))) {
+						Some(file) => vec![file.clone()],
+						None => {
+							eprintln!("ERROR: {} isn't a Lua file in this addon's lua/ folder", file);
+							abort!();
+						}
+					}
+				},
+				None => report.files.clone()
+			};
+
+			if args.is_present("json") {
+				println!("{}", serde_json::to_string_pretty(&files).unwrap());
+			} else {
+				for file in files.iter() {
+					match &file.addon {
+						Some(addon) => println!("{} ({}): {}", file.path, addon, file.describe()),
+						None => println!("{}: {}", file.path, file.describe())
+					}
+					let width = file.matched.iter().map(|matched| matched.option.len()).max().unwrap_or(0);
+					for matched in file.matched.iter() {
+						println!("  {:<width$}  {}", matched.option, matched.pattern, width = width);
+					}
+					for note in file.notes.iter() {
+						println!("  note: {}", note);
+					}
+				}
+
+				if args.value_of("file").is_none() {
+					use gluapack::explain::Decision;
+
+					println!();
+					println!(
+						"{} Lua file(s): {} packed, {} excluded, {} pruned, {} left untouched, {} realm conflict(s)",
+						report.files.len(), report.count(Decision::Packed), report.count(Decision::Excluded), report.count(Decision::Pruned), report.count(Decision::Untouched), report.count(Decision::Conflict)
+					);
+				}
+			}
+		},
+
+		("diff", Some(args)) => {
+			let (old, new) = (addon_path!(args, "old"), addon_path!(args, "new"));
+
+			let report = match Differ::diff(old, new, DiffOptions::new().patch(args.is_present("patch"))).await {
+				Ok(report) => report,
+				Err(error) => {
+					eprintln!("ERROR: {}", error);
+					#[cfg(all(feature = "nightly", debug_assertions))]
+					eprintln!("{:#?}", error.backtrace());
+					abort!();
+				}
+			};
+
+			if args.is_present("json") {
+				println!("{}", serde_json::to_string_pretty(&report).unwrap());
+			} else if report.is_empty() {
+				println!("No changes");
+			} else {
+				for realm in ["sv", "cl", "sh"] {
+					// Removed files are listed under their old realm, everything else under its new realm
+					let mut files = report.files.iter().filter(|file| file.new_realm.or(file.old_realm) == Some(realm)).peekable();
+					if files.peek().is_none() {
+						continue;
+					}
+					println!("{}:", realm);
+					for file in files {
+						match (file.old_realm, file.new_realm) {
+							(None, _) => println!("  added     {}", file.path),
+							(_, None) => println!("  removed   {}", file.path),
+							(Some(old_realm), Some(new_realm)) if old_realm != new_realm => println!("  moved     {} (from {}{})", file.path, old_realm, if file.modified { ", modified" } else { "" }),
+							_ => println!("  modified  {}", file.path)
+						}
+					}
+				}
+
+				if !report.entry_files.is_empty() {
+					println!("entry files:");
+					for entry_file in report.entry_files.iter() {
+						println!("  {}  {}  {}", if entry_file.added { "added  " } else { "removed" }, entry_file.realm, entry_file.path);
+					}
+				}
+
+				for patch in report.files.iter().filter_map(|file| file.patch.as_ref()) {
+					println!();
+					print!("{}", patch);
+				}
+
+				let count = |filter: fn(&&gluapack::diff::FileDiff) -> bool| report.files.iter().filter(filter).count();
+				println!();
+				println!(
+					"{} added, {} removed, {} modified, {} moved, {} entry file change(s)",
+					count(|file| file.is_added()), count(|file| file.is_removed()), count(|file| file.modified), count(|file| file.is_moved()), report.entry_files.len()
+				);
+			}
+		},
+
 		_ => unreachable!()
 	}
 }
@@ -0,0 +1,48 @@
+// Strips comments and redundant whitespace from Garry's Mod Lua
+
+use crate::lexer::{LexError, Lexer, Token};
+
+/// Returns true if `prev` and `next` would lex differently without whitespace between them, e.g. `a - -b` or `1 ..x`.
+fn needs_space(prev: &Token, next: &Token) -> bool {
+	if prev.is("#") {
+		// The lexer would mistake this for a shebang, but `#` can't combine with anything
+		return false;
+	}
+
+	let mut joined = Vec::with_capacity(prev.text.len() + 3);
+	joined.extend_from_slice(prev.text);
+	joined.extend(next.text.iter().take(3));
+
+	match Lexer::new(&joined).next() {
+		Some(Ok(token)) => token.text != prev.text,
+		_ => true
+	}
+}
+
+/// Minifies Lua source, removing comments (including GLua's `//` and `/* */`), indentation and other redundant whitespace.
+///
+/// String literals and long brackets are kept as-is, and line breaks are kept so that errors still point to the right line.
+pub fn minify(src: &[u8]) -> Result<Vec<u8>, LexError> {
+	let mut minified = Vec::with_capacity(src.len());
+	let mut prev: Option<Token> = None;
+	let mut line = 1;
+
+	for token in Lexer::new(src) {
+		let token = token?;
+
+		if token.line > line {
+			minified.resize(minified.len() + (token.line - line), b'\n');
+		} else if let Some(prev) = &prev {
+			if needs_space(prev, &token) {
+				minified.push(b' ');
+			}
+		}
+
+		minified.extend_from_slice(token.text);
+
+		line = token.end_line();
+		prev = Some(token);
+	}
+
+	Ok(minified)
+}
@@ -1,30 +1,10 @@
 // The order of operations should be: sv cl sh
 
-use crate::{MAX_LUA_SIZE, MEM_PREALLOCATE_MAX, TERMINATOR_HACK, util, config::{Config, GlobPattern}};
-use std::{collections::HashSet, convert::TryInto, path::PathBuf, time::Duration};
-use futures_util::{FutureExt, future};
+use crate::{MAX_LUA_SIZE, MIN_CHUNK_SIZE, MEM_PREALLOCATE_MAX, TERMINATOR_HACK, RealmReport, util, analysis::CallKind, classify::{self, Decision, REALMS}, minify, syntax, cache::{self, BuildCache, CachedRealm}, chunking::{self, Chunks, PackedEntry}, config::{ChunkEncoding, ChunkingStrategy, Config, ConfigOverrides, GlobPattern, Uncovered, Workspace}, format::{self, PackHeader}};
+use std::{collections::{BTreeMap, BTreeSet, HashMap, HashSet}, convert::TryInto, path::{Path, PathBuf}, time::Duration};
+use futures_util::future;
 use sha2::Digest;
 
-/// Lua comment
-const COMMENT_START: &'static [u8; 2] = b"--";
-
-/// Prepends `--` to every line in the byte vector.
-fn commentify(bytes: Vec<u8>) -> Vec<u8> {
-	const NEWLINE: u8 = '\n' as u8;
-	let mut escaped = Vec::with_capacity(bytes.len());
-	escaped.push('-' as u8);
-	escaped.push('-' as u8);
-	for byte in bytes {
-		escaped.push(byte);
-		if byte == NEWLINE {
-			escaped.reserve(2);
-			escaped.push('-' as u8);
-			escaped.push('-' as u8);
-		}
-	}
-	escaped
-}
-
 struct LuaFile {
 	path: String,
 	contents: Vec<u8>
@@ -40,48 +20,376 @@ impl PartialEq for LuaFile {
 	}
 }
 impl Eq for LuaFile {}
+impl PartialOrd for LuaFile {
+	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+		Some(self.cmp(other))
+	}
+}
+impl Ord for LuaFile {
+	fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+		self.path.cmp(&other.path)
+	}
+}
+
+/// Lua files of a realm, and the paths of its entry files
+type CollectedLuaFiles = (BTreeSet<LuaFile>, Vec<String>);
+
+/// Patterns of the packed files and loaders of previous builds, which are always excluded.
+pub(crate) const PACKED_FILES: [&str; 2] = ["gluapack/*/*", "autorun/*_gluapack_*.lua"];
+
+/// A Lua file that matches the `include_*` patterns of more than one realm.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RealmConflict {
+	pub path: String,
+
+	/// The realms the file matches, in the order sv, cl, sh
+	pub realms: Vec<&'static str>
+}
+impl std::fmt::Display for RealmConflict {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} ({})", self.path, self.realms.join(", "))
+	}
+}
+
+/// A config pattern that doesn't match any Lua file of the addon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnusedPattern {
+	/// The config option the pattern is in, such as `include_sv`
+	pub option: &'static str,
+
+	pub pattern: String,
+
+	/// Whether the pattern matches files relative to the addon's root. Patterns are relative to `lua/`.
+	pub relative_to_root: bool
+}
+impl std::fmt::Display for UnusedPattern {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}: {}", self.option, self.pattern)?;
+		if self.relative_to_root {
+			write!(f, " (this matches files relative to the addon's root, but patterns are relative to lua/)")?;
+		}
+		Ok(())
+	}
+}
+
+/// A file that more than one addon of a workspace contains.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathCollision {
+	/// Path of the file, relative to the addon root.
+	pub path: String,
+
+	/// The addons containing the file, in workspace order
+	pub addons: Vec<String>
+}
+impl std::fmt::Display for PathCollision {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} ({})", self.path, self.addons.join(", "))
+	}
+}
+
+/// An addon whose Lua files are being packed: the addon itself, or one of a workspace's addons.
+struct Source {
+	/// The addon's path in the workspace, or `None` when packing a single addon.
+	name: Option<String>,
+
+	/// The addon's `lua/` folder
+	dir: PathBuf,
+
+	/// The addon's own config, which decides which of its files are packed, in which realm, and its entry files.
+	config: Config
+}
+
+/// Options for [`Packer::pack`].
+#[derive(Debug, Clone)]
+pub struct PackOptions {
+	out_dir: Option<PathBuf>,
+	in_place: bool,
+	no_copy: bool,
+	quiet: bool,
+	cache: bool,
+	validate: bool,
+	config: Option<Config>,
+	overrides: ConfigOverrides
+}
+impl Default for PackOptions {
+	fn default() -> Self {
+		PackOptions {
+			out_dir: None,
+			in_place: false,
+			no_copy: false,
+			quiet: false,
+			cache: false,
+			validate: true,
+			config: None,
+			overrides: ConfigOverrides::default()
+		}
+	}
+}
+impl PackOptions {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Sets the output directory. Relative to the addon's parent directory. Can be an absolute path.
+	///
+	/// Defaults to `<addon>-packed`.
+	pub fn out_dir<P: Into<PathBuf>>(mut self, out_dir: P) -> Self {
+		self.out_dir = Some(out_dir.into());
+		self
+	}
+
+	/// Modifies the addon in-place, rather than creating a copy of the addon.
+	pub fn in_place(mut self, in_place: bool) -> Self {
+		self.in_place = in_place;
+		self
+	}
+
+	/// Do not create a copy of the addon in the output directory, only write the packed files.
+	pub fn no_copy(mut self, no_copy: bool) -> Self {
+		self.no_copy = no_copy;
+		self
+	}
+
+	/// Silences stdout (does not silence stderr)
+	pub fn quiet(mut self, quiet: bool) -> Self {
+		self.quiet = quiet;
+		self
+	}
+
+	/// Keeps a build cache in the addon's `.gluapack-cache/` directory, so that unchanged realms and files are not packed or copied again.
+	///
+	/// Defaults to `false`. Has no effect when packing in-place.
+	pub fn cache(mut self, cache: bool) -> Self {
+		self.cache = cache;
+		self
+	}
+
+	/// Checks the syntax of every Lua file before packing, failing with [`PackingError::SyntaxError`] if any are invalid.
+	///
+	/// Defaults to `true`.
+	pub fn validate(mut self, validate: bool) -> Self {
+		self.validate = validate;
+		self
+	}
+
+	/// Uses this config instead of reading the addon's gluapack.json
+	pub fn config(mut self, config: Config) -> Self {
+		self.config = Some(config);
+		self
+	}
+
+	/// Overrides options of the config, whether it's read from the addon or set with [`PackOptions::config`]. In a workspace, the overrides apply to the workspace and every addon's config.
+	pub fn overrides(mut self, overrides: ConfigOverrides) -> Self {
+		self.overrides = overrides;
+		self
+	}
+}
+
+/// The result of a successful [`Packer::pack`].
+#[derive(Debug, Clone)]
+pub struct PackReport {
+	pub sv: RealmReport,
+	pub cl: RealmReport,
+	pub sh: RealmReport,
+
+	/// Files that `auto_realms` couldn't classify. These fall back to the `include_*` patterns.
+	pub unclassified: Vec<String>,
+
+	/// Files that no entry file reaches through `include` or `AddCSLuaFile` calls. These are left out of the cl/sh packs if `prune_unreachable` is set.
+	pub unreachable: Vec<String>,
+
+	/// Lua files that no `include_*` or `entry_*` pattern covers and that aren't excluded, which are copied unpacked. Listed whatever `uncovered` is set to.
+	pub uncovered: Vec<String>,
+
+	/// `include_*`, `entry_*` and `exclude` patterns that don't match any Lua file. Patterns from the defaults and presets aren't checked.
+	pub unused_patterns: Vec<UnusedPattern>,
+
+	/// Bytes saved by `minify`
+	pub minified: MinifyReport,
+
+	/// The Lua files in each cl/sh chunk, by chunk file name. Files split across chunks are listed under each of them.
+	pub chunk_map: BTreeMap<String, Vec<String>>,
+
+	/// Total size of the cl/sh chunks with each `chunk_encoding`. The one that wasn't configured is estimated.
+	pub chunk_encoding: ChunkEncodingReport,
+
+	/// Files stored as references to another file with identical contents by `dedup`
+	pub deduplicated: DedupReport,
+
+	pub unique_id: String,
+	pub elapsed: Duration
+}
+impl PackReport {
+	/// Total number of Lua files that were packed.
+	pub fn unpacked_files(&self) -> usize {
+		self.sv.files + self.cl.files + self.sh.files
+	}
+
+	/// Total number of files gluapack produced, including the cache manifest and loader.
+	pub fn packed_files(&self) -> usize {
+		self.sv.chunks + self.cl.chunks + self.sh.chunks + 2
+	}
+}
+
+/// Bytes saved by minifying each realm's Lua files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MinifyReport {
+	pub sv: usize,
+	pub cl: usize,
+	pub sh: usize
+}
+impl MinifyReport {
+	pub fn total(&self) -> usize {
+		self.sv + self.cl + self.sh
+	}
+}
+
+/// Total size in bytes of the cl/sh chunks with each chunk encoding. Only the configured encoding is written, and the size of the other one is estimated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ChunkEncodingReport {
+	pub lines: usize,
+	pub long_string: usize
+}
+impl std::ops::Add for ChunkEncodingReport {
+	type Output = Self;
+
+	fn add(self, rhs: Self) -> Self {
+		ChunkEncodingReport {
+			lines: self.lines + rhs.lines,
+			long_string: self.long_string + rhs.long_string
+		}
+	}
+}
+
+/// Lua files that `dedup` stored as references to another file with identical contents.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DedupReport {
+	pub files: usize,
+
+	/// Bytes of file contents that weren't packed again, less the size of the references
+	pub bytes: usize
+}
 
 pub struct Packer {
 	pub dir: PathBuf,
 	pub out_dir: PathBuf,
 	pub config: Config,
 	pub unique_id: Option<String>,
-	pub quiet: bool
+	pub quiet: bool,
+	sources: Vec<Source>
 }
 impl Packer {
-	pub async fn pack(dir: PathBuf, out_dir: Option<PathBuf>, no_copy: bool, quiet: bool) -> Result<(usize, usize, Duration), PackingError> {
-		let mut config = {
-			let config_path = dir.join("gluapack.json");
-			if config_path.is_file() {
-				Config::read(config_path)?
-			} else {
-				quietln!(quiet, "WARNING: Couldn't find gluapack.json in your addon. Using the default config.");
-				Config::default()
-			}
+	pub async fn pack(dir: PathBuf, mut options: PackOptions) -> Result<PackReport, PackingError> {
+		let config = match options.config.take() {
+			Some(config) => config,
+			None => Packer::read_config(&dir, options.quiet)?
 		};
+		let config = options.overrides.apply(config)?;
+
+		let source = Source { name: None, dir: dir.join("lua"), config: config.clone() };
+		Packer::pack_sources(dir, config, vec![source], options).await
+	}
+
+	/// Packs the addons listed in the `gluapack-workspace.json` in `dir` into a single pack with a single loader, in the output directory.
+	///
+	/// Each addon's own gluapack.json decides which of its Lua files are packed and its entry files, which are run in workspace order. Options for the pack itself (such as `unique_id` or `chunk_size`) come from the workspace, or from [`PackOptions::config`] if set. Workspaces can't be packed in-place and don't use the build cache.
+	pub async fn pack_workspace(dir: PathBuf, mut options: PackOptions) -> Result<PackReport, PackingError> {
+		if options.in_place {
+			return Err(error!(PackingError::WorkspaceInPlace));
+		}
+		options.cache = false;
+
+		let workspace = Workspace::read(dir.join(Workspace::FILE_NAME))?;
+
+		let mut sources = Vec::with_capacity(workspace.addons.len());
+		for addon in workspace.addons.iter() {
+			let addon_dir = dir.join(addon);
+			if !addon_dir.join("lua").is_dir() {
+				return Err(error!(PackingError::InvalidWorkspaceAddon(addon_dir)));
+			}
+			sources.push(Source {
+				name: Some(addon.to_string_lossy().replace('\\', "/")),
+				dir: addon_dir.join("lua"),
+				config: options.overrides.apply(Packer::read_config(&addon_dir, options.quiet)?)?
+			});
+		}
+
+		let config = options.overrides.apply(options.config.take().unwrap_or(workspace.config))?;
+		Packer::pack_sources(dir, config, sources, options).await
+	}
+
+	/// Reads an addon's config file, or returns the default config if it doesn't have one.
+	pub(crate) fn read_config(dir: &Path, quiet: bool) -> Result<Config, PackingError> {
+		if let Some(config_path) = Config::find(dir) {
+			Config::read(config_path)
+		} else {
+			quietln!(quiet, "WARNING: Couldn't find gluapack.json in {}. Using the default config.", util::canonicalize(dir).display());
+			Ok(Config::default())
+		}
+	}
+
+	async fn pack_sources(dir: PathBuf, config: Config, mut sources: Vec<Source>, options: PackOptions) -> Result<PackReport, PackingError> {
+		let PackOptions { out_dir, in_place, no_copy, quiet, cache, validate, config: _, overrides } = options;
+
+		if !(MIN_CHUNK_SIZE..=MAX_LUA_SIZE).contains(&config.chunk_size) {
+			return Err(error!(PackingError::InvalidChunkSize(config.chunk_size)));
+		}
 
 		if !quiet {
 			config.dump_json();
-			println!("Addon Path: {}", util::canonicalize(&dir).display());
+			if !overrides.is_empty() {
+				println!("Overridden by: {}", overrides.sources().collect::<Vec<_>>().join(", "));
+			}
+			if sources.iter().all(|source| source.name.is_none()) {
+				println!("Addon Path: {}", util::canonicalize(&dir).display());
+			} else {
+				println!("Workspace Path: {}", util::canonicalize(&dir).display());
+				for source in sources.iter() {
+					println!("Addon Path: {}", util::canonicalize(source.dir.parent().unwrap()).display());
+				}
+			}
 		}
 
-		let (in_place, out_dir) = if let Some(out_dir) = out_dir {
-			util::prepare_output_dir(quiet, &out_dir).await;
-			(false, out_dir)
+		let mut build_cache = None;
+		let out_dir = if !in_place {
+			let out_dir = util::resolve_out_dir(&dir, out_dir.as_deref(), "packed", "unpacked");
+			if out_dir == dir {
+				return Err(error!(PackingError::OutputIsAddon));
+			}
+			if cache {
+				let cache_path = BuildCache::path(&dir, &out_dir);
+				let fresh = BuildCache::new(&config, no_copy);
+				match BuildCache::load(&cache_path, &fresh.key).filter(|_| out_dir.is_dir()) {
+					Some(cached) => {
+						// Keep the output directory, we'll only rewrite what changed
+						quietln!(quiet, "Output Path: {}", util::canonicalize(&out_dir).display());
+						quietln!(quiet, "Using build cache");
+						build_cache = Some((cache_path, cached));
+					},
+					None => {
+						util::prepare_output_dir(quiet, &out_dir).await;
+						build_cache = Some((cache_path, fresh));
+					}
+				}
+			} else {
+				util::prepare_output_dir(quiet, &out_dir).await;
+			}
+			out_dir
 		} else {
 			quietln!(quiet, "Output Path: In-place");
-			(true, dir.clone())
+			dir.clone()
 		};
 
-		if quiet && config.entry_cl.is_empty() && config.entry_sh.is_empty() && config.entry_sv.is_empty() {
+		if quiet && sources.iter().all(|source| source.config.entry_cl.is_empty() && source.config.entry_sh.is_empty() && source.config.entry_sv.is_empty()) {
 			println!("WARNING: You have not specified any entry file patterns in your config. gluapack will do nothing after unpacking your addon.");
 		}
 
 		quietln!(quiet);
 
 		// Make sure we exclude any previous gluapack files
-		config.exclude.push(GlobPattern::new("gluapack/*/*"));
-		config.exclude.push(GlobPattern::new("autorun/*_gluapack_*.lua"));
+		for source in sources.iter_mut() {
+			source.config.exclude.extend(PACKED_FILES.iter().map(|pattern| GlobPattern::new(pattern)));
+		}
 
 		// Start packing
 		let mut packer = Packer {
@@ -89,100 +397,287 @@ impl Packer {
 			dir,
 			config,
 			unique_id: None,
-			quiet
+			quiet,
+			sources
 		};
 
 		let started = std::time::Instant::now();
 
-		quietln!(quiet, "Collecting Lua files...");
-
 		packer.out_dir.push("lua");
 		packer.dir.push("lua");
 
-		let ((sv, sv_entry_files), (cl, cl_entry_files), (sh, sh_entry_files)) = tokio::try_join!(
-			packer.collect_lua_files(&packer.config.include_sv, &packer.config.exclude, &packer.config.entry_sv),
-			packer.collect_lua_files(&packer.config.include_cl, &packer.config.exclude, &packer.config.entry_cl),
-			packer.collect_lua_files(&packer.config.include_sh, &packer.config.exclude, &packer.config.entry_sh),
-		)?;
+		let mut unclassified = vec![];
+		let (mut uncovered, mut unused_patterns) = (vec![], vec![]);
+		let mut collected = Vec::with_capacity(packer.sources.len());
+		for source in packer.sources.iter() {
+			let source_collected = packer.collect_source(source, &mut unclassified).await?;
+			let lua_paths = Packer::lua_paths(&source.dir)?;
+			unused_patterns.extend(Packer::find_unused_patterns(source, &lua_paths));
+			uncovered.push((source.config.uncovered, Packer::find_uncovered(source, &source_collected, &lua_paths)));
+			collected.push(source_collected);
+		}
+		let uncovered = packer.check_uncovered(uncovered, &unused_patterns)?;
+		let ((sv, sv_entry_files), (mut cl, cl_entry_files), (mut sh, sh_entry_files)) = packer.merge_sources(collected)?;
+
+		quietln!(quiet, "Finding unreachable Lua files...");
+		let unreachable = packer.find_unreachable([&sv, &cl, &sh], &sv_entry_files, &cl_entry_files, &sh_entry_files);
+		if packer.config.prune_unreachable && !unreachable.is_empty() {
+			quietln!(quiet, "Pruning unreachable Lua files...");
+			cl.retain(|lua_file| unreachable.binary_search(&lua_file.path).is_err());
+			sh.retain(|lua_file| unreachable.binary_search(&lua_file.path).is_err());
+		}
 
-		{
-			quietln!(quiet, "Checking realms...");
-			let mut all_lua_files = HashSet::new();
-			for lua_file in sv.iter().chain(sh.iter()).chain(cl.iter()) {
-				if !all_lua_files.insert(lua_file.path.clone()) {
-					return Err(error!(PackingError::RealmConflict(lua_file.path.clone())));
-				}
-			}
+		if validate {
+			quietln!(quiet, "Validating syntax...");
+			Packer::validate_lua_files([&sv, &cl, &sh])?;
 		}
 
-		let total_unpacked_files = sv.len() + cl.len() + sh.len();
-		if total_unpacked_files == 0 {
+		let minify = packer.config.minify;
+		let (sv, cl, sh, minified) = if minify.sv || minify.cl || minify.sh {
+			quietln!(quiet, "Minifying...");
+
+			let ((sv, sv_saved), (cl, cl_saved), (sh, sh_saved)) = tokio::try_join!(
+				tokio::task::spawn_blocking(move || if minify.sv { Packer::minify_lua_files(sv, quiet) } else { (sv, 0) }),
+				tokio::task::spawn_blocking(move || if minify.cl { Packer::minify_lua_files(cl, quiet) } else { (cl, 0) }),
+				tokio::task::spawn_blocking(move || if minify.sh { Packer::minify_lua_files(sh, quiet) } else { (sh, 0) })
+			).expect("Failed to join threads");
+
+			(sv, cl, sh, MinifyReport { sv: sv_saved, cl: cl_saved, sh: sh_saved })
+		} else {
+			(sv, cl, sh, MinifyReport::default())
+		};
+
+		let duplicates = if packer.config.dedup {
+			quietln!(quiet, "Deduplicating...");
+			Packer::find_duplicates([&sv, &cl, &sh])
+		} else {
+			Default::default()
+		};
+
+		let mut report = PackReport {
+			sv: RealmReport { files: sv.len(), chunks: 0 },
+			cl: RealmReport { files: cl.len(), chunks: 0 },
+			sh: RealmReport { files: sh.len(), chunks: 0 },
+			unclassified,
+			unreachable,
+			uncovered,
+			unused_patterns,
+			minified,
+			chunk_map: BTreeMap::new(),
+			chunk_encoding: ChunkEncodingReport::default(),
+			deduplicated: DedupReport::default(),
+			unique_id: String::new(),
+			elapsed: Duration::default()
+		};
+
+		if report.unpacked_files() == 0 {
 			return Err(error!(PackingError::NoLuaFiles));
 		}
 
+		for (lua_files, duplicates) in [&sv, &cl, &sh].iter().zip(duplicates.iter()) {
+			for lua_file in lua_files.iter() {
+				if let Some(duplicate_of) = duplicates.get(&lua_file.path) {
+					report.deduplicated.files += 1;
+					report.deduplicated.bytes += lua_file.contents.len() - duplicate_of.len() - 1;
+				}
+			}
+		}
+
 		if !in_place {
 			if !no_copy {
-				quietln!(quiet, "Copying addon to output directory...");
-				packer.copy_addon().await?;
+				match build_cache.as_mut() {
+					Some((_, build_cache)) => {
+						quietln!(quiet, "Copying changed files to output directory...");
+
+						// Packed Lua files would just be deleted again
+						let skip = sv.iter().chain(cl.iter()).chain(sh.iter()).map(|lua_file| format!("lua/{}", lua_file.path)).collect::<HashSet<_>>();
+						let from = packer.sources[0].dir.parent().unwrap().to_path_buf();
+						let to = packer.out_dir.parent().unwrap().to_path_buf();
+						let copied = std::mem::take(&mut build_cache.copied);
+
+						build_cache.copied = tokio::task::spawn_blocking(move || cache::copy_addon_incremental(&from, &to, &skip, &copied)).await.expect("Failed to join thread")?;
+					},
+					None => {
+						quietln!(quiet, "Copying addon to output directory...");
+						packer.copy_addon().await?;
+					}
+				}
 			}
 		} else {
 			quietln!(quiet, "Deleting old gluapack files...");
 			packer.delete_old_gluapack_files().await?;
 		}
 
-		quietln!(quiet, "Packing...");
+		let realm_hash = |lua_files: &BTreeSet<LuaFile>, duplicates: &BTreeMap<String, String>| cache::realm_hash(lua_files.iter().map(|lua_file| (lua_file.path.as_str(), lua_file.contents.as_slice(), duplicates.get(&lua_file.path).map(String::as_str))));
+		let realm_hashes = [realm_hash(&sv, &duplicates[0]), realm_hash(&cl, &duplicates[1]), realm_hash(&sh, &duplicates[2])];
+
+		// Realms whose Lua files haven't changed since the last build, and whose packed files are still in the output directory
+		let [sv_cached, cl_cached, sh_cached] = match &build_cache {
+			Some((_, build_cache)) => {
+				let gluapack_dir = build_cache.unique_id.as_ref().map(|unique_id| packer.out_dir.join(format!("gluapack/{}", unique_id)));
+				let cached = |cached: &Option<CachedRealm>, realm: &str, hash: &str| -> Option<CachedRealm> {
+					let (cached, gluapack_dir) = (cached.as_ref()?, gluapack_dir.as_ref()?);
+					if cached.hash == hash && cache::chunk_sizes(gluapack_dir, realm, cached.chunks.len()).as_ref() == Some(&cached.chunks) {
+						Some(cached.clone())
+					} else {
+						None
+					}
+				};
+				[
+					cached(&build_cache.sv, "sv", &realm_hashes[0]),
+					cached(&build_cache.cl, "cl", &realm_hashes[1]),
+					cached(&build_cache.sh, "sh", &realm_hashes[2])
+				]
+			},
+			None => [None, None, None]
+		};
 
-		let ((sv_paths, sv), (cl_paths, cl), (sh_paths, sh)) = tokio::try_join!(
-			tokio::task::spawn_blocking(move || Packer::pack_lua_files(sv, false)),
-			tokio::task::spawn_blocking(move || Packer::pack_lua_files(cl, true)),
-			tokio::task::spawn_blocking(move || Packer::pack_lua_files(sh, true))
+		// The unique ID is a hash of the sv and sh packs, so we can only reuse it if neither changed
+		let cached_unique_id = match &build_cache {
+			Some((_, build_cache)) if packer.config.unique_id.is_none() && sv_cached.is_some() && sh_cached.is_some() => build_cache.unique_id.clone(),
+			_ => None
+		};
+		let (pack_sv, pack_cl, pack_sh) = (
+			sv_cached.is_none() || (packer.config.unique_id.is_none() && cached_unique_id.is_none()),
+			cl_cached.is_none(),
+			sh_cached.is_none() || (packer.config.unique_id.is_none() && cached_unique_id.is_none())
+		);
+
+		if pack_sv || pack_cl || pack_sh {
+			quietln!(quiet, "Packing...");
+		}
+
+		let chunk_encoding = packer.config.chunk_encoding;
+		let [sv_duplicates, cl_duplicates, sh_duplicates] = duplicates;
+		let ((sv_paths, sv, _), (cl_paths, cl, cl_entries), (sh_paths, sh, sh_entries)) = tokio::try_join!(
+			tokio::task::spawn_blocking(move || if pack_sv { Packer::pack_lua_files(sv, None, sv_duplicates) } else { Default::default() }),
+			tokio::task::spawn_blocking(move || if pack_cl { Packer::pack_lua_files(cl, Some(chunk_encoding), cl_duplicates) } else { Default::default() }),
+			tokio::task::spawn_blocking(move || if pack_sh { Packer::pack_lua_files(sh, Some(chunk_encoding), sh_duplicates) } else { Default::default() })
 		).expect("Failed to join threads");
 
-		packer.unique_id = Some(packer.config.unique_id.as_ref().map(|x| x.to_owned()).unwrap_or_else(|| {
-			const HASH_SUBHEX_LENGTH: usize = 16;
+		packer.unique_id = Some(match (&packer.config.unique_id, cached_unique_id) {
+			(Some(unique_id), _) => unique_id.to_owned(),
+			(None, Some(unique_id)) => unique_id,
+			(None, None) => {
+				const HASH_SUBHEX_LENGTH: usize = 16;
+
+				quietln!(quiet, "Calculating hash...");
+
+				let mut sha256 = sha2::Sha256::new();
+				sha256.update(&sv);
+				sha256.update(&sh);
+				format!("{:x}", sha256.finalize())[0..HASH_SUBHEX_LENGTH].to_string()
+			}
+		});
+
+		let gluapack_dir = packer.out_dir.join(format!("gluapack/{}", packer.unique_id()));
 
-			quietln!(quiet, "Calculating hash...");
+		if let Some((_, build_cache)) = &build_cache {
+			// Move the previous build's packed files over if the unique ID changed, so unchanged realms don't need rewriting
+			if let Some(old_unique_id) = build_cache.unique_id.as_ref().filter(|old_unique_id| *old_unique_id != packer.unique_id()) {
+				let old_gluapack_dir = packer.out_dir.join(format!("gluapack/{}", old_unique_id));
+				if old_gluapack_dir.is_dir() {
+					if gluapack_dir.is_dir() {
+						tokio::fs::remove_dir_all(&gluapack_dir).await?;
+					}
+					tokio::fs::rename(old_gluapack_dir, &gluapack_dir).await?;
+				}
+			}
 
-			let mut sha256 = sha2::Sha256::new();
-			sha256.update(&sv);
-			sha256.update(&sh);
-			format!("{:x}", sha256.finalize())[0..HASH_SUBHEX_LENGTH].to_string()
-		}));
+			// Delete the old loader and any other gluapack directories
+			packer.delete_old_gluapack_files().await?;
+		}
 
-		tokio::fs::create_dir_all(packer.out_dir.join(&format!("gluapack/{}", packer.unique_id()))).await.expect("Failed to create gluapack directory");
+		tokio::fs::create_dir_all(&gluapack_dir).await.expect("Failed to create gluapack directory");
 
-		if !sv.is_empty() {
-			quietln!(quiet, "Writing packed serverside files...");
-			tokio::fs::write(packer.out_dir.join(&format!("gluapack/{}/gluapack.sv.lua", packer.unique_id())), sv).await?;
+		match sv_cached {
+			Some(_) => quietln!(quiet, "Skipping unchanged serverside files..."),
+			None => {
+				packer.delete_packed_chunks("sv").await?;
+				if !sv.is_empty() {
+					quietln!(quiet, "Writing packed serverside files...");
+					tokio::fs::write(gluapack_dir.join("gluapack.sv.lua"), sv).await?;
+				}
+			}
 		}
+		report.sv.chunks = if gluapack_dir.join("gluapack.sv.lua").is_file() { 1 } else { 0 };
 
-		let total_packed_files = if !cl.is_empty() || !sh.is_empty() {
+		if pack_cl || pack_sh {
 			quietln!(quiet, "Chunking...");
+		}
+
+		let write_packed_chunks = |bytes: Vec<u8>, entries: Vec<PackedEntry>, chunk_name: &'static str, cached: Option<CachedRealm>| {
+			let packer = &packer;
+			async move {
+				match cached {
+					Some(cached) => {
+						quietln!(quiet, "Skipping unchanged {} chunks...", chunk_name);
+						Ok((cached.manifest_hashes.iter().filter_map(|hash| format::hash_from_hex(hash.as_bytes())).collect(), cached.chunk_map, cached.chunk_encoding))
+					},
+					None => {
+						packer.delete_packed_chunks(chunk_name).await?;
+						packer.write_packed_chunks(bytes, entries, chunk_name).await
+					}
+				}
+			}
+		};
+		let ((hashes_cl, chunk_map_cl, chunk_encoding_cl), (hashes_sh, chunk_map_sh, chunk_encoding_sh)) = tokio::try_join!(
+			write_packed_chunks(cl, cl_entries, "cl", cl_cached),
+			write_packed_chunks(sh, sh_entries, "sh", sh_cached),
+		)?;
 
-			let ((hashes_cl, chunk_n_cl), (hashes_sh, chunk_n_sh)) = tokio::try_join!(
-				packer.write_packed_chunks(cl, "cl"),
-				packer.write_packed_chunks(sh, "sh"),
-			)?;
+		report.cl.chunks = chunk_map_cl.len();
+		report.sh.chunks = chunk_map_sh.len();
+		report.chunk_encoding = chunk_encoding_cl + chunk_encoding_sh;
 
-			if !hashes_cl.is_empty() || !hashes_sh.is_empty() {
-				quietln!(quiet, "Generating clientside Lua cache manifest...");
-				packer.generate_cache_manifest(hashes_cl, hashes_sh).await?;
+		for (realm, chunk_map) in [("cl", &chunk_map_cl), ("sh", &chunk_map_sh)] {
+			for (i, paths) in chunk_map.iter().enumerate() {
+				report.chunk_map.insert(format!("gluapack.{}.{}.lua", i + 1, realm), paths.clone());
 			}
+		}
 
-			chunk_n_cl + chunk_n_sh
-		} else {
-			0
-		};
+		let manifest_hashes = [hashes_cl.iter().map(|hash| format::hash_to_hex(hash)).collect::<Vec<_>>(), hashes_sh.iter().map(|hash| format::hash_to_hex(hash)).collect::<Vec<_>>()];
+
+		if !hashes_cl.is_empty() || !hashes_sh.is_empty() {
+			quietln!(quiet, "Generating clientside Lua cache manifest...");
+			packer.generate_cache_manifest(hashes_cl, hashes_sh).await?;
+		} else if gluapack_dir.join("manifest.lua").is_file() {
+			tokio::fs::remove_file(gluapack_dir.join("manifest.lua")).await?;
+		}
 
 		quietln!(quiet, "Injecting loader...");
 		packer.write_loader(sv_entry_files, cl_entry_files, sh_entry_files).await?;
 
-		if !in_place && !no_copy {
+		if !in_place && !no_copy && build_cache.is_none() {
 			quietln!(quiet, "Deleting unpacked files...");
 			packer.delete_unpacked(sv_paths, cl_paths, sh_paths).await?;
 		}
 
-		Ok((total_unpacked_files, total_packed_files + 3, started.elapsed()))
+		if let Some((cache_path, mut build_cache)) = build_cache {
+			quietln!(quiet, "Saving build cache...");
+
+			let [sv_hash, cl_hash, sh_hash] = realm_hashes;
+			let [cl_manifest_hashes, sh_manifest_hashes] = manifest_hashes;
+			build_cache.unique_id = Some(packer.unique_id().to_owned());
+			build_cache.sv = Some(CachedRealm { hash: sv_hash, chunks: cache::chunk_sizes(&gluapack_dir, "sv", report.sv.chunks).unwrap_or_default(), ..Default::default() });
+			build_cache.cl = Some(CachedRealm { hash: cl_hash, chunks: cache::chunk_sizes(&gluapack_dir, "cl", report.cl.chunks).unwrap_or_default(), manifest_hashes: cl_manifest_hashes, chunk_map: chunk_map_cl, chunk_encoding: chunk_encoding_cl });
+			build_cache.sh = Some(CachedRealm { hash: sh_hash, chunks: cache::chunk_sizes(&gluapack_dir, "sh", report.sh.chunks).unwrap_or_default(), manifest_hashes: sh_manifest_hashes, chunk_map: chunk_map_sh, chunk_encoding: chunk_encoding_sh });
+			build_cache.save(cache_path).await?;
+		}
+
+		report.unique_id = packer.unique_id().to_owned();
+		report.elapsed = started.elapsed();
+
+		Ok(report)
+	}
+
+	/// Deletes a realm's packed files from the output's gluapack directory, if there are any from a previous build.
+	async fn delete_packed_chunks(&self, realm: &str) -> Result<(), PackingError> {
+		let pattern = if realm == "sv" { "gluapack.sv.lua".to_string() } else { format!("gluapack.*.{}.lua", realm) };
+		for path in util::glob(self.out_dir.join(format!("gluapack/{}/{}", self.unique_id(), pattern)).to_string_lossy()).unwrap() {
+			tokio::fs::remove_file(path?).await?;
+		}
+		Ok(())
 	}
 
 	fn unique_id(&self) -> &String {
@@ -190,136 +685,313 @@ impl Packer {
 		self.unique_id.as_ref().unwrap()
 	}
 
-	async fn collect_lua_files(&self, patterns: &[GlobPattern], excludes: &[GlobPattern], entries: &[GlobPattern]) -> Result<(HashSet<LuaFile>, Vec<String>), PackingError> {
-		let mut lua_files = HashSet::new();
-		let mut entry_files = vec![];
-		let mut abort_handles = vec![];
-
-		let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Result<(Vec<u8>, String), std::io::Error>>();
+	/// Checks the syntax of Lua files, returning the first syntax error (by realm, then path).
+	fn validate_lua_files(realms: [&BTreeSet<LuaFile>; 3]) -> Result<(), PackingError> {
+		for lua_file in realms.iter().flat_map(|lua_files| lua_files.iter()) {
+			if let Err(error) = syntax::validate(&lua_file.contents) {
+				return Err(error!(PackingError::SyntaxError(syntax::Diagnostic::new(lua_file.path.clone(), &lua_file.contents, error))));
+			}
+		}
+		Ok(())
+	}
 
-		for pattern in patterns.iter().chain(entries.iter()) {
-			for path in {
-				util::glob(&self.dir.join(pattern.as_str()).to_string_lossy())
-					.expect("Failed to construct glob when joining addon directory")
-					.filter(|result| {
-						match result {
-							Ok(path) => excludes.iter().find(|exclude| exclude.matches_path(path.strip_prefix(&self.dir).unwrap())).is_none(),
-							Err(_) => true,
-						}
-					})
-			} {
-				let fs_path = path?;
-				let path = fs_path.strip_prefix(&self.dir).unwrap().to_string_lossy().into_owned().replace('\\', "/");
-				let tx = tx.clone();
-
-				if !lua_files.insert(LuaFile {
-					path: path.to_owned(),
-					contents: vec![]
-				}) {
-					// We've already included this file, skip it.
-					continue;
+	/// Minifies Lua files, returning them and the number of bytes saved. Files that can't be minified are left as they are.
+	fn minify_lua_files(lua_files: BTreeSet<LuaFile>, quiet: bool) -> (BTreeSet<LuaFile>, usize) {
+		let mut saved = 0;
+		let lua_files = lua_files.into_iter().map(|lua_file| {
+			match minify::minify(&lua_file.contents) {
+				Ok(contents) => {
+					saved += lua_file.contents.len().saturating_sub(contents.len());
+					LuaFile { path: lua_file.path, contents }
+				},
+				Err(error) => {
+					quietln!(quiet, "WARNING: Couldn't minify {} ({} at line {}, column {})", lua_file.path, error.message, error.line, error.column);
+					lua_file
 				}
+			}
+		}).collect();
+		(lua_files, saved)
+	}
 
-				abort_handles.push(
-					tokio::spawn(async move {
-						tx.send(
-							tokio::fs::read(fs_path.clone()).map(|result| {
-								result.map(|contents| {
-									(contents, path)
-								})
-							}).await
-						).ok();
-					})
-				);
+	/// Finds Lua files with the same contents as another, returning the path of the file each should refer to instead, for the sv, cl and sh realms.
+	///
+	/// Every duplicate refers to the first of its copies in the order sh, cl, sv (then by path), so networked files only ever refer to other networked files. Files too small to save anything are left alone.
+	fn find_duplicates(realms: [&BTreeSet<LuaFile>; 3]) -> [BTreeMap<String, String>; 3] {
+		let mut copies: HashMap<&[u8], Vec<(usize, &str)>> = HashMap::new();
+		for realm in [2, 1, 0] {
+			for lua_file in realms[realm].iter() {
+				copies.entry(lua_file.contents.as_slice()).or_default().push((realm, lua_file.path.as_str()));
 			}
 		}
 
-		drop(tx);
-
-		while let Some(result) = rx.recv().await {
-			let (contents, path) = match result {
-				Ok(data) => data,
-				Err(error) => {
-					abort_handles.into_iter().for_each(|handle| handle.abort());
-					return Err(error!(PackingError::IoError(error)));
+		let mut duplicates: [BTreeMap<String, String>; 3] = Default::default();
+		for (contents, copies) in copies {
+			let (_, target) = copies[0];
+			for (realm, path) in copies.into_iter().skip(1) {
+				// The reference needs the target's path and a marker byte
+				if contents.len() > target.len() + 1 {
+					duplicates[realm].insert(path.to_owned(), target.to_owned());
 				}
-			};
+			}
+		}
+		duplicates
+	}
 
-			for entry in entries {
-				if entry.matches(&path) {
-					entry_files.push(path.to_owned());
-				}
+	/// Returns the sorted paths of the collected Lua files that can't be reached from any entry file.
+	fn find_unreachable(&self, realms: [&BTreeSet<LuaFile>; 3], sv_entry_files: &[String], cl_entry_files: &[String], sh_entry_files: &[String]) -> Vec<String> {
+		let lua_files = realms.iter().flat_map(|lua_files| lua_files.iter()).map(|lua_file| (lua_file.path.as_str(), lua_file.contents.as_slice()));
+		let (unreachable, graph) = classify::find_unreachable(lua_files, sv_entry_files, cl_entry_files, sh_entry_files);
+
+		if !unreachable.is_empty() {
+			quietln!(self.quiet, "WARNING: {} Lua file(s) can't be reached from any entry file:", unreachable.len());
+			for path in unreachable.iter() {
+				quietln!(self.quiet, "  {}", path);
 			}
 
-			lua_files.replace(LuaFile {
-				path,
-				contents
-			});
+			let dynamic = graph.unresolved.iter().filter(|call| call.arg.is_none()).count();
+			if dynamic != 0 {
+				quietln!(self.quiet, "WARNING: {} include/AddCSLuaFile call(s) have non-literal arguments and couldn't be followed, so some of these files may still be used", dynamic);
+			}
 		}
 
-		Ok((lua_files, entry_files))
+		unreachable
 	}
 
-	async fn copy_addon(&self) -> Result<(), std::io::Error> {
-		let out_dir = self.out_dir.parent().unwrap(); // pop lua/
+	/// Collects the Lua files of one of the addons being packed into realms, according to its own config.
+	///
+	/// Files are classified by [`classify::classify`], like `gluapack explain` does. Files that `auto_realms` can't classify are added to `unclassified`.
+	async fn collect_source(&self, source: &Source, unclassified: &mut Vec<String>) -> Result<(CollectedLuaFiles, CollectedLuaFiles, CollectedLuaFiles), PackingError> {
+		match &source.name {
+			Some(name) => quietln!(self.quiet, "Collecting Lua files from {}...", name),
+			None => quietln!(self.quiet, "Collecting Lua files...")
+		}
 
-		tokio::fs::remove_dir_all(out_dir).await?;
-		tokio::fs::create_dir_all(out_dir).await?;
+		let config = &source.config;
+		let dir = source.dir.clone();
+		let lua_files = tokio::task::spawn_blocking(move || util::read_lua_files(&dir)).await.expect("Failed to join thread")?;
 
-		fn copy_addon(visited_symlinks: &mut HashSet<PathBuf>, from: PathBuf, to: PathBuf) -> Result<(), std::io::Error> {
-			#[cfg(target_os = "windows")]
-			const FILE_ATTRIBUTE_HIDDEN: u32 = 0x02;
+		if config.auto_realms {
+			quietln!(self.quiet, "Classifying realms...");
+		}
+		let classification = classify::classify(&lua_files, config);
 
-			for dir_entry in from.read_dir()? {
-				let dir_entry = dir_entry?;
+		for call in classification.unresolved.iter() {
+			let function = match call.kind {
+				CallKind::Include => "include",
+				CallKind::AddCSLuaFile => "AddCSLuaFile"
+			};
+			match &call.arg {
+				Some(arg) => quietln!(self.quiet, "WARNING: {}:{}: {}(\"{}\") doesn't refer to a Lua file in this addon", call.file, call.line, function, arg),
+				None => quietln!(self.quiet, "WARNING: {}:{}: can't follow {}() with a non-literal argument", call.file, call.line, function)
+			}
+		}
 
-				let entry;
-				if dir_entry.file_type()?.is_symlink() {
-					let path = dir_entry.path();
-					if visited_symlinks.insert(path.clone()) {
-						entry = path.read_link()?;
-					} else {
-						continue;
+		for file in classification.files.iter().filter(|file| file.unclassified) {
+			match (file.decision, file.realms.as_slice()) {
+				(Decision::Untouched, _) => quietln!(self.quiet, "WARNING: Couldn't classify {} (not packed)", file.path),
+				(Decision::Packed, [realm]) if file.resolved_conflict.is_empty() => quietln!(self.quiet, "WARNING: Couldn't classify {} (falling back to include_{})", file.path, realm),
+				(Decision::Packed, [realm]) => quietln!(self.quiet, "WARNING: Couldn't classify {} (realm conflict: matches include_{}, packing it as {})", file.path, file.resolved_conflict.join(" and include_"), realm),
+				_ => {}
+			}
+			if file.decision != Decision::Conflict {
+				unclassified.push(file.path.clone());
+			}
+		}
+
+		quietln!(self.quiet, "Checking realms...");
+
+		let mut conflicts = vec![];
+		let mut realms: [BTreeSet<LuaFile>; 3] = Default::default();
+		for (file, (path, contents)) in classification.files.iter().zip(lua_files) {
+			debug_assert_eq!(file.path, path);
+			match file.decision {
+				Decision::Packed => {
+					if !file.unclassified && !file.resolved_conflict.is_empty() {
+						quietln!(self.quiet, "Realm conflict: {} matches {}, packing it as {}", file.path, file.resolved_conflict.join(" and "), file.realms[0]);
 					}
-				} else {
-					entry = dir_entry.path();
-				}
+					realms[REALMS.iter().position(|realm| *realm == file.realms[0]).unwrap()].insert(LuaFile { path, contents });
+				},
+				Decision::Conflict => conflicts.push(RealmConflict { path: file.path.clone(), realms: file.realms.clone() }),
+				_ => {}
+			}
+		}
+
+		if !conflicts.is_empty() {
+			return Err(error!(PackingError::RealmConflict(conflicts)));
+		}
+
+		let [sv, cl, sh] = realms;
+		Ok((
+			(sv, classification.entry_files("sv", &config.entry_sv)),
+			(cl, classification.entry_files("cl", &config.entry_cl)),
+			(sh, classification.entry_files("sh", &config.entry_sh))
+		))
+	}
 
-				let file_name = entry.file_name().as_ref().unwrap().to_string_lossy();
+	/// Returns the paths of every Lua file in a `lua/` folder, relative to it.
+	fn lua_paths(dir: &Path) -> Result<Vec<String>, PackingError> {
+		let mut lua_paths = vec![];
+		for path in util::glob(dir.join("**/*.lua").to_string_lossy()).expect("Failed to construct glob when joining addon directory") {
+			let path = path?;
+			if path.is_file() {
+				lua_paths.push(path.strip_prefix(dir).unwrap().to_string_lossy().replace('\\', "/"));
+			}
+		}
+		lua_paths.sort_unstable();
+		Ok(lua_paths)
+	}
 
-				if file_name.starts_with(".") || file_name == "gluapack.json" {
-					// Skip hidden files/dirs and gluapack.json
+	/// Returns the sorted paths of an addon's Lua files that weren't collected into any realm and aren't excluded.
+	fn find_uncovered(source: &Source, collected: &(CollectedLuaFiles, CollectedLuaFiles, CollectedLuaFiles), lua_paths: &[String]) -> Vec<String> {
+		let ((sv, _), (cl, _), (sh, _)) = collected;
+		lua_paths.iter()
+			.filter(|path| !source.config.exclude.iter().any(|exclude| exclude.matches(path)))
+			.filter(|path| {
+				let key = LuaFile { path: path.to_string(), contents: vec![] };
+				!(sv.contains(&key) || cl.contains(&key) || sh.contains(&key))
+			})
+			.cloned()
+			.collect()
+	}
+
+	/// Finds the patterns of an addon's config that don't match any of its Lua files, which is usually a sign of a path relative to the wrong folder.
+	///
+	/// Patterns from the defaults and presets are skipped, as they're only meant to match some addons.
+	fn find_unused_patterns(source: &Source, lua_paths: &[String]) -> Vec<UnusedPattern> {
+		let presets = Config::PRESETS.iter().filter_map(|name| Config::preset(name)).collect::<Vec<_>>();
+		let addon_dir = source.dir.parent().unwrap();
+
+		let mut unused = vec![];
+		for (i, (option, patterns)) in source.config.patterns().iter().enumerate() {
+			for pattern in patterns.iter() {
+				let built_in = PACKED_FILES.contains(&pattern.as_str()) || presets.iter().any(|preset| preset.patterns()[i].1.iter().any(|preset| preset.as_str() == pattern.as_str()));
+				if built_in || lua_paths.iter().any(|path| pattern.matches(path)) {
 					continue;
 				}
+				unused.push(UnusedPattern {
+					option,
+					pattern: pattern.as_str().to_string(),
+					relative_to_root: util::glob(addon_dir.join(pattern.as_str()).to_string_lossy()).map(|mut paths| paths.next().is_some()).unwrap_or(false)
+				});
+			}
+		}
+		unused
+	}
 
-				#[cfg(target_os = "windows")]
-				if std::os::windows::fs::MetadataExt::file_attributes(&entry.metadata()?) & FILE_ATTRIBUTE_HIDDEN != 0 {
-					// Skip hidden files (Windows)
-					continue;
+	/// Warns about unused patterns and uncovered Lua files, or fails if an addon's `uncovered` is `error`. Returns every uncovered Lua file.
+	fn check_uncovered(&self, uncovered: Vec<(Uncovered, Vec<String>)>, unused_patterns: &[UnusedPattern]) -> Result<Vec<String>, PackingError> {
+		if !unused_patterns.is_empty() {
+			quietln!(self.quiet, "WARNING: {} pattern(s) in the config don't match any Lua file:", unused_patterns.len());
+			for unused_pattern in unused_patterns {
+				quietln!(self.quiet, "  {}", unused_pattern);
+			}
+		}
+
+		let errors = uncovered.iter().filter(|(policy, _)| *policy == Uncovered::Error).flat_map(|(_, paths)| paths.iter().cloned()).collect::<Vec<_>>();
+		if !errors.is_empty() {
+			return Err(error!(PackingError::Uncovered(errors)));
+		}
+
+		let warnings = uncovered.iter().filter(|(policy, _)| *policy == Uncovered::Warn).flat_map(|(_, paths)| paths.iter()).collect::<Vec<_>>();
+		if !warnings.is_empty() {
+			quietln!(self.quiet, "WARNING: {} Lua file(s) aren't covered by any include or entry pattern and will be copied unpacked:", warnings.len());
+			for path in warnings {
+				quietln!(self.quiet, "  {}", path);
+			}
+		}
+
+		Ok(uncovered.into_iter().flat_map(|(_, paths)| paths).collect())
+	}
+
+	/// Merges the Lua files collected from each addon being packed, failing if more than one addon has a Lua file at the same path.
+	///
+	/// Entry files are kept in the order of the addons, then in each addon's own order.
+	fn merge_sources(&self, collected: Vec<(CollectedLuaFiles, CollectedLuaFiles, CollectedLuaFiles)>) -> Result<(CollectedLuaFiles, CollectedLuaFiles, CollectedLuaFiles), PackingError> {
+		let mut merged: (CollectedLuaFiles, CollectedLuaFiles, CollectedLuaFiles) = Default::default();
+		let mut addons: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+		for (source, (sv, cl, sh)) in self.sources.iter().zip(collected) {
+			for ((lua_files, entry_files), (merged_lua_files, merged_entry_files)) in IntoIterator::into_iter([sv, cl, sh]).zip([&mut merged.0, &mut merged.1, &mut merged.2]) {
+				for lua_file in lua_files {
+					addons.entry(lua_file.path.clone()).or_default().push(source.name.clone().unwrap_or_default());
+					merged_lua_files.insert(lua_file);
 				}
+				merged_entry_files.extend(entry_files);
+			}
+		}
+
+		let collisions = addons.into_iter()
+			.filter(|(_, addons)| addons.len() > 1)
+			.map(|(path, addons)| PathCollision { path: format!("lua/{}", path), addons })
+			.collect::<Vec<_>>();
 
-				let file_name = file_name.into_owned();
+		if !collisions.is_empty() {
+			return Err(error!(PackingError::PathCollision(collisions)));
+		}
+
+		Ok(merged)
+	}
+
+	/// Copies the addon, or every addon of a workspace, to the output directory. Fails if more than one addon has a file at the same path.
+	async fn copy_addon(&self) -> Result<(), PackingError> {
+		let out_dir = self.out_dir.parent().unwrap(); // pop lua/
+
+		tokio::fs::remove_dir_all(out_dir).await?;
+		tokio::fs::create_dir_all(out_dir).await?;
 
-				if entry.is_dir() {
-					let dir = to.join(&file_name);
-					std::fs::create_dir_all(&dir)?;
-					copy_addon(visited_symlinks, entry, dir)?;
-				} else if entry.is_file() {
-					std::fs::copy(entry, to.join(&file_name))?;
+		/// The addon each copied file came from, by its path in the output directory, and the files more than one addon has.
+		#[derive(Default)]
+		struct Copied {
+			files: HashMap<PathBuf, usize>,
+			collisions: BTreeMap<PathBuf, Vec<usize>>
+		}
+
+		fn copy_addon(visited_symlinks: &mut HashSet<PathBuf>, copied: &mut Copied, source: usize, from: PathBuf, to: PathBuf) -> Result<(), std::io::Error> {
+			for entry in util::walk_addon(&from, visited_symlinks)? {
+				let to = to.join(&entry.relative);
+				if entry.is_dir {
+					std::fs::create_dir_all(&to)?;
+					continue;
+				}
+				match copied.files.get(&to) {
+					Some(first) => copied.collisions.entry(to).or_insert_with(|| vec![*first]).push(source),
+					None => {
+						std::fs::copy(entry.path, &to)?;
+						copied.files.insert(to, source);
+					}
 				}
 			}
 			Ok(())
 		}
 
-		let from = self.dir.parent().unwrap().to_path_buf();
+		let sources = self.sources.iter().map(|source| source.dir.parent().unwrap().to_path_buf()).collect::<Vec<_>>();
 		let to = out_dir.to_path_buf();
 
-		tokio::task::spawn_blocking(move || {
+		let copied = tokio::task::spawn_blocking(move || {
 			let mut visited_symlinks = HashSet::new();
-			copy_addon(&mut visited_symlinks, from, to)
-		}).await.expect("Failed to join thread")
+			let mut copied = Copied::default();
+			for (source, from) in sources.into_iter().enumerate() {
+				copy_addon(&mut visited_symlinks, &mut copied, source, from, to.clone())?;
+			}
+			Result::<_, std::io::Error>::Ok(copied)
+		}).await.expect("Failed to join thread")?;
+
+		if !copied.collisions.is_empty() {
+			return Err(error!(PackingError::PathCollision(copied.collisions.into_iter().map(|(path, sources)| PathCollision {
+				path: path.strip_prefix(out_dir).unwrap_or(&path).to_string_lossy().replace('\\', "/"),
+				addons: sources.into_iter().map(|source| self.sources[source].name.clone().unwrap_or_default()).collect()
+			}).collect())));
+		}
+
+		Ok(())
 	}
 
+	/// Returns true if `path` is the gluapack directory we're packing into. Only possible when using the build cache.
+	fn is_current_gluapack_dir(&self, path: &std::path::Path) -> bool {
+		matches!((&self.unique_id, path.file_name()), (Some(unique_id), Some(file_name)) if file_name == unique_id.as_str())
+	}
+
+	#[allow(clippy::needless_borrows_for_generic_args)]
 	async fn delete_old_gluapack_files(&self) -> Result<(), PackingError> {
 		async fn delete<I, V>(gluapack_dir: I, gluapack_loader: V) -> Result<(), PackingError>
 		where
@@ -338,7 +1010,7 @@ impl Packer {
 		if !self.quiet {
 			let mut gluapack_dir = util::glob(&self.out_dir.join("gluapack/*").to_string_lossy()).unwrap()
 				.filter(|result| match result {
-					Ok(path) => path.is_dir(),
+					Ok(path) => path.is_dir() && !self.is_current_gluapack_dir(path),
 					Err(_) => true
 				})
 				.peekable();
@@ -354,7 +1026,7 @@ impl Packer {
 		} else {
 			let gluapack_dir = util::glob(&self.out_dir.join("gluapack/*").to_string_lossy()).unwrap()
 				.filter(|result| match result {
-					Ok(path) => path.is_dir(),
+					Ok(path) => path.is_dir() && !self.is_current_gluapack_dir(path),
 					Err(_) => true
 				});
 
@@ -366,14 +1038,45 @@ impl Packer {
 		Ok(())
 	}
 
-	fn pack_lua_files(lua_files: HashSet<LuaFile>, is_sent_to_client: bool) -> (Vec<String>, Vec<u8>) {
+	/// Packs a realm's Lua files. `chunk_encoding` is only given for cl/sh packs, which are sent to the client.
+	///
+	/// Files in `duplicates` are packed as a reference to the path they map to, rather than with their contents.
+	#[allow(clippy::needless_borrow, clippy::unnecessary_mut_passed)]
+	fn pack_lua_files(lua_files: BTreeSet<LuaFile>, chunk_encoding: Option<ChunkEncoding>, duplicates: BTreeMap<String, String>) -> (Vec<String>, Vec<u8>, Vec<PackedEntry>) {
 		use std::io::Write;
 
+		let is_sent_to_client = chunk_encoding.is_some();
+
+		if lua_files.is_empty() {
+			return (vec![], vec![], vec![]);
+		}
+
 		let mut file_list = Vec::with_capacity(lua_files.len());
+		let mut entries = Vec::with_capacity(lua_files.len());
 
 		let mut superchunk: Vec<u8> = Vec::with_capacity((lua_files.len() * MAX_LUA_SIZE).min(MEM_PREALLOCATE_MAX));
+		let flags = format::FLAG_CHECKSUMS | match chunk_encoding {
+			Some(ChunkEncoding::Lines) => format::FLAG_ESCAPED_LINES,
+			Some(ChunkEncoding::LongString) => format::FLAG_LONG_STRING_CHUNKS,
+			None => 0
+		} | if duplicates.is_empty() { 0 } else { format::FLAG_DEDUP };
+		superchunk.extend_from_slice(&PackHeader::with_flags(flags).to_bytes());
+
+		// File count, used by `gluapack verify` to detect missing files
+		if is_sent_to_client {
+			superchunk.write_all(format!("{:x}", lua_files.len()).as_bytes()).expect("Failed to write file count into superchunk");
+			superchunk.push(TERMINATOR_HACK);
+		} else {
+			superchunk.extend_from_slice(&(lua_files.len() as u32).to_le_bytes());
+		}
+
 		for mut lua_file in lua_files.into_iter() {
-			superchunk.reserve_exact(lua_file.contents.len() + lua_file.path.len() + 4);
+			superchunk.reserve_exact(lua_file.contents.len() + lua_file.path.len() + 4 + 40);
+
+			let entry_start = superchunk.len();
+
+			let hash = format::hash_file(&lua_file.contents);
+			let duplicate_of = duplicates.get(&lua_file.path);
 
 			superchunk.write_all(&mut lua_file.path.as_bytes()).expect("Failed to write script path into superchunk");
 			if is_sent_to_client {
@@ -382,85 +1085,100 @@ impl Packer {
 				superchunk.push(TERMINATOR_HACK);
 
 				// Write the length of the file as a hex string since we can't use NUL to terminate
-				superchunk.write_all(format!("{:x}", lua_file.contents.len()).as_bytes()).expect("Failed to write Lua file length into superchunk");
+				match duplicate_of {
+					Some(duplicate_of) => {
+						superchunk.push(format::DEDUP_MARKER);
+						superchunk.write_all(duplicate_of.as_bytes()).expect("Failed to write duplicate path into superchunk");
+					},
+					None => superchunk.write_all(format!("{:x}", lua_file.contents.len()).as_bytes()).expect("Failed to write Lua file length into superchunk")
+				}
+				superchunk.push(TERMINATOR_HACK);
+
+				superchunk.write_all(format::hash_to_hex(&hash).as_bytes()).expect("Failed to write Lua file hash into superchunk");
 				superchunk.push(TERMINATOR_HACK);
 			} else {
 				superchunk.push(0);
 
-				debug_assert_eq!((lua_file.contents.len() as u32).to_le_bytes().len(), 4);
-				for byte in (lua_file.contents.len() as u32).to_le_bytes().iter() {
+				let len = if duplicate_of.is_some() { format::DEDUP_SV_LEN } else { lua_file.contents.len() as u32 };
+				debug_assert_eq!(len.to_le_bytes().len(), 4);
+				for byte in len.to_le_bytes().iter() {
 					superchunk.push(*byte);
 				}
+
+				superchunk.extend_from_slice(&hash);
+
+				if let Some(duplicate_of) = duplicate_of {
+					superchunk.write_all(duplicate_of.as_bytes()).expect("Failed to write duplicate path into superchunk");
+					superchunk.push(0);
+				}
 			}
 
-			superchunk.write_all(&mut lua_file.contents).expect("Failed to write Lua file into superchunk");
+			if duplicate_of.is_none() {
+				superchunk.write_all(&mut lua_file.contents).expect("Failed to write Lua file into superchunk");
+			}
 
+			entries.push(PackedEntry { path: lua_file.path.clone(), range: entry_start..superchunk.len() });
 			file_list.push(lua_file.path);
 		}
 
-		(file_list, superchunk)
+		(file_list, superchunk, entries)
 	}
 
-	async fn write_packed_chunks(&self, bytes: Vec<u8>, chunk_name: &'static str) -> Result<(Vec<[u8; 20]>, usize), PackingError> {
-		use tokio::io::AsyncWriteExt;
+	/// Splits a cl/sh pack into chunks using the configured strategy and encoding, and writes them.
+	///
+	/// Returns the chunks' cache manifest hashes, the Lua files in each chunk, and the size of the chunks with each encoding (estimated for the encoding that wasn't configured).
+	async fn write_packed_chunks(&self, bytes: Vec<u8>, entries: Vec<PackedEntry>, chunk_name: &'static str) -> Result<(Vec<[u8; 20]>, Vec<Vec<String>>, ChunkEncodingReport), PackingError> {
+		if bytes.is_empty() {
+			return Ok((vec![], vec![], ChunkEncodingReport::default()));
+		}
 
 		let gluapack_dir = self.out_dir.join(format!("gluapack/{}", self.unique_id()));
 
-		let is_sent_to_client = matches!(chunk_name, "sh" | "cl");
-		if is_sent_to_client {
-			let mut chunk_n = 0;
-
-			let hashes = future::try_join_all(
-				commentify(bytes).chunks(MAX_LUA_SIZE).enumerate().map(|(i, chunk)| {
-					chunk_n += 1;
-					let file_name = format!("gluapack.{}.{}.lua", i + 1, chunk_name);
-					let path = gluapack_dir.join(&file_name);
-					async move {
-						if !chunk.starts_with(COMMENT_START) {
-							let mut f = tokio::fs::File::create(&path).await?;
-							f.write_all(COMMENT_START).await?;
-							f.write_all(&chunk).await?;
-
-							Result::<[u8; 20], std::io::Error>::Ok({
-								let mut sha256 = sha2::Sha256::new();
-								sha256.update(COMMENT_START);
-								sha256.update(chunk);
-								sha256.update(&[0u8]);
-
-								let sha256 = sha256.finalize();
-								sha256[0..20].try_into().unwrap()
-							})
-						} else {
-							tokio::fs::write(&path, &chunk).await?;
-
-							Result::<[u8; 20], std::io::Error>::Ok({
-								let mut sha256 = sha2::Sha256::new();
-								sha256.update(chunk);
-								sha256.update(&[0u8]);
-
-								let sha256 = sha256.finalize();
-								sha256[0..20].try_into().unwrap()
-							})
-						}
+		let (chunk_size, chunking, chunk_encoding) = (self.config.chunk_size, self.config.chunking, self.config.chunk_encoding);
+		let (Chunks { chunks, map }, encoded_sizes) = tokio::task::spawn_blocking(move || {
+			let chunks = match chunking {
+				ChunkingStrategy::Stream => chunking::stream(&bytes, &entries, chunk_size, chunk_encoding)?,
+				ChunkingStrategy::FileAligned => chunking::file_aligned(&bytes, &entries, chunk_size, chunk_encoding)?
+			};
+			let size = chunks.chunks.iter().map(Vec::len).sum();
+
+			// Only estimate the other encoding, which is just reported
+			let encoded_sizes = match chunk_encoding {
+				ChunkEncoding::Lines => ChunkEncodingReport { lines: size, long_string: chunking::estimate_size(&bytes, chunk_size, ChunkEncoding::LongString) },
+				ChunkEncoding::LongString => ChunkEncodingReport { lines: chunking::estimate_size(&bytes, chunk_size, ChunkEncoding::Lines), long_string: size }
+			};
+
+			Result::<_, PackingError>::Ok((chunks, encoded_sizes))
+		}).await.expect("Failed to join thread")?;
+
+		let hashes = future::try_join_all(
+			chunks.into_iter().enumerate().map(|(i, chunk)| {
+				let path = gluapack_dir.join(format!("gluapack.{}.{}.lua", i + 1, chunk_name));
+				async move {
+					// The client compiles every chunk to get it into the Lua cache, so it mustn't contain any code
+					if !chunking::is_inert(&chunk) {
+						return Err(error!(PackingError::ChunkNotInert(path)));
 					}
-				})
-			).await?;
 
-			Ok((hashes, chunk_n))
-		} else {
-			let mut chunk_n = 0;
-
-			future::try_join_all(
-				bytes.chunks(MAX_LUA_SIZE).enumerate().map(|(i, chunk)| {
-					chunk_n += 1;
-					let file_name = format!("gluapack.{}.{}.lua", i + 1, chunk_name);
-					let path = gluapack_dir.join(&file_name);
-					tokio::fs::write(path, chunk)
-				})
-			).await?;
+					tokio::fs::write(&path, &chunk).await?;
 
-			Ok((vec![], chunk_n))
-		}
+					// Garry's Mod won't network the chunk if it's too large, so make sure of what actually ended up on disk
+					let size = tokio::fs::metadata(&path).await?.len();
+					if size > chunk_size as u64 {
+						return Err(error!(PackingError::ChunkTooLarge((path, size, chunk_size))));
+					}
+
+					let mut sha256 = sha2::Sha256::new();
+					sha256.update(&chunk);
+					sha256.update([0u8]);
+
+					let sha256 = sha256.finalize();
+					Result::<[u8; 20], PackingError>::Ok(sha256[0..20].try_into().unwrap())
+				}
+			})
+		).await?;
+
+		Ok((hashes, map, encoded_sizes))
 	}
 
 	async fn generate_cache_manifest(&self, hashes_cl: Vec<[u8; 20]>, hashes_sh: Vec<[u8; 20]>) -> Result<(), PackingError> {
@@ -504,6 +1222,7 @@ impl Packer {
 		Ok(())
 	}
 
+	#[allow(clippy::redundant_static_lifetimes)]
 	async fn write_loader(&self, sv_entry_files: Vec<String>, cl_entry_files: Vec<String>, sh_entry_files: Vec<String>) -> Result<(), PackingError> {
 		const GLUAPACK_LOADER: &'static str = include_str!("gluapack.lua");
 
@@ -534,7 +1253,12 @@ impl Packer {
 		let loader = GLUAPACK_LOADER
 			.replacen("{ENTRY_FILES_SV}", &sv_entry_files, 1)
 			.replacen("{ENTRY_FILES_CL}", &cl_entry_files, 1)
-			.replacen("{ENTRY_FILES_SH}", &sh_entry_files, 1);
+			.replacen("{ENTRY_FILES_SH}", &sh_entry_files, 1)
+			.replacen("{FORMAT_VERSION}", &format::FORMAT_VERSION.to_string(), 1)
+			.replacen("{FORMAT_SUPPORTED_FLAGS}", &format::SUPPORTED_FLAGS.to_string(), 1)
+			.replacen("{FORMAT_FLAG_CHECKSUMS}", &format::FLAG_CHECKSUMS.to_string(), 1)
+			.replacen("{FORMAT_FLAG_ESCAPED_LINES}", &format::FLAG_ESCAPED_LINES.to_string(), 1)
+			.replacen("{FORMAT_FLAG_DEDUP}", &format::FLAG_DEDUP.to_string(), 1);
 
 		tokio::fs::create_dir_all(self.out_dir.join("autorun")).await?;
 		tokio::fs::write(self.out_dir.join(format!("autorun/{}_gluapack_{}.lua", self.unique_id(), env!("CARGO_PKG_VERSION"))), loader).await?;
@@ -542,6 +1266,7 @@ impl Packer {
 		Ok(())
 	}
 
+	#[allow(clippy::useless_conversion)]
 	async fn delete_unpacked(&self, sv_paths: Vec<String>, cl_paths: Vec<String>, sh_paths: Vec<String>) -> Result<(), PackingError> {
 		let mut check_empty = Vec::new();
 
@@ -581,20 +1306,102 @@ pub enum PackingError {
 		backtrace: std::backtrace::Backtrace
 	},
 
-	#[error("gluapack.json error: {error}")]
+	#[error("Config error in {error}")]
 	ConfigError {
-		error: serde_json::Error,
+		error: syntax::Diagnostic,
 		#[cfg(all(debug_assertions, feature = "nightly"))]
 		backtrace: std::backtrace::Backtrace
 	},
 
-	#[error("Realm conflict! This file is included in multiple realms: {error}\nPlease tinker your config and resolve the realm conflicts.")]
+	#[error("Realm conflict! These files are included in multiple realms:{}\nPlease tinker your config or set realm_conflicts to resolve the realm conflicts.", .error.iter().map(|conflict| format!("\n  {}", conflict)).collect::<String>())]
 	RealmConflict {
+		error: Vec<RealmConflict>,
+		#[cfg(all(debug_assertions, feature = "nightly"))]
+		backtrace: std::backtrace::Backtrace
+	},
+
+	#[error("Workspace error in {error}")]
+	WorkspaceError {
+		error: syntax::Diagnostic,
+		#[cfg(all(debug_assertions, feature = "nightly"))]
+		backtrace: std::backtrace::Backtrace
+	},
+
+	#[error("Couldn't find an addon containing a lua/ folder at {}, which is listed in gluapack-workspace.json", .error.display())]
+	InvalidWorkspaceAddon {
+		error: PathBuf,
+		#[cfg(all(debug_assertions, feature = "nightly"))]
+		backtrace: std::backtrace::Backtrace
+	},
+
+	#[error("A workspace can't be packed in-place, as its addons are packed into a single output directory")]
+	WorkspaceInPlace {
+		#[cfg(all(debug_assertions, feature = "nightly"))]
+		backtrace: std::backtrace::Backtrace
+	},
+
+	#[error("These Lua files aren't covered by any include or entry pattern:{}\nAdd them to the include_* patterns of their realm or to exclude, or set uncovered to \"warn\" or \"ignore\".", .error.iter().map(|path| format!("\n  {}", path)).collect::<String>())]
+	Uncovered {
+		error: Vec<String>,
+		#[cfg(all(debug_assertions, feature = "nightly"))]
+		backtrace: std::backtrace::Backtrace
+	},
+
+	#[error("Path collision! These files are in more than one addon of the workspace:{}\nPlease rename or exclude them so that each path is only used by one addon.", .error.iter().map(|collision| format!("\n  {}", collision)).collect::<String>())]
+	PathCollision {
+		error: Vec<PathCollision>,
+		#[cfg(all(debug_assertions, feature = "nightly"))]
+		backtrace: std::backtrace::Backtrace
+	},
+
+	#[error("Invalid config override {error}")]
+	InvalidOverride {
 		error: String,
 		#[cfg(all(debug_assertions, feature = "nightly"))]
 		backtrace: std::backtrace::Backtrace
 	},
 
+	#[error("Output directory cannot be the same as the addon directory!")]
+	OutputIsAddon {
+		#[cfg(all(debug_assertions, feature = "nightly"))]
+		backtrace: std::backtrace::Backtrace
+	},
+
+	#[error("Syntax error in {error}\nFix the error, or use --no-validate to skip syntax validation.")]
+	SyntaxError {
+		error: syntax::Diagnostic,
+		#[cfg(all(debug_assertions, feature = "nightly"))]
+		backtrace: std::backtrace::Backtrace
+	},
+
+	#[error("Invalid chunk_size {error}. It must be between {min} and {max} bytes.", min = MIN_CHUNK_SIZE, max = MAX_LUA_SIZE)]
+	InvalidChunkSize {
+		error: usize,
+		#[cfg(all(debug_assertions, feature = "nightly"))]
+		backtrace: std::backtrace::Backtrace
+	},
+
+	#[error("{} is {} bytes, which is larger than the chunk size ({} bytes). This is a bug in gluapack!", .error.0.display(), .error.1, .error.2)]
+	ChunkTooLarge {
+		error: (PathBuf, u64, usize),
+		#[cfg(all(debug_assertions, feature = "nightly"))]
+		backtrace: std::backtrace::Backtrace
+	},
+
+	#[error("Couldn't fit the pack into {:?} chunks: a chunk needs {} bytes, but chunk_size is {} bytes. This is a bug in gluapack!", .error.0, .error.1, .error.2)]
+	ChunkOverflow {
+		error: (ChunkEncoding, usize, usize),
+		#[cfg(all(debug_assertions, feature = "nightly"))]
+		backtrace: std::backtrace::Backtrace
+	},
+
+	#[error("{} would run code when compiled by clients. This is a bug in gluapack!", .error.display())]
+	ChunkNotInert {
+		error: PathBuf,
+		#[cfg(all(debug_assertions, feature = "nightly"))]
+		backtrace: std::backtrace::Backtrace
+	},
+
 	#[error("No Lua files were found in your addon using this inclusion configuration")]
 	NoLuaFiles {
 		#[cfg(all(debug_assertions, feature = "nightly"))]
@@ -602,7 +1409,6 @@ pub enum PackingError {
 	},
 }
 impl_error!(std::io::Error, PackingError::IoError);
-impl_error!(serde_json::Error, PackingError::ConfigError);
 impl From<glob::GlobError> for PackingError {
 	fn from(error: glob::GlobError) -> Self {
 		Self::IoError {
@@ -0,0 +1,630 @@
+// Syntax validation for Garry's Mod Lua (Lua 5.1 + LuaJIT + GLua extensions)
+
+use crate::lexer::{self, Lexer, Token, TokenKind};
+
+/// A syntax error in a Lua or config file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+	pub message: String,
+
+	/// 1-based line number
+	pub line: usize,
+
+	/// 1-based column (in bytes)
+	pub column: usize
+}
+
+/// A [`SyntaxError`] in a specific file, with the offending line for context.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+	pub path: String,
+	pub message: String,
+	pub line: usize,
+	pub column: usize,
+
+	/// The line the error is on
+	pub excerpt: String
+}
+impl Diagnostic {
+	pub fn new(path: String, src: &[u8], error: SyntaxError) -> Diagnostic {
+		let start = line_start(src, error.line);
+		let end = src[start..].iter().position(|byte| matches!(byte, b'\n' | b'\r')).map(|end| start + end).unwrap_or(src.len());
+		let excerpt = String::from_utf8_lossy(&src[start..end]).into_owned();
+
+		Diagnostic {
+			path,
+			message: error.message,
+			line: error.line,
+			column: error.column,
+			excerpt
+		}
+	}
+}
+impl std::fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		const TAB: &str = "    ";
+
+		let line_number = self.line.to_string();
+		let gutter = " ".repeat(line_number.len());
+
+		// Tabs are expanded so that the caret lines up, and the column is in bytes so skip UTF-8 continuation bytes
+		let excerpt = self.excerpt.replace('\t', TAB);
+		let caret_offset = self.excerpt.as_bytes().iter().take(self.column.saturating_sub(1)).map(|byte| match byte {
+			b'\t' => TAB.len(),
+			0x80..=0xBF => 0,
+			_ => 1
+		}).sum::<usize>();
+
+		writeln!(f, "{}:{}:{}: {}", self.path, self.line, self.column, self.message)?;
+		writeln!(f, " {} | {}", line_number, excerpt)?;
+		write!(f, " {} | {}^", gutter, " ".repeat(caret_offset))
+	}
+}
+
+/// Returns the byte offset of the start of a 1-based line number.
+fn line_start(src: &[u8], line: usize) -> usize {
+	let mut lines = 1;
+	let mut i = 0;
+	while i < src.len() && lines < line {
+		if let first @ (b'\n' | b'\r') = src[i] {
+			lines += 1;
+			if matches!(src.get(i + 1), Some(second @ (b'\n' | b'\r')) if *second != first) {
+				i += 1;
+			}
+		}
+		i += 1;
+	}
+	i
+}
+
+/// Checks a number is well-formed, e.g. `1`, `.5`, `1e-3`, `0xFF`, `0x1p4` or LuaJIT's `1ULL`.
+fn valid_number(text: &[u8]) -> bool {
+	let text = text.to_ascii_lowercase();
+	let text = [&b"ull"[..], b"ll", b"i"].iter().find_map(|suffix| text.strip_suffix(*suffix)).unwrap_or(&text);
+
+	let (digits, exponent, is_digit): (&[u8], u8, fn(&u8) -> bool) = match text.strip_prefix(b"0x") {
+		Some(hex) => (hex, b'p', u8::is_ascii_hexdigit),
+		None => (text, b'e', u8::is_ascii_digit)
+	};
+
+	let (mantissa, exponent) = match digits.iter().position(|byte| *byte == exponent) {
+		Some(pos) => (&digits[..pos], Some(&digits[pos + 1..])),
+		None => (digits, None)
+	};
+
+	let mut parts = mantissa.splitn(2, |byte| *byte == b'.');
+	let (integer, fraction) = (parts.next().unwrap_or_default(), parts.next().unwrap_or_default());
+	if integer.is_empty() && fraction.is_empty() {
+		return false;
+	}
+	if !integer.iter().all(is_digit) || !fraction.iter().all(is_digit) {
+		return false;
+	}
+
+	match exponent {
+		Some(exponent) => {
+			let exponent = exponent.strip_prefix(b"+").or_else(|| exponent.strip_prefix(b"-")).unwrap_or(exponent);
+			!exponent.is_empty() && exponent.iter().all(u8::is_ascii_digit)
+		},
+		None => true
+	}
+}
+
+/// LuaJIT's `LJ_MAX_XLEVEL`
+const MAX_SYNTAX_LEVELS: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExprKind {
+	/// A variable or table index, which can be assigned to
+	Assignable,
+
+	/// A function or method call, which can be a statement
+	Call,
+
+	Other
+}
+
+struct Parser<'a> {
+	tokens: Vec<Token<'a>>,
+	pos: usize,
+
+	/// Where the file ends, for errors at `<eof>`
+	eof: (usize, usize),
+
+	/// Whether each function we're in is a vararg function
+	vararg: Vec<bool>,
+
+	/// How deeply blocks and expressions are nested, counted like LuaJIT's `synlevel_begin`
+	level: usize
+}
+impl<'a> Parser<'a> {
+	#[inline]
+	fn peek(&self) -> Option<&Token<'a>> {
+		self.tokens.get(self.pos)
+	}
+
+	#[inline]
+	fn check(&self, text: &str) -> bool {
+		self.peek().map(|token| token.is(text)).unwrap_or(false)
+	}
+
+	#[inline]
+	fn check_kind(&self, kind: TokenKind) -> bool {
+		self.peek().map(|token| token.kind == kind).unwrap_or(false)
+	}
+
+	#[inline]
+	fn advance(&mut self) {
+		self.pos += 1;
+	}
+
+	fn accept(&mut self, text: &str) -> bool {
+		if self.check(text) {
+			self.advance();
+			true
+		} else {
+			false
+		}
+	}
+
+	/// Returns an error pointing at the current token.
+	fn error(&self, message: &str) -> SyntaxError {
+		let (near, line, column) = match self.peek() {
+			Some(token) => (String::from_utf8_lossy(token.text).into_owned(), token.line, token.column),
+			None => ("<eof>".to_string(), self.eof.0, self.eof.1)
+		};
+
+		// Don't print huge long strings
+		let near = match near.char_indices().nth(40) {
+			Some((end, _)) => format!("{}...", &near[..end]),
+			None => near
+		};
+
+		SyntaxError {
+			message: format!("{} near '{}'", message, near),
+			line,
+			column
+		}
+	}
+
+	/// Enters a block or expression, failing like LuaJIT does once they are nested too deeply.
+	fn enter_level(&mut self) -> Result<(), SyntaxError> {
+		self.level += 1;
+		if self.level >= MAX_SYNTAX_LEVELS {
+			let (line, column) = self.peek().map(|token| (token.line, token.column)).unwrap_or(self.eof);
+			return Err(SyntaxError {
+				message: "chunk has too many syntax levels".to_string(),
+				line,
+				column
+			});
+		}
+		Ok(())
+	}
+
+	#[inline]
+	fn leave_level(&mut self) {
+		self.level -= 1;
+	}
+
+	fn expect(&mut self, text: &str) -> Result<(), SyntaxError> {
+		if self.accept(text) {
+			Ok(())
+		} else {
+			Err(self.error(&format!("'{}' expected", text)))
+		}
+	}
+
+	/// Expects the token closing a block, mentioning where the block was opened if it was on a different line.
+	fn expect_closing(&mut self, text: &str, opener: &str, line: usize) -> Result<(), SyntaxError> {
+		if self.accept(text) {
+			Ok(())
+		} else if self.peek().map(|token| token.line == line).unwrap_or(false) {
+			Err(self.error(&format!("'{}' expected", text)))
+		} else {
+			Err(self.error(&format!("'{}' expected (to close '{}' at line {})", text, opener, line)))
+		}
+	}
+
+	fn name(&mut self) -> Result<(), SyntaxError> {
+		if self.check_kind(TokenKind::Name) {
+			self.advance();
+			Ok(())
+		} else {
+			Err(self.error("<name> expected"))
+		}
+	}
+
+	fn line(&self) -> usize {
+		self.peek().map(|token| token.line).unwrap_or(self.eof.0)
+	}
+
+	fn block_follows(&self) -> bool {
+		match self.peek() {
+			None => true,
+			Some(token) => token.kind == TokenKind::Keyword && (token.is("else") || token.is("elseif") || token.is("end") || token.is("until"))
+		}
+	}
+
+	fn chunk(&mut self) -> Result<(), SyntaxError> {
+		self.block()?;
+		if self.peek().is_some() {
+			return Err(self.error("'<eof>' expected"));
+		}
+		Ok(())
+	}
+
+	fn block(&mut self) -> Result<(), SyntaxError> {
+		self.enter_level()?;
+		while !self.block_follows() {
+			if self.accept("return") {
+				if !self.block_follows() && !self.check(";") {
+					self.expr_list()?;
+				}
+				self.accept(";");
+
+				// Nothing can follow a return statement
+				break;
+			}
+			self.statement()?;
+		}
+		self.leave_level();
+		Ok(())
+	}
+
+	fn statement(&mut self) -> Result<(), SyntaxError> {
+		let line = self.line();
+		let token = match self.peek() {
+			Some(token) if token.kind == TokenKind::Keyword || token.kind == TokenKind::Symbol => token.text,
+			_ => return self.expr_statement()
+		};
+
+		match token {
+			b";" => self.advance(),
+
+			b"if" => {
+				self.advance();
+				self.expr()?;
+				self.expect("then")?;
+				self.block()?;
+				while self.accept("elseif") {
+					self.expr()?;
+					self.expect("then")?;
+					self.block()?;
+				}
+				if self.accept("else") {
+					self.block()?;
+				}
+				self.expect_closing("end", "if", line)?;
+			},
+
+			b"while" => {
+				self.advance();
+				self.expr()?;
+				self.expect("do")?;
+				self.block()?;
+				self.expect_closing("end", "while", line)?;
+			},
+
+			b"do" => {
+				self.advance();
+				self.block()?;
+				self.expect_closing("end", "do", line)?;
+			},
+
+			b"for" => {
+				self.advance();
+				self.name()?;
+				if self.accept("=") {
+					self.expr()?;
+					self.expect(",")?;
+					self.expr()?;
+					if self.accept(",") {
+						self.expr()?;
+					}
+				} else if self.check(",") || self.check("in") {
+					while self.accept(",") {
+						self.name()?;
+					}
+					self.expect("in")?;
+					self.expr_list()?;
+				} else {
+					return Err(self.error("'=' or 'in' expected"));
+				}
+				self.expect("do")?;
+				self.block()?;
+				self.expect_closing("end", "for", line)?;
+			},
+
+			b"repeat" => {
+				self.advance();
+				self.block()?;
+				self.expect_closing("until", "repeat", line)?;
+				self.expr()?;
+			},
+
+			b"function" => {
+				self.advance();
+				self.name()?;
+				while self.accept(".") {
+					self.name()?;
+				}
+				if self.accept(":") {
+					self.name()?;
+				}
+				self.function_body(line)?;
+			},
+
+			b"local" => {
+				self.advance();
+				if self.accept("function") {
+					self.name()?;
+					self.function_body(line)?;
+				} else {
+					self.name()?;
+					while self.accept(",") {
+						self.name()?;
+					}
+					if self.accept("=") {
+						self.expr_list()?;
+					}
+				}
+			},
+
+			b"::" => {
+				self.advance();
+				self.name()?;
+				self.expect("::")?;
+			},
+
+			b"goto" => {
+				self.advance();
+				self.name()?;
+			},
+
+			// LuaJIT allows `break` anywhere in a block, and GLua adds `continue`
+			b"break" | b"continue" => self.advance(),
+
+			_ => return self.expr_statement()
+		}
+
+		Ok(())
+	}
+
+	/// A function call or an assignment.
+	fn expr_statement(&mut self) -> Result<(), SyntaxError> {
+		let kind = self.suffixed_expr()?;
+		if self.check("=") || self.check(",") {
+			if kind != ExprKind::Assignable {
+				return Err(self.error("syntax error"));
+			}
+			while self.accept(",") {
+				if self.suffixed_expr()? != ExprKind::Assignable {
+					return Err(self.error("syntax error"));
+				}
+			}
+			self.expect("=")?;
+			self.expr_list()
+		} else if kind != ExprKind::Call {
+			Err(self.error("syntax error"))
+		} else {
+			Ok(())
+		}
+	}
+
+	fn suffixed_expr(&mut self) -> Result<ExprKind, SyntaxError> {
+		let mut kind = if self.check_kind(TokenKind::Name) {
+			self.advance();
+			ExprKind::Assignable
+		} else if self.check("(") {
+			let line = self.line();
+			self.advance();
+			self.expr()?;
+			self.expect_closing(")", "(", line)?;
+			ExprKind::Other
+		} else {
+			return Err(self.error("unexpected symbol"));
+		};
+
+		loop {
+			if self.accept(".") {
+				self.name()?;
+				kind = ExprKind::Assignable;
+			} else if self.check("[") {
+				self.advance();
+				self.expr()?;
+				self.expect("]")?;
+				kind = ExprKind::Assignable;
+			} else if self.accept(":") {
+				self.name()?;
+				self.call_args()?;
+				kind = ExprKind::Call;
+			} else if self.check("(") || self.check("{") || self.check_kind(TokenKind::String) || self.check_kind(TokenKind::LongString) {
+				self.call_args()?;
+				kind = ExprKind::Call;
+			} else {
+				return Ok(kind);
+			}
+		}
+	}
+
+	fn call_args(&mut self) -> Result<(), SyntaxError> {
+		if self.check_kind(TokenKind::String) || self.check_kind(TokenKind::LongString) {
+			self.advance();
+			Ok(())
+		} else if self.check("{") {
+			self.table()
+		} else if self.check("(") {
+			let line = self.line();
+			if self.pos > 0 && self.tokens[self.pos - 1].end_line() != line {
+				return Err(self.error("ambiguous syntax (function call x new statement)"));
+			}
+			self.advance();
+			if !self.check(")") {
+				self.expr_list()?;
+			}
+			self.expect_closing(")", "(", line)
+		} else {
+			Err(self.error("function arguments expected"))
+		}
+	}
+
+	fn table(&mut self) -> Result<(), SyntaxError> {
+		let line = self.line();
+		self.expect("{")?;
+		while !self.check("}") {
+			if self.accept("[") {
+				self.expr()?;
+				self.expect("]")?;
+				self.expect("=")?;
+				self.expr()?;
+			} else if self.check_kind(TokenKind::Name) && self.tokens.get(self.pos + 1).map(|token| token.is("=")).unwrap_or(false) {
+				self.pos += 2;
+				self.expr()?;
+			} else {
+				self.expr()?;
+			}
+			if !self.accept(",") && !self.accept(";") {
+				break;
+			}
+		}
+		self.expect_closing("}", "{", line)
+	}
+
+	fn function_body(&mut self, line: usize) -> Result<(), SyntaxError> {
+		self.expect("(")?;
+		let mut vararg = false;
+		if !self.check(")") {
+			loop {
+				if self.accept("...") {
+					vararg = true;
+					break;
+				}
+				self.name()?;
+				if !self.accept(",") {
+					break;
+				}
+			}
+		}
+		self.expect(")")?;
+
+		self.vararg.push(vararg);
+		self.block()?;
+		self.vararg.pop();
+
+		self.expect_closing("end", "function", line)
+	}
+
+	fn expr_list(&mut self) -> Result<(), SyntaxError> {
+		self.expr()?;
+		while self.accept(",") {
+			self.expr()?;
+		}
+		Ok(())
+	}
+
+	fn expr(&mut self) -> Result<(), SyntaxError> {
+		self.binary_expr(0)
+	}
+
+	/// Parses an expression whose binary operators bind tighter than `limit`, recursing the way LuaJIT's `expr_binop` does so that nesting is counted the same.
+	fn binary_expr(&mut self, limit: u8) -> Result<(), SyntaxError> {
+		const UNARY: &[&str] = &["-", "not", "#", "!"];
+		const UNARY_PRIORITY: u8 = 8;
+
+		self.enter_level()?;
+
+		if UNARY.iter().any(|op| self.check(op)) {
+			self.advance();
+			self.binary_expr(UNARY_PRIORITY)?;
+		} else {
+			self.simple_expr()?;
+		}
+
+		while let Some((left, right)) = self.binary_priority() {
+			if left <= limit {
+				break;
+			}
+			self.advance();
+			self.binary_expr(right)?;
+		}
+
+		self.leave_level();
+		Ok(())
+	}
+
+	/// The left and right priority of the binary operator at the current token, if it is one.
+	fn binary_priority(&self) -> Option<(u8, u8)> {
+		let token = self.peek()?;
+		if token.kind != TokenKind::Symbol && token.kind != TokenKind::Keyword {
+			return None;
+		}
+		Some(match token.text {
+			b"or" | b"||" => (1, 1),
+			b"and" | b"&&" => (2, 2),
+			b"==" | b"~=" | b"!=" | b"<" | b"<=" | b">" | b">=" => (3, 3),
+			b".." => (5, 4),
+			b"+" | b"-" => (6, 6),
+			b"*" | b"/" | b"%" => (7, 7),
+			b"^" => (10, 9),
+			_ => return None
+		})
+	}
+
+	fn simple_expr(&mut self) -> Result<(), SyntaxError> {
+		let token = match self.peek() {
+			Some(token) => *token,
+			None => return Err(self.error("unexpected symbol"))
+		};
+
+		match token.kind {
+			TokenKind::Number => {
+				if !valid_number(token.text) {
+					return Err(self.error("malformed number"));
+				}
+				self.advance();
+			},
+			TokenKind::String | TokenKind::LongString => self.advance(),
+			TokenKind::Keyword if token.is("nil") || token.is("true") || token.is("false") => self.advance(),
+			TokenKind::Keyword if token.is("function") => {
+				self.advance();
+				self.function_body(token.line)?;
+			},
+			TokenKind::Symbol if token.is("...") => {
+				if !self.vararg.last().copied().unwrap_or(true) {
+					return Err(self.error("cannot use '...' outside a vararg function"));
+				}
+				self.advance();
+			},
+			TokenKind::Symbol if token.is("{") => self.table()?,
+			_ => {
+				self.suffixed_expr()?;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// Checks that `src` is valid Garry's Mod Lua, returning the first syntax error.
+pub fn validate(src: &[u8]) -> Result<(), SyntaxError> {
+	let tokens = Lexer::new(src).collect::<Result<Vec<Token>, _>>().map_err(|error| SyntaxError {
+		message: error.message.to_string(),
+		line: error.line,
+		column: error.column
+	})?;
+
+	let eof = {
+		let last_line = src.rsplit(|byte| matches!(byte, b'\n' | b'\r')).next().unwrap_or_default();
+		(lexer::line_breaks(src) + 1, last_line.len() + 1)
+	};
+
+	let mut parser = Parser {
+		tokens,
+		pos: 0,
+		eof,
+		vararg: vec![],
+		level: 0
+	};
+
+	parser.chunk()
+}
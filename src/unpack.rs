@@ -1,6 +1,6 @@
-use std::{collections::HashSet, ffi::OsString, io::{BufRead, Seek}, path::{Path, PathBuf}, time::Duration};
+use std::{collections::HashSet, ffi::OsString, io::BufRead, path::{Path, PathBuf}, time::Duration};
 
-use crate::{config::GlobPattern, MAX_LUA_SIZE, TERMINATOR_HACK, MEM_PREALLOCATE_MAX, util};
+use crate::{chunking::ChunkFormat, config::{Config, GlobPattern}, format::{self, FileHash, PackHeader, FLAG_CHECKSUMS, FLAG_DEDUP}, MAX_LUA_SIZE, TERMINATOR_HACK, MEM_PREALLOCATE_MAX, RealmReport, util};
 
 lazy_static! {
 	static ref LOADER_GLOB: GlobPattern = GlobPattern::new("autorun/*_gluapack_*.lua");
@@ -10,16 +10,474 @@ lazy_static! {
 	static ref GLUAPACK_DIR: PathBuf = PathBuf::from("gluapack");
 }
 
+/// Options for [`Unpacker::unpack`].
+#[derive(Debug, Default, Clone)]
+pub struct UnpackOptions {
+	out_dir: Option<PathBuf>,
+	in_place: bool,
+	no_copy: bool,
+	quiet: bool
+}
+impl UnpackOptions {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Sets the output directory. Relative to the addon's parent directory. Can be an absolute path.
+	///
+	/// Defaults to `<addon>-unpacked`.
+	pub fn out_dir<P: Into<PathBuf>>(mut self, out_dir: P) -> Self {
+		self.out_dir = Some(out_dir.into());
+		self
+	}
+
+	/// Modifies the addon in-place, rather than creating a copy of the addon.
+	pub fn in_place(mut self, in_place: bool) -> Self {
+		self.in_place = in_place;
+		self
+	}
+
+	/// Do not create a copy of the addon in the output directory, only write the unpacked files.
+	pub fn no_copy(mut self, no_copy: bool) -> Self {
+		self.no_copy = no_copy;
+		self
+	}
+
+	/// Silences stdout (does not silence stderr)
+	pub fn quiet(mut self, quiet: bool) -> Self {
+		self.quiet = quiet;
+		self
+	}
+}
+
+/// The result of a successful [`Unpacker::unpack`].
+#[derive(Debug, Clone)]
+pub struct UnpackReport {
+	pub sv: RealmReport,
+	pub cl: RealmReport,
+	pub sh: RealmReport,
+	pub elapsed: Duration
+}
+impl UnpackReport {
+	/// Total number of Lua files that were unpacked.
+	pub fn unpacked_files(&self) -> usize {
+		self.sv.files + self.cl.files + self.sh.files
+	}
+
+	/// Total number of packed files that were read, including the cache manifest and loader.
+	pub fn packed_files(&self) -> usize {
+		self.sv.chunks + self.cl.chunks + self.sh.chunks + 2
+	}
+}
+
+/// The packed files of a single pack (`lua/gluapack/<unique_id>/`) in a packed addon.
+#[derive(Debug, Clone, Default)]
+pub struct PackDir {
+	pub unique_id: String,
+	pub dir: PathBuf,
+	pub sv: Option<PathBuf>,
+	pub cl: Vec<PathBuf>,
+	pub sh: Vec<PathBuf>,
+	pub manifest: Option<PathBuf>,
+
+	/// `lua/autorun/<unique_id>_gluapack_<version>.lua`
+	pub loader: Option<PathBuf>
+}
+impl PackDir {
+	/// Finds every pack in an addon's `lua/` folder. Chunks are sorted by their chunk number.
+	pub fn discover(lua_dir: &Path) -> Result<Vec<PackDir>, std::io::Error> {
+		let mut pack_dirs = vec![];
+
+		let gluapack_dir = lua_dir.join(&*GLUAPACK_DIR);
+		if !gluapack_dir.is_dir() {
+			return Ok(pack_dirs);
+		}
+
+		for dir_entry in gluapack_dir.read_dir()? {
+			let dir = dir_entry?.path();
+			if !dir.is_dir() {
+				continue;
+			}
+
+			let mut pack_dir = PackDir {
+				unique_id: dir.file_name().unwrap().to_string_lossy().into_owned(),
+				dir: dir.clone(),
+				..Default::default()
+			};
+
+			for dir_entry in dir.read_dir()? {
+				let entry = dir_entry?.path();
+				let file_name = entry.file_name().unwrap().to_string_lossy();
+				if file_name == "gluapack.sv.lua" {
+					pack_dir.sv = Some(entry.clone());
+				} else if file_name == "manifest.lua" {
+					pack_dir.manifest = Some(entry.clone());
+				} else if file_name.ends_with(".sh.lua") {
+					pack_dir.sh.push(entry.clone());
+				} else if file_name.ends_with(".cl.lua") {
+					pack_dir.cl.push(entry.clone());
+				}
+			}
+
+			pack_dir.cl.sort_by_key(|path| chunk_number(path));
+			pack_dir.sh.sort_by_key(|path| chunk_number(path));
+
+			pack_dir.loader = util::glob(lua_dir.join(format!("autorun/{}_gluapack_*.lua", glob::Pattern::escape(&pack_dir.unique_id))).to_string_lossy())
+				.expect("Failed to construct glob when joining addon directory")
+				.find_map(|result| result.ok());
+
+			pack_dirs.push(pack_dir);
+		}
+
+		pack_dirs.sort_by(|a, b| a.unique_id.cmp(&b.unique_id));
+
+		Ok(pack_dirs)
+	}
+
+	/// Parses the sv pack (if any) and the cl and sh packs, filling in the contents of deduplicated files.
+	pub fn read_packs(&self) -> Result<(Option<Pack>, Pack, Pack), UnpackingError> {
+		// Deduplicated files can refer to files in any realm of the pack, so parse them all first
+		let mut sv = self.sv.as_ref().map(Pack::read_sv).transpose()?;
+		let mut cl = Pack::read_chunks(&self.cl)?;
+		let mut sh = Pack::read_chunks(&self.sh)?;
+
+		if let Some(path) = Pack::resolve_duplicates(sv.iter_mut().chain([&mut cl, &mut sh])).into_iter().next() {
+			return Err(error!(UnpackingError::CorruptEntry(path)));
+		}
+
+		Ok((sv, cl, sh))
+	}
+}
+
+/// The entry files listed in a pack's loader.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntryFiles {
+	pub sv: Vec<String>,
+	pub cl: Vec<String>,
+	pub sh: Vec<String>
+}
+impl EntryFiles {
+	/// Reads the `ENTRY_FILES_*` tables injected into a gluapack loader.
+	pub fn read_loader<P: AsRef<Path>>(loader: P) -> Result<EntryFiles, UnpackingError> {
+		let loader_path = loader.as_ref();
+		let loader = std::fs::read_to_string(loader_path)?;
+		let invalid = || error!(UnpackingError::InvalidLoader(loader_path.display().to_string()));
+
+		let table = |name: &str| {
+			let start = loader.find(&format!("local {} = ", name))? + "local  = ".len() + name.len();
+			parse_lua_string_table(&loader[start..])
+		};
+		if let (Some(sv), Some(cl), Some(sh)) = (table("ENTRY_FILES_SV"), table("ENTRY_FILES_CL"), table("ENTRY_FILES_SH")) {
+			return Ok(EntryFiles { sv, cl, sh });
+		}
+
+		// Loaders of gluapack 0.3.0 and older inline the tables into `includeEntryFiles`, which iterates them in the order sh, cl, sv
+		if loader.contains("local ENTRY_FILES_") {
+			return Err(invalid());
+		}
+		let mut tables = loader.match_indices("ipairs({").map(|(i, _)| parse_lua_string_table(&loader[i + "ipairs(".len()..]));
+		let mut next = || tables.next().flatten().ok_or_else(invalid);
+
+		let sh = next()?;
+		let cl = next()?;
+		let sv = next()?;
+		Ok(EntryFiles { sv, cl, sh })
+	}
+
+	pub fn contains(&self, realm: &str, path: &str) -> bool {
+		let entry_files = match realm {
+			"sv" => &self.sv,
+			"cl" => &self.cl,
+			"sh" => &self.sh,
+			_ => return false
+		};
+		entry_files.iter().any(|entry_file| entry_file == path)
+	}
+}
+
+/// Parses a Lua table of double-quoted strings, e.g. `{"a","b\"c"}`
+fn parse_lua_string_table(lua: &str) -> Option<Vec<String>> {
+	let mut chars = lua.chars();
+	if chars.next()? != '{' {
+		return None;
+	}
+
+	let mut strings = vec![];
+	loop {
+		match chars.next()? {
+			'}' => return Some(strings),
+			'"' => {
+				let mut string = String::new();
+				loop {
+					match chars.next()? {
+						'"' => break,
+						'\\' => string.push(chars.next()?),
+						char => string.push(char)
+					}
+				}
+				strings.push(string);
+			},
+			_ => continue
+		}
+	}
+}
+
+/// Extracts `N` from `gluapack.N.<realm>.lua`
+pub(crate) fn chunk_number(path: &Path) -> usize {
+	path.file_name()
+		.and_then(|file_name| file_name.to_str())
+		.and_then(|file_name| file_name.split('.').nth(1))
+		.and_then(|n| n.parse().ok())
+		.unwrap_or(usize::MAX)
+}
+
+/// A single file in a pack.
+#[derive(Debug, Clone)]
+pub struct PackEntry {
+	pub path: String,
+	pub contents: Vec<u8>,
+
+	/// The hash recorded in the pack, if the pack has checksums.
+	pub hash: Option<FileHash>,
+
+	/// Indices of the chunks this file is stored in.
+	pub chunks: std::ops::Range<usize>,
+
+	/// The path of the file this entry is a reference to, if it was deduplicated. Its contents are empty until [`Pack::resolve_duplicates`] fills them in.
+	pub duplicate_of: Option<String>
+}
+
+/// A parsed serverside pack, or all the chunks of a clientside/shared pack joined together.
+#[derive(Debug, Clone)]
+pub struct Pack {
+	pub header: PackHeader,
+
+	/// The number of files recorded in the pack, if the pack has checksums.
+	pub file_count: Option<usize>,
+
+	pub entries: Vec<PackEntry>
+}
+impl Pack {
+	/// Parses a serverside pack file.
+	pub fn read_sv<P: AsRef<Path>>(sv_packed_file: P) -> Result<Pack, UnpackingError> {
+		use std::{fs::File, io::{BufReader, Read}};
+
+		let mut f = BufReader::new(File::open(sv_packed_file)?);
+		let header = PackHeader::read(&mut f)?;
+
+		let checksums = header.has_flag(FLAG_CHECKSUMS);
+		let dedup = header.has_flag(FLAG_DEDUP);
+
+		let file_count = if checksums {
+			let mut file_count = [0u8; 4];
+			f.read_exact(&mut file_count)?;
+			Some(u32::from_le_bytes(file_count) as usize)
+		} else {
+			None
+		};
+
+		fn read_entry(f: &mut BufReader<File>, checksums: bool, dedup: bool) -> Result<Option<PackEntry>, std::io::Error> {
+			let mut path = Vec::with_capacity(255);
+			f.read_until(0, &mut path)?;
+
+			if path.is_empty() {
+				return Ok(None);
+			}
+
+			let mut len = [0u8; 4];
+			f.read_exact(&mut len)?;
+			let len = u32::from_le_bytes(len);
+
+			let hash = if checksums {
+				let mut hash = FileHash::default();
+				f.read_exact(&mut hash)?;
+				Some(hash)
+			} else {
+				None
+			};
+
+			if dedup && len == format::DEDUP_SV_LEN {
+				let mut duplicate_of = Vec::with_capacity(255);
+				f.read_until(0, &mut duplicate_of)?;
+				if duplicate_of.pop() != Some(0) {
+					return Err(std::io::ErrorKind::UnexpectedEof.into());
+				}
+
+				return Ok(Some(PackEntry {
+					path: String::from_utf8_lossy(&path[0..path.len()-1]).into_owned(),
+					contents: vec![],
+					hash,
+					chunks: 0..1,
+					duplicate_of: Some(String::from_utf8_lossy(&duplicate_of).into_owned())
+				}));
+			}
+
+			let mut contents = Vec::with_capacity(len as usize);
+			f.by_ref().take(len as u64).read_to_end(&mut contents)?;
+
+			Ok(Some(PackEntry {
+				path: String::from_utf8_lossy(&path[0..path.len()-1]).into_owned(),
+				contents,
+				hash,
+				chunks: 0..1,
+				duplicate_of: None
+			}))
+		}
+
+		let mut entries = vec![];
+		loop {
+			match read_entry(&mut f, checksums, dedup) {
+				Ok(Some(entry)) => entries.push(entry),
+				Ok(None) => break,
+				Err(error) => if let std::io::ErrorKind::UnexpectedEof = error.kind() {
+					break;
+				} else {
+					return Err(error!(UnpackingError::IoError(error)));
+				},
+			}
+		}
+
+		Ok(Pack { header, file_count, entries })
+	}
+
+	/// Parses the chunks of a clientside or shared pack. The chunks must be in order.
+	pub fn read_chunks<P: AsRef<Path>>(packed_files: &[P]) -> Result<Pack, UnpackingError> {
+		use std::io::{Read, Cursor};
+
+		let chunks = packed_files.iter().map(std::fs::read).collect::<Result<Vec<_>, _>>()?;
+		let format = match chunks.first() {
+			Some(first) => ChunkFormat::detect(first)?,
+			None => ChunkFormat::Unescaped
+		};
+
+		let mut superchunk = Vec::with_capacity((MAX_LUA_SIZE * packed_files.len()).min(MEM_PREALLOCATE_MAX));
+		let mut chunk_ends = Vec::with_capacity(packed_files.len());
+		for chunk in chunks {
+			superchunk.extend_from_slice(&format.decode(&chunk));
+			chunk_ends.push(superchunk.len());
+		}
+
+		fn read_terminated(f: &mut Cursor<Vec<u8>>) -> Result<Vec<u8>, UnpackingError> {
+			let mut field = Vec::with_capacity(16);
+			f.read_until(TERMINATOR_HACK, &mut field)?;
+			if field.pop() != Some(TERMINATOR_HACK) {
+				return Err(error!(UnpackingError::IoError(std::io::ErrorKind::UnexpectedEof.into())));
+			}
+			Ok(field)
+		}
+
+		fn read_entry(f: &mut Cursor<Vec<u8>>, checksums: bool, dedup: bool) -> Result<Option<PackEntry>, UnpackingError> {
+			let mut path = Vec::with_capacity(255);
+			f.read_until(TERMINATOR_HACK, &mut path)?;
+
+			if path.is_empty() {
+				return Ok(None);
+			}
+
+			let len = read_terminated(f)?;
+			let duplicate_of = match len.split_first() {
+				Some((&format::DEDUP_MARKER, duplicate_of)) if dedup => Some(String::from_utf8_lossy(duplicate_of).into_owned()),
+				_ => None
+			};
+			let len = if duplicate_of.is_some() { 0 } else { u32::from_str_radix(std::str::from_utf8(&len)?, 16)? };
+
+			let hash = if checksums {
+				let hash = read_terminated(f)?;
+				Some(format::hash_from_hex(&hash).ok_or_else(|| error!(UnpackingError::CorruptEntry(String::from_utf8_lossy(&path[0..path.len()-1]).into_owned())))?)
+			} else {
+				None
+			};
+
+			let mut contents = Vec::with_capacity(len as usize);
+			f.by_ref().take(len as u64).read_to_end(&mut contents)?;
+
+			Ok(Some(PackEntry {
+				path: String::from_utf8_lossy(&path[0..path.len()-1]).into_owned(),
+				contents,
+				hash,
+				chunks: 0..0,
+				duplicate_of
+			}))
+		}
+
+		let mut f = Cursor::new(superchunk);
+		let header = PackHeader::read(&mut f)?;
+
+		let checksums = header.has_flag(FLAG_CHECKSUMS);
+		let dedup = header.has_flag(FLAG_DEDUP);
+
+		let file_count = if checksums {
+			let file_count = read_terminated(&mut f)?;
+			Some(usize::from_str_radix(std::str::from_utf8(&file_count)?, 16)?)
+		} else {
+			None
+		};
+
+		// Index of the chunk containing this offset into the superchunk
+		let chunk_index = |offset: u64| chunk_ends.iter().position(|end| (*end as u64) > offset).unwrap_or(chunk_ends.len().saturating_sub(1));
+
+		let mut entries = vec![];
+		loop {
+			let start = f.position();
+			match read_entry(&mut f, checksums, dedup) {
+				Ok(Some(mut entry)) => {
+					entry.chunks = chunk_index(start)..chunk_index(f.position().saturating_sub(1)) + 1;
+					entries.push(entry);
+				},
+				Ok(None) => break,
+				Err(UnpackingError::IoError { error, .. }) => if let std::io::ErrorKind::UnexpectedEof = error.kind() {
+					break;
+				} else {
+					return Err(error!(UnpackingError::IoError(error)));
+				}
+				Err(error) => return Err(error),
+			}
+		}
+
+		Ok(Pack { header, file_count, entries })
+	}
+
+	/// Fills in the contents of deduplicated entries from the entries they refer to, which can be in any of the packs of the same pack directory.
+	///
+	/// Returns the paths of the entries whose target couldn't be found.
+	pub fn resolve_duplicates<'a, I: IntoIterator<Item = &'a mut Pack>>(packs: I) -> Vec<String> {
+		let mut packs = packs.into_iter().collect::<Vec<_>>();
+
+		let contents = packs.iter()
+			.flat_map(|pack| pack.entries.iter())
+			.filter(|entry| entry.duplicate_of.is_none())
+			.map(|entry| (entry.path.clone(), entry.contents.clone()))
+			.collect::<std::collections::HashMap<_, _>>();
+
+		let mut unresolved = vec![];
+		for entry in packs.iter_mut().flat_map(|pack| pack.entries.iter_mut()) {
+			if let Some(duplicate_of) = &entry.duplicate_of {
+				match contents.get(duplicate_of) {
+					Some(contents) => entry.contents = contents.clone(),
+					None => unresolved.push(entry.path.clone())
+				}
+			}
+		}
+		unresolved
+	}
+}
+
 pub struct Unpacker {
 	pub dir: PathBuf,
 	pub out_dir: PathBuf,
 	pub quiet: bool
 }
 impl Unpacker {
-	pub async fn unpack(dir: PathBuf, out_dir: Option<PathBuf>, no_copy: bool, quiet: bool) -> Result<(usize, usize, Duration), UnpackingError> {
+	pub async fn unpack(dir: PathBuf, options: UnpackOptions) -> Result<UnpackReport, UnpackingError> {
+		let UnpackOptions { out_dir, in_place, no_copy, quiet } = options;
+
 		quietln!(quiet, "Addon Path: {}", util::canonicalize(&dir).display());
 
-		let out_dir = if let Some(out_dir) = out_dir {
+		let out_dir = if !in_place {
+			let out_dir = util::resolve_out_dir(&dir, out_dir.as_deref(), "unpacked", "packed");
+			if out_dir == dir {
+				return Err(error!(UnpackingError::OutputIsAddon));
+			}
 			util::prepare_output_dir(quiet, &out_dir).await;
 			out_dir
 		} else {
@@ -38,59 +496,55 @@ impl Unpacker {
 
 		let started = std::time::Instant::now();
 
-		let (sv_packed_file, cl_chunk_files, sh_chunk_files) = if no_copy {
-			quietln!(quiet, "Discovering chunk files...");
-
-			let (mut cl_chunk_files, mut sh_chunk_files) = (vec![], vec![]);
-
-			for entry in util::glob(unpacker.dir.join("lua/gluapack/*/*.lua").to_string_lossy()).unwrap().filter_map(|result| result.ok()) {
-				let file_name = entry.file_name().as_ref().unwrap().to_string_lossy();
-				if file_name.ends_with(".sh.lua") {
-					sh_chunk_files.push(entry.clone());
-				} else if file_name.ends_with(".cl.lua") {
-					cl_chunk_files.push(entry.clone());
-				}
-			}
-
-			(
-				util::glob(unpacker.dir.join("lua/gluapack/autorun/*_gluapack_*.lua").to_string_lossy()).unwrap().find_map(|result| result.ok()),
-				cl_chunk_files,
-				sh_chunk_files
-			)
-		} else {
+		if !no_copy && !in_place {
 			quietln!(quiet, "Copying addon to output directory...");
 			let dir = unpacker.dir.clone();
 			let out_dir = unpacker.out_dir.clone();
-			tokio::task::spawn_blocking(move || Unpacker::copy_addon(dir, out_dir)).await.expect("Failed to join thread")?
-		};
+			tokio::task::spawn_blocking(move || Unpacker::copy_addon(dir, out_dir)).await.expect("Failed to join thread")?;
+		}
 
 		unpacker.out_dir.push("lua");
 		unpacker.dir.push("lua");
 
-		let mut total_packed_files = cl_chunk_files.len() + sh_chunk_files.len();
-		let mut total_unpacked_files = 0;
+		quietln!(quiet, "Discovering chunk files...");
+		let pack_dirs = PackDir::discover(&unpacker.dir)?;
 
-		if let Some(sv_packed_file) = sv_packed_file {
-			total_packed_files += 1;
+		let mut report = UnpackReport {
+			sv: RealmReport::default(),
+			cl: RealmReport::default(),
+			sh: RealmReport::default(),
+			elapsed: Duration::default()
+		};
 
-			quietln!(quiet, "Unpacking serverside files...");
-			// Parse the serverside pack file and unpack it!
-			total_unpacked_files += unpacker.parse_sv_packed_file(sv_packed_file).await?;
-		}
+		for pack_dir in pack_dirs {
+			report.cl.chunks += pack_dir.cl.len();
+			report.sh.chunks += pack_dir.sh.len();
 
-		quietln!(quiet, "Unpacking clientside files...");
-		total_unpacked_files += unpacker.parse_packed_files(cl_chunk_files).await?;
+			let (sv, cl, sh) = pack_dir.read_packs()?;
+
+			if let Some(sv) = sv {
+				report.sv.chunks += 1;
+				quietln!(quiet, "Unpacking serverside files...");
+				report.sv.files += unpacker.write_entries(sv)?;
+			}
 
-		quietln!(quiet, "Unpacking shared files...");
-		total_unpacked_files += unpacker.parse_packed_files(sh_chunk_files).await?;
+			quietln!(quiet, "Unpacking clientside files...");
+			report.cl.files += unpacker.write_entries(cl)?;
 
-		Ok((total_unpacked_files, total_packed_files + 2, started.elapsed()))
+			quietln!(quiet, "Unpacking shared files...");
+			report.sh.files += unpacker.write_entries(sh)?;
+		}
+
+		report.elapsed = started.elapsed();
+
+		Ok(report)
 	}
 
-	fn copy_addon(dir: PathBuf, out_dir: PathBuf) -> Result<(Option<PathBuf>, Vec<PathBuf>, Vec<PathBuf>), std::io::Error> {
+	fn copy_addon(dir: PathBuf, out_dir: PathBuf) -> Result<(), std::io::Error> {
 		std::fs::create_dir_all(&out_dir)?;
 
-		fn copy_addon(visited_symlinks: &mut HashSet<PathBuf>, lua_folder: &Path, from: PathBuf, to: PathBuf, sv_packed_file: &mut Option<PathBuf>, cl_chunk_files: &mut Vec<PathBuf>, sh_chunk_files: &mut Vec<PathBuf>) -> Result<(), std::io::Error> {
+		#[allow(clippy::op_ref)]
+		fn copy_addon(visited_symlinks: &mut HashSet<PathBuf>, lua_folder: &Path, from: PathBuf, to: PathBuf) -> Result<(), std::io::Error> {
 			#[cfg(target_os = "windows")]
 			const FILE_ATTRIBUTE_HIDDEN: u32 = 0x02;
 
@@ -112,34 +566,19 @@ impl Unpacker {
 				let file_name = entry.file_name().as_ref().unwrap().to_string_lossy();
 
 				// If we're in <dir>/lua
-				let skip_copy = if let Ok(lua_relative) = entry.strip_prefix(lua_folder) {
+				if let Ok(lua_relative) = entry.strip_prefix(lua_folder) {
 					// Skip gluapack files
 					if entry.is_dir() {
-						lua_relative == &*GLUAPACK_DIR || CHUNK_DIR_GLOB.matches_path(lua_relative)
-					} else {
-						if LOADER_GLOB.matches_path(lua_relative) {
-							continue;
-						} else if CHUNK_FILE_GLOB.matches_path(lua_relative) {
-							// Remember chunk files for later
-							if &file_name == "gluapack.sv.lua" {
-								debug_assert!(sv_packed_file.is_none());
-								*sv_packed_file = Some(entry.clone());
-							} else if file_name.ends_with(".sh.lua") {
-								sh_chunk_files.push(entry.clone());
-							} else if file_name.ends_with(".cl.lua") {
-								cl_chunk_files.push(entry.clone());
-							}
+						if lua_relative == &*GLUAPACK_DIR || CHUNK_DIR_GLOB.matches_path(lua_relative) {
 							continue;
-						} else {
-							false
 						}
+					} else if LOADER_GLOB.matches_path(lua_relative) || CHUNK_FILE_GLOB.matches_path(lua_relative) {
+						continue;
 					}
-				} else {
-					false
-				};
+				}
 
-				if file_name.starts_with(".") || file_name == "gluapack.json" {
-					// Skip hidden files/dirs and gluapack.json
+				if file_name.starts_with('.') || Config::FILE_NAMES.contains(&&*file_name) {
+					// Skip hidden files/dirs and the config file
 					continue;
 				}
 
@@ -153,134 +592,30 @@ impl Unpacker {
 
 				if entry.is_dir() {
 					let dir = to.join(&file_name);
-					if !skip_copy {
-						std::fs::create_dir_all(&dir)?;
-					}
-					copy_addon(visited_symlinks, lua_folder, entry, dir, sv_packed_file, cl_chunk_files, sh_chunk_files)?;
-				} else if entry.is_file() && !skip_copy {
+					std::fs::create_dir_all(&dir)?;
+					copy_addon(visited_symlinks, lua_folder, entry, dir)?;
+				} else if entry.is_file() {
 					std::fs::copy(entry, to.join(&file_name))?;
 				}
 			}
 			Ok(())
 		}
 
-		let mut sv_packed_file = None;
-		let mut cl_chunk_files = vec![];
-		let mut sh_chunk_files = vec![];
-
 		let mut visited_symlinks = HashSet::new();
-		copy_addon(&mut visited_symlinks, &dir.join("lua"), dir, out_dir, &mut sv_packed_file, &mut cl_chunk_files, &mut sh_chunk_files)?;
-
-		Ok((sv_packed_file, cl_chunk_files, sh_chunk_files))
+		copy_addon(&mut visited_symlinks, &dir.join("lua"), dir, out_dir)
 	}
 
-	async fn parse_sv_packed_file(&self, sv_packed_file: PathBuf) -> Result<usize, UnpackingError> {
-		use std::{fs::File, io::{BufReader, Read}};
-
-		let mut entries = 0;
-
-		let mut f = BufReader::new(File::open(sv_packed_file)?);
-		fn read_entry(out_dir: &PathBuf, f: &mut BufReader<File>) -> Result<bool, std::io::Error> {
-			let mut path = Vec::with_capacity(255);
-			f.read_until(0, &mut path)?;
-
-			if path.is_empty() {
-				return Ok(true);
-			}
-
-			let mut len = [0u8; 4];
-			f.read_exact(&mut len)?;
-			let len = u32::from_le_bytes(len);
-
-			let path = out_dir.join(String::from_utf8_lossy(&path[0..path.len()-1]).as_ref());
+	fn write_entries(&self, pack: Pack) -> Result<usize, UnpackingError> {
+		let entries = pack.entries.len();
+		for entry in pack.entries {
+			let path = self.out_dir.join(&entry.path);
 
 			if let Some(parent) = path.parent() {
 				std::fs::create_dir_all(parent)?;
 			}
 
-			let mut out = File::create(path)?;
-			std::io::copy(&mut f.by_ref().take(len as u64), &mut out)?;
-
-			Ok(false)
+			std::fs::write(path, entry.contents)?;
 		}
-		loop {
-			match read_entry(&self.out_dir, &mut f) {
-				Ok(true) => break,
-				Ok(false) => entries += 1,
-				Err(error) => if let std::io::ErrorKind::UnexpectedEof = error.kind() {
-					break;
-				} else {
-					return Err(error!(UnpackingError::IoError(error)));
-				},
-			}
-		}
-
-		Ok(entries)
-	}
-
-	async fn parse_packed_files(&self, packed_files: Vec<PathBuf>) -> Result<usize, UnpackingError> {
-		use std::{fs::File, io::{SeekFrom, BufReader, Read, Cursor}};
-
-		let mut entries = 0;
-
-		fn read_commented_file<P: AsRef<std::path::Path>>(packed_file: P) -> Result<Vec<u8>, std::io::Error> {
-			let mut buf = Vec::with_capacity(packed_file.as_ref().metadata()?.len() as usize);
-			let mut f = BufReader::new(File::open(packed_file)?);
-			loop {
-				let mut line = String::new();
-				f.seek(SeekFrom::Current(2))?;
-				if f.read_line(&mut line)? == 0 {
-					break;
-				}
-				buf.extend_from_slice(&line.as_bytes())
-			}
-			Ok(buf)
-		}
-
-		let mut superchunk = Vec::with_capacity((MAX_LUA_SIZE * packed_files.len()).min(MEM_PREALLOCATE_MAX));
-		for packed_file in packed_files {
-			superchunk.extend_from_slice(&read_commented_file(packed_file)?);
-		}
-
-		fn read_entry(out_dir: &PathBuf, f: &mut std::io::Cursor<Vec<u8>>) -> Result<bool, UnpackingError> {
-			let mut path = Vec::with_capacity(255);
-			f.read_until(TERMINATOR_HACK, &mut path)?;
-
-			if path.is_empty() {
-				return Ok(true);
-			}
-
-			let mut len = Vec::with_capacity(16);
-			f.read_until(TERMINATOR_HACK, &mut len)?;
-
-			let len = u32::from_str_radix(std::str::from_utf8(&len[0..len.len()-1])?, 16)?;
-
-			let path = out_dir.join(String::from_utf8_lossy(&path[0..path.len()-1]).as_ref());
-
-			if let Some(parent) = path.parent() {
-				std::fs::create_dir_all(parent)?;
-			}
-
-			let mut out = File::create(path)?;
-			std::io::copy(&mut f.by_ref().take(len as u64), &mut out)?;
-
-			Ok(false)
-		}
-
-		let mut f = Cursor::new(superchunk);
-		loop {
-			match read_entry(&self.out_dir, &mut f) {
-				Ok(true) => break,
-				Ok(false) => entries += 1,
-				Err(UnpackingError::IoError { error, .. }) => if let std::io::ErrorKind::UnexpectedEof = error.kind() {
-					break;
-				} else {
-					return Err(error!(UnpackingError::IoError(error)));
-				}
-				Err(error) => return Err(error),
-			}
-		}
-
 		Ok(entries)
 	}
 }
@@ -294,6 +629,46 @@ pub enum UnpackingError {
 		backtrace: std::backtrace::Backtrace
 	},
 
+	#[error("Output directory cannot be the same as the addon directory!")]
+	OutputIsAddon {
+		#[cfg(all(debug_assertions, feature = "nightly"))]
+		backtrace: std::backtrace::Backtrace
+	},
+
+	#[error("This addon was packed with a newer version of gluapack (pack format version {error}, this version of gluapack supports up to {})", crate::format::FORMAT_VERSION)]
+	UnsupportedFormatVersion {
+		error: u8,
+		#[cfg(all(debug_assertions, feature = "nightly"))]
+		backtrace: std::backtrace::Backtrace
+	},
+
+	#[error("This addon was packed with pack format flags ({error:#04x}) that this version of gluapack does not understand")]
+	UnsupportedFormatFlags {
+		error: u8,
+		#[cfg(all(debug_assertions, feature = "nightly"))]
+		backtrace: std::backtrace::Backtrace
+	},
+
+	#[error("File format error: corrupt pack header")]
+	InvalidHeader {
+		#[cfg(all(debug_assertions, feature = "nightly"))]
+		backtrace: std::backtrace::Backtrace
+	},
+
+	#[error("File format error: corrupt entry for {error}")]
+	CorruptEntry {
+		error: String,
+		#[cfg(all(debug_assertions, feature = "nightly"))]
+		backtrace: std::backtrace::Backtrace
+	},
+
+	#[error("File format error: couldn't read the entry files of loader {error}")]
+	InvalidLoader {
+		error: String,
+		#[cfg(all(debug_assertions, feature = "nightly"))]
+		backtrace: std::backtrace::Backtrace
+	},
+
 	#[error("UTF-8 error: {error}")]
 	Utf8Error {
 		error: std::str::Utf8Error,
@@ -1,4 +1,6 @@
-use std::path::PathBuf;
+use std::{collections::{BTreeMap, HashSet}, path::{Path, PathBuf}};
+
+use crate::config::Config;
 
 #[macro_export]
 macro_rules! abort {
@@ -65,24 +67,117 @@ pub fn glob<S: AsRef<str>>(pattern: S) -> Result<glob::Paths, glob::PatternError
 	})
 }
 
+/// A file or directory found by [`walk_addon`].
+pub struct AddonEntry {
+	/// Where the entry is, with symlinks followed
+	pub path: PathBuf,
+
+	/// Path relative to the addon root, separated by `/`
+	pub relative: String,
+
+	pub is_dir: bool
+}
+
+/// Lists the files and directories of an addon that are copied to the output directory, directories before their contents.
+///
+/// Hidden files and directories and the config file are skipped. Symlinks are followed, but only the first time they are seen in `visited_symlinks`.
+pub fn walk_addon(dir: &Path, visited_symlinks: &mut HashSet<PathBuf>) -> std::io::Result<Vec<AddonEntry>> {
+	fn visit(visited_symlinks: &mut HashSet<PathBuf>, from: PathBuf, rel: &str, entries: &mut Vec<AddonEntry>) -> std::io::Result<()> {
+		#[cfg(target_os = "windows")]
+		const FILE_ATTRIBUTE_HIDDEN: u32 = 0x02;
+
+		for dir_entry in from.read_dir()? {
+			let dir_entry = dir_entry?;
+
+			let entry;
+			if dir_entry.file_type()?.is_symlink() {
+				let path = dir_entry.path();
+				if visited_symlinks.insert(path.clone()) {
+					entry = path.read_link()?;
+				} else {
+					continue;
+				}
+			} else {
+				entry = dir_entry.path();
+			}
+
+			let file_name = entry.file_name().as_ref().unwrap().to_string_lossy();
+
+			if file_name.starts_with('.') || Config::FILE_NAMES.contains(&&*file_name) {
+				// Skip hidden files/dirs and the config file
+				continue;
+			}
+
+			#[cfg(target_os = "windows")]
+			if std::os::windows::fs::MetadataExt::file_attributes(&entry.metadata()?) & FILE_ATTRIBUTE_HIDDEN != 0 {
+				// Skip hidden files (Windows)
+				continue;
+			}
+
+			let relative = if rel.is_empty() { file_name.into_owned() } else { format!("{}/{}", rel, file_name) };
+
+			if entry.is_dir() {
+				entries.push(AddonEntry { path: entry.clone(), relative: relative.clone(), is_dir: true });
+				visit(visited_symlinks, entry, &relative, entries)?;
+			} else if entry.is_file() {
+				entries.push(AddonEntry { path: entry, relative, is_dir: false });
+			}
+		}
+		Ok(())
+	}
+
+	let mut entries = vec![];
+	visit(visited_symlinks, dir.to_path_buf(), "", &mut entries)?;
+	Ok(entries)
+}
+
+/// Reads every Lua file in a `lua/` folder, by path relative to it.
+pub fn read_lua_files(dir: &Path) -> std::io::Result<BTreeMap<String, Vec<u8>>> {
+	let mut lua_files = BTreeMap::new();
+	for path in glob(dir.join("**/*.lua").to_string_lossy()).expect("Failed to construct glob when joining addon directory") {
+		let fs_path = path.map_err(glob::GlobError::into_error)?;
+		if fs_path.is_file() {
+			let path = fs_path.strip_prefix(dir).unwrap().to_string_lossy().replace('\\', "/");
+			lua_files.insert(path, std::fs::read(&fs_path)?);
+		}
+	}
+	Ok(lua_files)
+}
+
 #[inline(always)]
-pub fn canonicalize(path: &PathBuf) -> PathBuf {
-	dunce::canonicalize(path).as_ref().unwrap_or(path).to_owned()
+pub fn canonicalize(path: &Path) -> PathBuf {
+	dunce::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Resolves the output directory of an addon.
+///
+/// Relative paths are relative to the addon's parent directory. If no output directory is given, `<addon>-<suffix_to>` is used (stripping any `-<suffix_from>` suffix from the addon's directory name).
+pub fn resolve_out_dir(dir: &Path, out_dir: Option<&Path>, suffix_to: &str, suffix_from: &str) -> PathBuf {
+	let parent = dir.parent().unwrap_or(dir);
+	match out_dir {
+		Some(out_dir) if out_dir.is_absolute() => out_dir.to_path_buf(),
+		Some(out_dir) => parent.join(out_dir),
+		None => {
+			let name = dir.file_name().unwrap_or_default().to_string_lossy();
+			let name = name.strip_suffix(&format!("-{}", suffix_from)).unwrap_or(&name);
+			parent.join(format!("{}-{}", name, suffix_to))
+		}
+	}
 }
 
 #[inline(always)]
-pub async fn prepare_output_dir(quiet: bool, out_dir: &PathBuf) {
+pub async fn prepare_output_dir(quiet: bool, out_dir: &Path) {
 	if out_dir.is_dir() {
 		quietln!(quiet, "Deleting old output directory...");
-		tokio::fs::remove_dir_all(&out_dir).await.expect("Failed to delete existing output directory");
+		tokio::fs::remove_dir_all(out_dir).await.expect("Failed to delete existing output directory");
 	} else if out_dir.is_file() {
 		quietln!(quiet, "Deleting old output directory...");
-		tokio::fs::remove_file(&out_dir).await.expect("Failed to delete existing output directory");
+		tokio::fs::remove_file(out_dir).await.expect("Failed to delete existing output directory");
 	}
 
-	let result = tokio::fs::create_dir_all(&out_dir).await;
+	let result = tokio::fs::create_dir_all(out_dir).await;
 
-	quietln!(quiet, "Output Path: {}", canonicalize(&out_dir).display());
+	quietln!(quiet, "Output Path: {}", canonicalize(out_dir).display());
 
 	result.expect("Failed to create output directory");
 }
\ No newline at end of file
@@ -0,0 +1,202 @@
+use std::{path::{Path, PathBuf}, time::Duration};
+
+use crate::{format::{self, FileHash, FLAG_CHECKSUMS}, unpack::{Pack, PackDir, UnpackingError}};
+
+/// Something in a packed addon that doesn't match what gluapack produced.
+#[derive(Debug, Clone)]
+pub enum Mismatch {
+	/// A file's contents don't match the hash recorded for it.
+	FileHash { expected: FileHash, actual: FileHash },
+
+	/// The pack contains a different number of files than it recorded.
+	FileCount { expected: usize, actual: usize },
+
+	/// The pack was created by a version of gluapack that did not record checksums.
+	NoChecksums,
+
+	/// The pack couldn't be parsed at all.
+	Unreadable(String),
+
+	/// A chunk's cache hash doesn't match the one in `manifest.lua`.
+	ManifestHash { expected: FileHash, actual: FileHash },
+
+	/// The number of chunks doesn't match the number of hashes in `manifest.lua`.
+	ManifestChunkCount { expected: usize, actual: usize },
+
+	/// There are networked chunks but no `manifest.lua`.
+	ManifestMissing,
+
+	/// A deduplicated file refers to a file that isn't in the pack.
+	DuplicateMissing(String)
+}
+impl std::fmt::Display for Mismatch {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Mismatch::FileHash { expected, actual } => write!(f, "hash mismatch (expected {}, got {})", format::hash_to_hex(expected), format::hash_to_hex(actual)),
+			Mismatch::FileCount { expected, actual } => write!(f, "file count mismatch (expected {}, got {})", expected, actual),
+			Mismatch::NoChecksums => write!(f, "no checksums recorded (packed with an older version of gluapack)"),
+			Mismatch::Unreadable(error) => write!(f, "unreadable ({})", error),
+			Mismatch::ManifestHash { expected, actual } => write!(f, "manifest.lua hash mismatch (expected {}, got {})", format::hash_to_hex(expected), format::hash_to_hex(actual)),
+			Mismatch::ManifestChunkCount { expected, actual } => write!(f, "manifest.lua lists {} chunk(s), found {}", expected, actual),
+			Mismatch::ManifestMissing => write!(f, "manifest.lua is missing"),
+			Mismatch::DuplicateMissing(duplicate_of) => write!(f, "duplicate of {}, which isn't in the pack", duplicate_of)
+		}
+	}
+}
+
+/// A problem found by [`Verifier::verify`].
+#[derive(Debug, Clone)]
+pub struct Problem {
+	/// The file or chunk (relative to `lua/`) the problem was found in.
+	pub path: String,
+	pub mismatch: Mismatch
+}
+
+/// The result of [`Verifier::verify`].
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+	/// Number of packed Lua files that were checked.
+	pub files: usize,
+
+	/// Number of packed files (sv packs and cl/sh chunks) that were checked.
+	pub chunks: usize,
+
+	pub problems: Vec<Problem>,
+	pub elapsed: Duration
+}
+impl VerifyReport {
+	pub fn is_ok(&self) -> bool {
+		self.problems.is_empty()
+	}
+}
+
+pub struct Verifier {
+	pub dir: PathBuf,
+	report: VerifyReport
+}
+impl Verifier {
+	/// Checks that every file in a packed addon matches the hashes recorded when it was packed.
+	pub async fn verify(dir: PathBuf) -> Result<VerifyReport, UnpackingError> {
+		tokio::task::spawn_blocking(move || {
+			let started = std::time::Instant::now();
+
+			let mut verifier = Verifier {
+				dir: dir.join("lua"),
+				report: VerifyReport::default()
+			};
+
+			for pack_dir in PackDir::discover(&verifier.dir)? {
+				let mut packs = vec![];
+				if let Some(sv) = &pack_dir.sv {
+					verifier.report.chunks += 1;
+					packs.push((sv, Pack::read_sv(sv)));
+				}
+				for chunks in [&pack_dir.cl, &pack_dir.sh].iter() {
+					if let Some(first) = chunks.first() {
+						verifier.report.chunks += chunks.len();
+						packs.push((first, Pack::read_chunks(chunks)));
+					}
+				}
+
+				// Deduplicated files can refer to files in any realm of the pack
+				let unresolved = Pack::resolve_duplicates(packs.iter_mut().filter_map(|(_, pack)| pack.as_mut().ok()));
+				for (packed_file, pack) in packs {
+					verifier.verify_pack(packed_file, pack, &unresolved);
+				}
+
+				verifier.verify_manifest(&pack_dir)?;
+			}
+
+			verifier.report.elapsed = started.elapsed();
+			Ok(verifier.report)
+		}).await.expect("Failed to join thread")
+	}
+
+	fn relative(&self, path: &Path) -> String {
+		path.strip_prefix(&self.dir).unwrap_or(path).to_string_lossy().replace('\\', "/")
+	}
+
+	fn problem(&mut self, path: String, mismatch: Mismatch) {
+		self.report.problems.push(Problem { path, mismatch });
+	}
+
+	/// Checks the hashes of a pack's entries. `unresolved` are the paths of deduplicated entries whose target couldn't be found.
+	fn verify_pack(&mut self, packed_file: &Path, pack: Result<Pack, UnpackingError>, unresolved: &[String]) {
+		let packed_file = self.relative(packed_file);
+
+		let pack = match pack {
+			Ok(pack) => pack,
+			Err(error) => return self.problem(packed_file, Mismatch::Unreadable(error.to_string()))
+		};
+
+		if !pack.header.has_flag(FLAG_CHECKSUMS) {
+			return self.problem(packed_file, Mismatch::NoChecksums);
+		}
+
+		if let Some(expected) = pack.file_count {
+			if expected != pack.entries.len() {
+				self.problem(packed_file, Mismatch::FileCount { expected, actual: pack.entries.len() });
+			}
+		}
+
+		for entry in pack.entries {
+			self.report.files += 1;
+			if let (Some(duplicate_of), true) = (&entry.duplicate_of, unresolved.contains(&entry.path)) {
+				self.problem(entry.path.clone(), Mismatch::DuplicateMissing(duplicate_of.clone()));
+			} else if let Some(expected) = entry.hash {
+				let actual = format::hash_file(&entry.contents);
+				if expected != actual {
+					self.problem(entry.path, Mismatch::FileHash { expected, actual });
+				}
+			}
+		}
+	}
+
+	fn verify_manifest(&mut self, pack_dir: &PackDir) -> Result<(), UnpackingError> {
+		if pack_dir.cl.is_empty() && pack_dir.sh.is_empty() {
+			return Ok(());
+		}
+
+		let manifest = match &pack_dir.manifest {
+			Some(manifest) => std::fs::read_to_string(manifest)?,
+			None => {
+				self.problem(self.relative(&pack_dir.dir.join("manifest.lua")), Mismatch::ManifestMissing);
+				return Ok(());
+			}
+		};
+
+		for (realm, chunks) in [("cl", &pack_dir.cl), ("sh", &pack_dir.sh)].iter() {
+			let hashes = parse_manifest(&manifest, realm);
+			if hashes.len() != chunks.len() {
+				self.problem(self.relative(pack_dir.manifest.as_ref().unwrap()), Mismatch::ManifestChunkCount { expected: hashes.len(), actual: chunks.len() });
+			}
+
+			for (chunk, expected) in chunks.iter().zip(hashes) {
+				let actual = format::hash_chunk(&std::fs::read(chunk)?);
+				match expected {
+					Some(expected) if expected == actual => {},
+					Some(expected) => self.problem(self.relative(chunk), Mismatch::ManifestHash { expected, actual }),
+					None => self.problem(self.relative(chunk), Mismatch::Unreadable("invalid hash in manifest.lua".to_string()))
+				}
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// Extracts the chunk hashes of a realm from `return{sh={"<hash>",...},cl={"<hash>",...}}`
+fn parse_manifest(manifest: &str, realm: &str) -> Vec<Option<FileHash>> {
+	let key = format!("{}={{", realm);
+	let start = match manifest.find(&key) {
+		Some(start) => start + key.len(),
+		None => return vec![]
+	};
+	let hashes = &manifest[start..];
+	let hashes = &hashes[..hashes.find('}').unwrap_or(hashes.len())];
+	hashes.split(',')
+		.map(|hash| hash.trim().trim_matches('"'))
+		.filter(|hash| !hash.is_empty())
+		.map(|hash| format::hash_from_hex(hash.as_bytes()))
+		.collect()
+}
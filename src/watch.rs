@@ -0,0 +1,107 @@
+// Polls an addon for changes to its Lua files and config, for `gluapack pack --watch`
+
+use std::{collections::BTreeMap, path::{Path, PathBuf}, time::{Duration, SystemTime}};
+
+use crate::config::Config;
+
+/// How often the addon is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long the addon must go unchanged before a burst of edits is considered finished.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Modification time and size of every watched file.
+type Snapshot = BTreeMap<PathBuf, (Option<SystemTime>, u64)>;
+
+fn snapshot(dir: &Path) -> Snapshot {
+	fn visit(dir: &Path, snapshot: &mut Snapshot) {
+		let entries = match dir.read_dir() {
+			Ok(entries) => entries,
+			Err(_) => return
+		};
+		for entry in entries.filter_map(Result::ok) {
+			let path = entry.path();
+			match entry.metadata() {
+				Ok(metadata) if metadata.is_dir() => visit(&path, snapshot),
+				Ok(metadata) => {
+					snapshot.insert(path, (metadata.modified().ok(), metadata.len()));
+				},
+				Err(_) => {}
+			}
+		}
+	}
+
+	let mut snapshot = Snapshot::new();
+	visit(&dir.join("lua"), &mut snapshot);
+
+	for file_name in Config::FILE_NAMES.iter() {
+		let config_path = dir.join(file_name);
+		if let Ok(metadata) = config_path.metadata() {
+			snapshot.insert(config_path, (metadata.modified().ok(), metadata.len()));
+		}
+	}
+
+	snapshot
+}
+
+/// Watches an addon's `lua/` tree and config file for changes.
+pub struct Watcher {
+	dir: PathBuf,
+	snapshot: Snapshot,
+	poll_interval: Duration,
+	debounce: Duration
+}
+impl Watcher {
+	pub async fn new(dir: PathBuf) -> Watcher {
+		let snapshot = Watcher::snapshot(dir.clone()).await;
+		Watcher { dir, snapshot, poll_interval: POLL_INTERVAL, debounce: DEBOUNCE }
+	}
+
+	/// Sets how often the addon is checked for changes.
+	///
+	/// Defaults to 250ms.
+	pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+		self.poll_interval = poll_interval;
+		self
+	}
+
+	/// Sets how long the addon must go unchanged before a burst of edits is considered finished.
+	///
+	/// Defaults to 500ms.
+	pub fn debounce(mut self, debounce: Duration) -> Self {
+		self.debounce = debounce;
+		self
+	}
+
+	async fn snapshot(dir: PathBuf) -> Snapshot {
+		tokio::task::spawn_blocking(move || snapshot(&dir)).await.expect("Failed to join threads")
+	}
+
+	/// Waits until something in the addon changes, and then until it has stopped changing for a moment.
+	///
+	/// Returns the number of files that were added, modified or removed.
+	pub async fn changed(&mut self) -> usize {
+		let mut latest = loop {
+			tokio::time::sleep(self.poll_interval).await;
+			let snapshot = Watcher::snapshot(self.dir.clone()).await;
+			if snapshot != self.snapshot {
+				break snapshot;
+			}
+		};
+
+		loop {
+			tokio::time::sleep(self.debounce).await;
+			let snapshot = Watcher::snapshot(self.dir.clone()).await;
+			if snapshot == latest {
+				break;
+			}
+			latest = snapshot;
+		}
+
+		let changed = latest.iter().filter(|(path, state)| self.snapshot.get(*path) != Some(state)).count()
+			+ self.snapshot.keys().filter(|path| !latest.contains_key(*path)).count();
+
+		self.snapshot = latest;
+		changed
+	}
+}
@@ -0,0 +1,96 @@
+use gluapack::analysis::IncludeGraph;
+
+#[test]
+fn classifies_realms_from_calls() {
+	let files: &[(&str, &[u8])] = &[
+		("autorun/myaddon.lua", b"
+			if SERVER then
+				AddCSLuaFile()
+				AddCSLuaFile(\"myaddon/cl_hud.lua\")
+				AddCSLuaFile(\"myaddon/shared.lua\")
+				include(\"myaddon/server.lua\")
+			else
+				include \"myaddon/cl_hud.lua\"
+			end
+			include(\"myaddon/shared.lua\")
+			include(\"myaddon/\" .. name)
+		"),
+		("myaddon/server.lua", b"-- include(\"myaddon/commented.lua\")"),
+		("myaddon/cl_hud.lua", b"include(\"helper.lua\")"),
+		("myaddon/helper.lua", b""),
+		("myaddon/shared.lua", b""),
+		("myaddon/commented.lua", b""),
+	];
+
+	let graph = IncludeGraph::build(files.iter().copied());
+	let realms = graph.propagate(&[], &[], &["autorun/myaddon.lua".to_string()]);
+	let realm = |path: &str| realms.get(path).and_then(|realms| realms.realm());
+
+	assert_eq!(realm("autorun/myaddon.lua"), Some("sh"));
+	assert_eq!(realm("myaddon/server.lua"), Some("sv"));
+	assert_eq!(realm("myaddon/cl_hud.lua"), Some("cl"));
+	// Included clientside, but never sent to clients
+	assert_eq!(realm("myaddon/helper.lua"), None);
+	assert_eq!(realm("myaddon/shared.lua"), Some("sh"));
+	assert_eq!(realm("myaddon/commented.lua"), None);
+
+	assert_eq!(graph.unresolved.len(), 1);
+	assert_eq!(graph.unresolved[0].file, "autorun/myaddon.lua");
+	assert_eq!(graph.unresolved[0].arg, None);
+}
+
+#[test]
+fn never_sends_server_code_to_clients() {
+	// secret.lua is included on both sides, but clients can't include a file that was never sent to them
+	let files: &[(&str, &[u8])] = &[
+		("autorun/myaddon.lua", b"AddCSLuaFile()\ninclude('myaddon/secret.lua')\n"),
+		("myaddon/secret.lua", b"")
+	];
+	let graph = IncludeGraph::build(files.iter().copied());
+	let realms = graph.propagate(&[], &[], &["autorun/myaddon.lua".to_string()]);
+	assert_eq!(realms["myaddon/secret.lua"].realm(), Some("sv"));
+	assert!(realms["myaddon/secret.lua"].client);
+}
+
+#[test]
+fn early_returns_narrow_the_realm() {
+	let files: &[(&str, &[u8])] = &[
+		("autorun/myaddon.lua", b"
+			AddCSLuaFile()
+			AddCSLuaFile(\"myaddon/cl_init.lua\")
+			include(\"myaddon/shared.lua\")
+			if CLIENT then include(\"myaddon/cl_init.lua\") end
+			if CLIENT then return end
+			include('myaddon/secret.lua')
+		"),
+		("myaddon/shared.lua", b"AddCSLuaFile()"),
+		("myaddon/secret.lua", b""),
+		("myaddon/cl_init.lua", b"
+			if SERVER then return; end
+			include(\"myaddon/cl_hud.lua\")
+			function Foo()
+				if !CLIENT then return end
+				AddCSLuaFile(\"myaddon/never.lua\")
+			end
+			if SERVER then
+				AddCSLuaFile(\"myaddon/cl_hud.lua\")
+			end
+		"),
+		("myaddon/cl_hud.lua", b""),
+		("myaddon/never.lua", b"")
+	];
+
+	let graph = IncludeGraph::build(files.iter().copied());
+	let realms = graph.propagate(&[], &[], &["autorun/myaddon.lua".to_string()]);
+	let realm = |path: &str| realms.get(path).and_then(|realms| realms.realm());
+
+	assert_eq!(realm("myaddon/shared.lua"), Some("sh"));
+	assert_eq!(realm("myaddon/secret.lua"), Some("sv"));
+	assert!(!realms["myaddon/secret.lua"].client);
+	assert_eq!(realm("myaddon/cl_init.lua"), Some("cl"));
+
+	// The rest of cl_init.lua only runs clientside, where AddCSLuaFile does nothing
+	assert_eq!(realm("myaddon/cl_hud.lua"), None);
+	assert!(realms["myaddon/cl_hud.lua"].client);
+	assert_eq!(realm("myaddon/never.lua"), None);
+}
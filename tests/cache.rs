@@ -0,0 +1,84 @@
+use std::path::Path;
+
+use gluapack::{Packer, PackOptions};
+
+mod common;
+use common::{fixture, out_dir, read_tree};
+
+fn copy_tree(from: &Path, to: &Path) {
+	std::fs::create_dir_all(to).unwrap();
+	for entry in from.read_dir().unwrap() {
+		let path = entry.unwrap().path();
+		let to = to.join(path.file_name().unwrap());
+		if path.is_dir() {
+			copy_tree(&path, &to);
+		} else {
+			std::fs::copy(&path, &to).unwrap();
+		}
+	}
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn cached_packs_match_clean_packs() {
+	let (addon, cached, clean) = (out_dir("cache-addon"), out_dir("cache-cached"), out_dir("cache-clean"));
+	copy_tree(&fixture(), &addon);
+
+	let pack = |out_dir: &Path, cache: bool| Packer::pack(addon.clone(), PackOptions::new().out_dir(out_dir).quiet(true).cache(cache));
+
+	// The cache is opt-in
+	Packer::pack(addon.clone(), PackOptions::new().out_dir(&clean).quiet(true)).await.unwrap();
+	assert!(!addon.join(".gluapack-cache").exists());
+
+	pack(&cached, true).await.unwrap();
+	assert!(addon.join(".gluapack-cache").is_dir());
+
+	// No-op
+	pack(&cached, true).await.unwrap();
+	pack(&clean, false).await.unwrap();
+	assert!(read_tree(&cached) == read_tree(&clean), "no-op cached pack differs from clean pack");
+
+	// Clientside change, new asset, removed asset
+	std::fs::write(addon.join("lua/fixture/cl_hud.lua"), "print(\"changed\")").unwrap();
+	std::fs::write(addon.join("materials/fixture/new.txt"), "new").unwrap();
+	std::fs::remove_file(addon.join("materials/fixture/icon.png")).unwrap();
+	pack(&cached, true).await.unwrap();
+	pack(&clean, false).await.unwrap();
+	assert!(read_tree(&cached) == read_tree(&clean), "cached pack differs from clean pack after clientside change");
+
+	// Shared change, which changes the unique ID
+	std::fs::write(addon.join("lua/fixture/sh_init.lua"), "FIXTURE = {}").unwrap();
+	let report = pack(&cached, true).await.unwrap();
+	pack(&clean, false).await.unwrap();
+	let (tree_cached, tree_clean) = (read_tree(&cached), read_tree(&clean));
+
+	std::fs::remove_dir_all(&addon).ok();
+	std::fs::remove_dir_all(&cached).ok();
+	std::fs::remove_dir_all(&clean).ok();
+
+	assert!(tree_cached == tree_clean, "cached pack differs from clean pack after shared change");
+	assert!(tree_cached.iter().any(|(path, _)| path.starts_with(Path::new("lua/gluapack").join(&report.unique_id))));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn replaces_changed_copies() {
+	let (addon, out) = (out_dir("cache-copies-addon"), out_dir("cache-copies"));
+	copy_tree(&fixture(), &addon);
+	std::fs::write(addon.join("materials/fixture/readme.txt"), "original").unwrap();
+
+	let pack = || Packer::pack(addon.clone(), PackOptions::new().out_dir(&out).quiet(true).cache(true));
+	pack().await.unwrap();
+
+	// Same length, so only the modification time gives it away
+	std::fs::write(out.join("materials/fixture/readme.txt"), "tampered").unwrap();
+	std::fs::remove_file(out.join("materials/fixture/icon.png")).unwrap();
+	pack().await.unwrap();
+
+	let (readme, icon) = (std::fs::read(out.join("materials/fixture/readme.txt")), std::fs::read(out.join("materials/fixture/icon.png")));
+	let expected_icon = std::fs::read(addon.join("materials/fixture/icon.png")).unwrap();
+
+	std::fs::remove_dir_all(&addon).ok();
+	std::fs::remove_dir_all(&out).ok();
+
+	assert_eq!(readme.unwrap(), b"original");
+	assert_eq!(icon.unwrap(), expected_icon);
+}
@@ -0,0 +1,283 @@
+use std::path::Path;
+
+use gluapack::{chunking::{self, ChunkFormat}, config::{ChunkEncoding, ChunkingStrategy}, Config, Inspector, Packer, PackOptions, PackingError, Verifier};
+
+mod common;
+use common::{out_dir};
+
+/// An addon with clientside files of varying sizes, one of which is larger than a chunk.
+fn addon(dir: &Path) {
+	let lua = dir.join("lua");
+	std::fs::create_dir_all(lua.join("autorun/client")).unwrap();
+	std::fs::create_dir_all(lua.join("chunking")).unwrap();
+
+	std::fs::write(lua.join("autorun/client/cl_chunking.lua"), "include(\"chunking/cl_big.lua\")\n").unwrap();
+	std::fs::write(lua.join("chunking/cl_big.lua"), (0..100).map(|i| format!("print(\"line {}\")\n", i)).collect::<String>()).unwrap();
+	for i in 0..12 {
+		std::fs::write(lua.join(format!("chunking/cl_{}.lua", i)), (0..i * 2).map(|j| format!("local x{} = {}\n", j, j)).collect::<String>()).unwrap();
+	}
+}
+
+async fn pack(name: &str, chunking: ChunkingStrategy, chunk_encoding: ChunkEncoding, chunk_size: usize) {
+	let (dir, out) = (out_dir(&format!("{}-src", name)), out_dir(name));
+	addon(&dir);
+
+	let config = Config { chunk_size, chunking, chunk_encoding, ..Default::default() };
+	let report = Packer::pack(dir.clone(), PackOptions::new().out_dir(&out).quiet(true).config(config)).await.unwrap();
+
+	let gluapack_dir = out.join(format!("lua/gluapack/{}", report.unique_id));
+	let chunk_sizes = (1..=report.cl.chunks).map(|i| std::fs::metadata(gluapack_dir.join(format!("gluapack.{}.cl.lua", i))).unwrap().len()).collect::<Vec<_>>();
+
+	let verified = Verifier::verify(out.clone()).await.unwrap();
+	let inspected = Inspector::inspect(out.clone()).await.unwrap();
+
+	std::fs::remove_dir_all(&dir).ok();
+	std::fs::remove_dir_all(&out).ok();
+
+	assert!(report.cl.chunks > 1);
+	assert!(chunk_sizes.iter().all(|size| *size as usize <= chunk_size), "{:?}", chunk_sizes);
+	assert!(verified.is_ok(), "{:?}", verified.problems);
+	assert_eq!(verified.files, 14);
+
+	// The chunk map must agree with where the files actually ended up
+	assert_eq!(report.chunk_map.len(), report.cl.chunks);
+	for file in inspected.files {
+		let mut chunks = report.chunk_map.iter().filter(|(_, paths)| paths.contains(&file.path)).map(|(chunk, _)| format!("gluapack/{}/{}", report.unique_id, chunk)).collect::<Vec<_>>();
+		chunks.sort_by_key(|chunk| chunk.split('.').nth(1).unwrap().parse::<usize>().unwrap());
+		assert_eq!(chunks, file.chunks, "{}", file.path);
+	}
+
+	if chunking == ChunkingStrategy::FileAligned {
+		// Only the file that's larger than a chunk is split
+		for (chunk, paths) in &report.chunk_map {
+			for path in paths {
+				let split = report.chunk_map.iter().filter(|(other, paths)| *other != chunk && paths.contains(path)).count() != 0;
+				assert_eq!(split, path == "chunking/cl_big.lua", "{}", path);
+			}
+		}
+	}
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn stream_chunking() {
+	pack("chunking-stream", ChunkingStrategy::Stream, ChunkEncoding::Lines, 512).await;
+	pack("chunking-stream-long-string", ChunkingStrategy::Stream, ChunkEncoding::LongString, 512).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn file_aligned_chunking() {
+	pack("chunking-file-aligned", ChunkingStrategy::FileAligned, ChunkEncoding::Lines, 512).await;
+	pack("chunking-file-aligned-long-string", ChunkingStrategy::FileAligned, ChunkEncoding::LongString, 512).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn chunk_size_is_bounded() {
+	let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/addon");
+	for chunk_size in [0, gluapack::MIN_CHUNK_SIZE - 1, gluapack::MAX_LUA_SIZE + 1] {
+		let config = Config { chunk_size, ..Default::default() };
+		let result = Packer::pack(dir.clone(), PackOptions::new().out_dir(out_dir("chunking-bounded")).quiet(true).config(config)).await;
+		assert!(matches!(result, Err(PackingError::InvalidChunkSize { .. })));
+	}
+}
+
+/// Uncomments and joins chunks the same way the loader does: `("\n" .. chunk):gsub("([\r\n])%-%-%-?", "%1"):sub(2)`
+fn uncomment(chunks: &[Vec<u8>]) -> Vec<u8> {
+	let mut bytes = vec![];
+	for chunk in chunks {
+		assert!(chunk.starts_with(b"--"));
+		assert!(chunking::is_inert(chunk), "{:?}", String::from_utf8_lossy(chunk));
+
+		let start = bytes.len();
+		let chunk = [b"\n" as &[u8], chunk].concat();
+		let mut i = 0;
+		while i < chunk.len() {
+			if i != 0 {
+				bytes.push(chunk[i]);
+			}
+			if matches!(chunk[i], b'\r' | b'\n') && chunk[i + 1..].starts_with(b"--") {
+				i += if chunk.get(i + 3) == Some(&b'-') { 4 } else { 3 };
+			} else {
+				i += 1;
+			}
+		}
+
+		assert_eq!(chunking::uncommentify(&chunk[1..], true), bytes[start..]);
+	}
+	bytes
+}
+
+fn stream(bytes: &[u8]) -> Vec<Vec<u8>> {
+	let chunks = chunking::stream(bytes, &[], gluapack::MIN_CHUNK_SIZE, ChunkEncoding::Lines).unwrap().chunks;
+	assert!(chunks.iter().all(|chunk| chunk.len() <= gluapack::MIN_CHUNK_SIZE), "{:?}", chunks.iter().map(Vec::len).collect::<Vec<_>>());
+	assert_eq!(uncomment(&chunks), bytes);
+	chunks
+}
+
+#[test]
+fn stream_split_after_newline() {
+	// 2 + 251 + 3 (the newline and the next line's --) fills the chunk exactly
+	let mut bytes = vec![b'a'; 251];
+	bytes.extend_from_slice(b"\nbbb");
+
+	let chunks = stream(&bytes);
+	assert_eq!(chunks.len(), 2);
+	assert_eq!(chunks[0].len(), gluapack::MIN_CHUNK_SIZE);
+	assert!(chunks[0].ends_with(b"a\n--"));
+	assert_eq!(chunks[1], b"--bbb");
+}
+
+#[test]
+fn stream_split_before_newline() {
+	// The newline and its -- don't fit after 2 + 254 bytes
+	let mut bytes = vec![b'a'; 254];
+	bytes.extend_from_slice(b"\nbbb");
+
+	let chunks = stream(&bytes);
+	assert_eq!(chunks.len(), 2);
+	assert_eq!(chunks[0].len(), gluapack::MIN_CHUNK_SIZE);
+	assert_eq!(chunks[1], b"--\n--bbb");
+}
+
+#[test]
+fn stream_split_mid_comment() {
+	// Slicing the commented pack at 256 bytes would have split the second line's --
+	let mut bytes = vec![b'a'; 252];
+	bytes.extend_from_slice(b"\nbbb");
+	let chunks = stream(&bytes);
+	assert_eq!(chunks.len(), 2);
+	assert_eq!(chunks[1], b"--\n--bbb");
+
+	// A Lua comment in a packed file split across chunks
+	let mut bytes = vec![b'a'; 253];
+	bytes.extend_from_slice(b"--comment\n");
+	let chunks = stream(&bytes);
+	assert_eq!(chunks.len(), 2);
+	assert!(chunks[0].ends_with(b"a-"));
+	assert_eq!(chunks[1], b"----comment\n--");
+}
+
+#[test]
+fn stream_split_every_offset() {
+	let line = b"print(\"--\")\n";
+	let bytes = line.iter().copied().cycle().take(line.len() * 100).collect::<Vec<_>>();
+	for len in 0..=bytes.len() {
+		stream(&bytes[..len]);
+	}
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn chunks_are_within_the_engine_limit() {
+	let (dir, out) = (out_dir("chunking-limit-src"), out_dir("chunking-limit"));
+
+	// Large enough to need several chunks at the default chunk size
+	let lua = dir.join("lua");
+	std::fs::create_dir_all(lua.join("autorun/client")).unwrap();
+	std::fs::write(lua.join("autorun/client/cl_big.lua"), (0..20000).map(|i| format!("-- {}\nprint(\"--\")\n", i)).collect::<String>()).unwrap();
+
+	let report = Packer::pack(dir.clone(), PackOptions::new().out_dir(&out).quiet(true)).await.unwrap();
+
+	let gluapack_dir = out.join(format!("lua/gluapack/{}", report.unique_id));
+	let chunk_sizes = (1..=report.cl.chunks).map(|i| std::fs::metadata(gluapack_dir.join(format!("gluapack.{}.cl.lua", i))).unwrap().len()).collect::<Vec<_>>();
+	let verified = Verifier::verify(out.clone()).await.unwrap();
+
+	std::fs::remove_dir_all(&dir).ok();
+	std::fs::remove_dir_all(&out).ok();
+
+	assert!(report.cl.chunks > 1);
+	assert!(chunk_sizes.iter().all(|size| *size as usize <= gluapack::MAX_LUA_SIZE), "{:?}", chunk_sizes);
+	assert!(verified.is_ok(), "{:?}", verified.problems);
+}
+
+#[test]
+fn chunk_overflow_is_an_error() {
+	// Two bytes only leave room for the --
+	assert!(matches!(chunking::stream(b"print(1)\n", &[], 2, ChunkEncoding::Lines), Err(PackingError::ChunkOverflow { .. })));
+	assert!(matches!(chunking::file_aligned(b"print(1)\n", &[], 6, ChunkEncoding::LongString), Err(PackingError::ChunkOverflow { .. })));
+}
+
+#[test]
+fn chunks_are_inert() {
+	let srcs: &[(&[u8], bool)] = &[
+		(b"[[\n]] print(\"long comment\")", true),
+		(b"[==[\n]==] print(\"long comment\")\n", true),
+		(b"a\rprint(\"bare carriage return\")\r", true),
+		(b"a\r\nprint(1)\r\n[[\r]] print(2)\n\r-", true),
+		(b"-[[\n]] print(\"not a long comment\")", false),
+		(b"--[[\n]]print(1)", false),
+		(b"\n\n[\n-\n\r\r", false),
+	];
+	for (src, old_runs_code) in srcs {
+		// Whether the old encoding would have run code
+		let mut old = b"--".to_vec();
+		for byte in src.iter() {
+			old.push(*byte);
+			if *byte == b'\n' {
+				old.extend_from_slice(b"--");
+			}
+		}
+		assert_eq!(chunking::is_inert(&old), !old_runs_code, "{:?}", String::from_utf8_lossy(src));
+
+		let chunk = chunking::commentify(src);
+		assert!(chunking::is_inert(&chunk), "{:?}", String::from_utf8_lossy(&chunk));
+		assert_eq!(chunking::uncommentify(&chunk, true), *src);
+		assert_eq!(uncomment(&[chunk]), *src);
+	}
+
+	for size in gluapack::MIN_CHUNK_SIZE..gluapack::MIN_CHUNK_SIZE + 8 {
+		let src = b"[[\r\n]] x\r[=[\r]=]--\n-- y\r\r\n".iter().copied().cycle().take(2000).collect::<Vec<_>>();
+		let chunks = chunking::stream(&src, &[], size, ChunkEncoding::Lines).unwrap().chunks;
+		assert!(chunks.iter().all(|chunk| chunk.len() <= size));
+		assert_eq!(uncomment(&chunks), src);
+	}
+}
+
+#[test]
+fn long_string_chunks() {
+	let srcs: &[(&[u8], usize)] = &[
+		(b"print(1)\n", 0),
+		(b"]] print(1)", 1),
+		(b"a]", 1),
+		(b"]]]=]a]==", 3),
+		(b"]=] ]]\r\n[==[", 2),
+	];
+	for (src, level) in srcs {
+		let chunk = chunking::long_comment(src);
+		assert!(chunk.starts_with(format!("--[{}[", "=".repeat(*level)).as_bytes()), "{:?}", String::from_utf8_lossy(&chunk));
+		assert!(chunking::is_inert(&chunk), "{:?}", String::from_utf8_lossy(&chunk));
+		assert_eq!(ChunkFormat::LongString.decode(&chunk), *src);
+	}
+
+	// Payloads that need a higher level than expected still fit
+	let src = b"]]]=]]==]x".iter().copied().cycle().take(5000).collect::<Vec<_>>();
+	for size in gluapack::MIN_CHUNK_SIZE..gluapack::MIN_CHUNK_SIZE + 8 {
+		let chunks = chunking::stream(&src, &[], size, ChunkEncoding::LongString).unwrap().chunks;
+		assert!(chunks.iter().all(|chunk| chunk.len() <= size && chunking::is_inert(chunk)));
+		assert_eq!(chunks.iter().flat_map(|chunk| ChunkFormat::LongString.decode(chunk)).collect::<Vec<_>>(), src);
+	}
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn chunk_encoding_report() {
+	let (dir, lines, long_string) = (out_dir("chunking-encoding-src"), out_dir("chunking-encoding-lines"), out_dir("chunking-encoding-long-string"));
+	addon(&dir);
+
+	let options = |out: &Path, chunk_encoding| PackOptions::new().out_dir(out).quiet(true).config(Config { chunk_encoding, ..Default::default() });
+	let lines_report = Packer::pack(dir.clone(), options(&lines, ChunkEncoding::Lines)).await.unwrap();
+	let long_string_report = Packer::pack(dir.clone(), options(&long_string, ChunkEncoding::LongString)).await.unwrap();
+
+	let size = |out: &Path, report: &gluapack::PackReport| std::fs::metadata(out.join(format!("lua/gluapack/{}/gluapack.1.cl.lua", report.unique_id))).unwrap().len() as usize;
+	let (lines_size, long_string_size) = (size(&lines, &lines_report), size(&long_string, &long_string_report));
+	let verified = Verifier::verify(long_string.clone()).await.unwrap();
+
+	std::fs::remove_dir_all(&dir).ok();
+	std::fs::remove_dir_all(&lines).ok();
+	std::fs::remove_dir_all(&long_string).ok();
+
+	// The configured encoding's size is exact, the other one is estimated
+	assert_eq!(lines_report.chunk_encoding.lines, lines_size);
+	assert_eq!(long_string_report.chunk_encoding.long_string, long_string_size);
+	assert!((long_string_size..=long_string_size + 8).contains(&lines_report.chunk_encoding.long_string), "{:?}", lines_report.chunk_encoding);
+	assert!((lines_size..=lines_size + lines_size / 20).contains(&long_string_report.chunk_encoding.lines), "{:?}", long_string_report.chunk_encoding);
+	assert!(long_string_size < lines_size);
+	assert!(verified.is_ok(), "{:?}", verified.problems);
+}
@@ -0,0 +1,42 @@
+// Helpers shared by the integration tests
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+
+/// The example addon in `tests/fixtures/addon`.
+pub fn fixture() -> PathBuf {
+	Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/addon")
+}
+
+/// A temporary directory unique to this test run.
+pub fn out_dir(name: &str) -> PathBuf {
+	std::env::temp_dir().join(format!("gluapack-test-{}-{}", name, std::process::id()))
+}
+
+/// Writes files, creating their parent directories.
+pub fn write(dir: &Path, files: &[(&str, &str)]) {
+	for (path, contents) in files {
+		let path = dir.join(path);
+		std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+		std::fs::write(path, contents).unwrap();
+	}
+}
+
+/// Reads every file under `root`, ordered by path relative to `root`.
+pub fn read_tree(root: &Path) -> Vec<(PathBuf, Vec<u8>)> {
+	fn visit(root: &Path, dir: &Path, files: &mut Vec<(PathBuf, Vec<u8>)>) {
+		for entry in dir.read_dir().unwrap() {
+			let path = entry.unwrap().path();
+			if path.is_dir() {
+				visit(root, &path, files);
+			} else {
+				files.push((path.strip_prefix(root).unwrap().to_path_buf(), std::fs::read(&path).unwrap()));
+			}
+		}
+	}
+
+	let mut files = vec![];
+	visit(root, root, &mut files);
+	files.sort();
+	files
+}
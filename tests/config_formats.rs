@@ -0,0 +1,132 @@
+use gluapack::{config::{ChunkingStrategy, RealmConflicts}, Config, PackingError};
+
+mod common;
+use common::{out_dir};
+
+/// Writes a config file and reads it back.
+fn read(name: &str, file_name: &str, contents: &str) -> Result<Config, PackingError> {
+	let dir = out_dir(name);
+	std::fs::create_dir_all(&dir).unwrap();
+	std::fs::write(dir.join(file_name), contents).unwrap();
+	let config = Config::read(Config::find(&dir).unwrap());
+	std::fs::remove_dir_all(&dir).ok();
+	config
+}
+
+/// Returns the line, column and message of a config error.
+fn error(result: Result<Config, PackingError>) -> (usize, usize, String) {
+	match result {
+		Err(PackingError::ConfigError { error, .. }) => (error.line, error.column, error.message),
+		result => panic!("expected a config error, got {:?}", result.map(|_| ()))
+	}
+}
+
+fn assert_example(config: Config) {
+	assert_eq!(config.unique_id.as_deref(), Some("my_addon"));
+	assert_eq!(config.include_cl.iter().map(|pattern| pattern.as_str()).collect::<Vec<_>>(), vec!["**/cl_*.lua", "vgui/*.lua"]);
+	assert!(config.exclude.is_empty());
+	assert!(config.minify.cl && !config.minify.sv);
+	assert_eq!(config.chunk_size, 32768);
+	assert_eq!(config.chunking, ChunkingStrategy::FileAligned);
+	assert_eq!(config.realm_conflicts, RealmConflicts::PromoteShared);
+	assert!(!config.dedup);
+}
+
+#[test]
+fn readme_example() {
+	// The configuration example in the README has comments and trailing commas
+	let readme = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/README.md")).unwrap();
+	let example = readme.split("# Configuration\n").nth(1).unwrap().split("```js\n").nth(1).unwrap().split("```").next().unwrap();
+	let config = read("config-readme", "gluapack.json", example).unwrap();
+	assert_eq!(config.include_sh.len(), Config::default().include_sh.len());
+	assert_eq!(config.chunk_size, Config::default().chunk_size);
+}
+
+#[test]
+fn json5() {
+	assert_example(read("config-json5", "gluapack.json5", r#"
+		// Unquoted keys, single quotes and hex numbers
+		{
+			unique_id: 'my_addon',
+			include_cl: [
+				"**/cl_*.lua",
+				'vgui/*.lua', /* trailing comma */
+			],
+			exclude: [],
+			minify: { cl: true, },
+			chunk_size: 0x8000,
+			chunking: "file_aligned",
+			realm_conflicts: 'promote_shared',
+			dedup: false,
+		}
+	"#).unwrap());
+}
+
+#[test]
+fn toml() {
+	assert_example(read("config-toml", "gluapack.toml", r#"
+		# gluapack.toml
+		unique_id = "my_addon"
+		include_cl = [
+			"**/cl_*.lua",
+			'vgui/*.lua', # trailing comma
+		]
+		exclude = []
+		chunk_size = 32_768
+		chunking = "file_aligned"
+		realm_conflicts = "promote_shared"
+		dedup = false
+
+		[minify]
+		cl = true
+	"#).unwrap());
+}
+
+#[test]
+fn yaml() {
+	assert_example(read("config-yaml", "gluapack.yaml", r#"
+# gluapack.yaml
+unique_id: my_addon
+include_cl:
+- "**/cl_*.lua"
+- 'vgui/*.lua' # a comment
+exclude: []
+minify:
+  cl: true
+  sv: false
+chunk_size: 32768
+chunking: file_aligned
+realm_conflicts: promote_shared
+dedup: false
+"#).unwrap());
+
+	assert_example(read("config-yml", "gluapack.yml", "unique_id: my_addon\ninclude_cl: [\"**/cl_*.lua\",\n  vgui/*.lua]\nexclude: []\nminify: { cl: true }\nchunk_size: 32768\nchunking: file_aligned\nrealm_conflicts: promote_shared\ndedup: false\n").unwrap());
+}
+
+#[test]
+fn yaml_follows_the_spec() {
+	// A plain scalar can't contain ": "
+	assert_eq!(error(read("config-yaml-mapping", "gluapack.yaml", "unique_id: x: y\n")), (1, 13, "mapping values are not allowed in this context".to_string()));
+
+	// Block scalars
+	let config = read("config-yaml-block", "gluapack.yaml", "unique_id: >-\n  my\n  addon\nexclude:\n- |-\n  dev/*.lua\n").unwrap();
+	assert_eq!(config.unique_id.as_deref(), Some("my addon"));
+	assert_eq!(config.exclude.iter().map(|pattern| pattern.as_str()).collect::<Vec<_>>(), vec!["dev/*.lua"]);
+}
+
+#[test]
+fn errors_point_to_the_file() {
+	// Syntax errors
+	assert_eq!(error(read("config-error-json", "gluapack.json", "{\n\t// comment\n\t\"chunk_size\": 100,,\n}")), (3, 20, "expected identifier".to_string()));
+	assert_eq!(error(read("config-error-toml", "gluapack.toml", "unique_id = \"a\"\nchunk_size = \n")).0, 2);
+	assert_eq!(error(read("config-error-yaml", "gluapack.yaml", "minify:\n  cl: true\n sv: true\n")), (3, 2, "did not find expected key".to_string()));
+
+	// Invalid values
+	assert_eq!(error(read("config-error-json-value", "gluapack.json", "{\n  unique_id: 'a', chunking: 'fast'\n}")), (2, 29, "unknown variant `fast`, expected `stream` or `file_aligned`".to_string()));
+	let (line, column, message) = error(read("config-error-toml-value", "gluapack.toml", "unique_id = \"a\"\n\n[minify]\ncl = \"yes\"\n"));
+	assert_eq!((line, column), (4, 6));
+	assert!(message.starts_with("invalid type: string \"yes\", expected a boolean"), "{}", message);
+
+	let error = read("config-error-display", "gluapack.yml", "chunking: fast\n").unwrap_err().to_string();
+	assert!(error.starts_with("Config error in ") && error.contains("gluapack.yml:1:11: chunking: unknown variant `fast`"), "{}", error);
+}
@@ -0,0 +1,71 @@
+use gluapack::{Config, Inspector, Packer, PackOptions, Unpacker, UnpackOptions, Verifier};
+
+mod common;
+use common::{out_dir, write};
+
+const SHARED: &str = "print(\"the same file, in three realms\")\n";
+const SERVERSIDE: &str = "print(\"the same file, clientside and serverside\")\n";
+
+/// The Lua files of an addon with copies of the same file across realms.
+const FILES: &[(&str, &str)] = &[
+	("autorun/sh_dedup.lua", "include(\"dedup/sh_a.lua\")\n"),
+	("dedup/sh_a.lua", SHARED),
+	("dedup/cl_a.lua", SHARED),
+	("dedup/sv_a.lua", SHARED),
+	("dedup/cl_b.lua", SERVERSIDE),
+	("dedup/sv_b.lua", SERVERSIDE),
+	("dedup/sv_c.lua", SERVERSIDE),
+	// Too small to be worth a reference
+	("dedup/cl_x.lua", "f()"),
+	("dedup/sv_x.lua", "f()")
+];
+
+#[tokio::test(flavor = "multi_thread")]
+async fn dedup() {
+	let (dir, packed, unpacked) = (out_dir("dedup-src"), out_dir("dedup"), out_dir("dedup-unpacked"));
+	write(&dir.join("lua"), FILES);
+
+	let report = Packer::pack(dir.clone(), PackOptions::new().out_dir(&packed).quiet(true)).await.unwrap();
+	let undeduplicated = Packer::pack(dir.clone(), PackOptions::new().out_dir(out_dir("dedup-off")).quiet(true).config(Config { dedup: false, ..Default::default() })).await.unwrap();
+
+	let verified = Verifier::verify(packed.clone()).await.unwrap();
+	let inspected = Inspector::inspect(packed.clone()).await.unwrap();
+	Unpacker::unpack(packed.clone(), UnpackOptions::new().out_dir(&unpacked).quiet(true)).await.unwrap();
+
+	let unpacked_files = FILES.iter().map(|(path, _)| std::fs::read_to_string(unpacked.join("lua").join(path)).unwrap()).collect::<Vec<_>>();
+
+	for dir in [&dir, &packed, &unpacked, &out_dir("dedup-off")] {
+		std::fs::remove_dir_all(dir).ok();
+	}
+
+	assert_eq!(report.deduplicated.files, 4);
+	assert_eq!(undeduplicated.deduplicated.files, 0);
+	assert!(report.deduplicated.bytes > 0);
+	assert!(report.chunk_encoding.lines < undeduplicated.chunk_encoding.lines);
+
+	assert!(verified.is_ok(), "{:?}", verified.problems);
+	assert_eq!(verified.files, FILES.len());
+
+	for ((path, contents), unpacked) in FILES.iter().zip(unpacked_files) {
+		assert_eq!(*contents, unpacked, "{}", path);
+	}
+
+	let duplicate_of = |path: &str| inspected.files.iter().find(|file| file.path == path).unwrap().duplicate_of.clone();
+	assert_eq!(duplicate_of("dedup/cl_a.lua").as_deref(), Some("dedup/sh_a.lua"));
+	assert_eq!(duplicate_of("dedup/sv_a.lua").as_deref(), Some("dedup/sh_a.lua"));
+	assert_eq!(duplicate_of("dedup/sv_b.lua").as_deref(), Some("dedup/cl_b.lua"));
+	assert_eq!(duplicate_of("dedup/sv_c.lua").as_deref(), Some("dedup/cl_b.lua"));
+	assert_eq!(duplicate_of("dedup/cl_x.lua"), None);
+	assert_eq!(duplicate_of("dedup/sv_x.lua"), None);
+
+	// Networked files never refer to serverside files
+	for file in inspected.files.iter().filter(|file| file.realm != "sv") {
+		if let Some(duplicate_of) = &file.duplicate_of {
+			assert_ne!(inspected.files.iter().find(|other| &other.path == duplicate_of).unwrap().realm, "sv");
+		}
+	}
+
+	for file in inspected.files.iter() {
+		assert_eq!(file.size, FILES.iter().find(|(path, _)| *path == file.path).unwrap().1.len(), "{}", file.path);
+	}
+}
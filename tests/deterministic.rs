@@ -0,0 +1,24 @@
+use gluapack::{Packer, PackOptions};
+
+mod common;
+use common::{fixture, out_dir, read_tree};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn packing_is_deterministic() {
+	let (a, b) = (out_dir("deterministic-a"), out_dir("deterministic-b"));
+
+	let report_a = Packer::pack(fixture(), PackOptions::new().out_dir(&a).quiet(true)).await.unwrap();
+	let report_b = Packer::pack(fixture(), PackOptions::new().out_dir(&b).quiet(true)).await.unwrap();
+
+	let (tree_a, tree_b) = (read_tree(&a), read_tree(&b));
+
+	std::fs::remove_dir_all(&a).ok();
+	std::fs::remove_dir_all(&b).ok();
+
+	assert_eq!(report_a.unique_id, report_b.unique_id);
+	assert!(!tree_a.is_empty());
+	assert_eq!(tree_a.iter().map(|(path, _)| path).collect::<Vec<_>>(), tree_b.iter().map(|(path, _)| path).collect::<Vec<_>>());
+	for ((path, a), (_, b)) in tree_a.iter().zip(tree_b.iter()) {
+		assert!(a == b, "{} differs between runs", path.display());
+	}
+}
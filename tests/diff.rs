@@ -0,0 +1,81 @@
+use gluapack::{config::GlobPattern, diff::{self, FileDiff}, Config, Differ, DiffOptions, Packer, PackOptions};
+
+mod common;
+use common::{out_dir, write};
+
+fn config(include_sh: &'static str) -> Config {
+	Config {
+		include_sh: vec![GlobPattern::new("diff/sh_*.lua"), GlobPattern::new(include_sh)],
+		include_cl: vec![GlobPattern::new("diff/cl_*.lua"), GlobPattern::new("diff/moved.lua")],
+		include_sv: vec![GlobPattern::new("diff/sv_*.lua")],
+		..Default::default()
+	}
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn diff_packed_addons() {
+	let (old_src, new_src, old, new) = (out_dir("diff-old-src"), out_dir("diff-new-src"), out_dir("diff-old"), out_dir("diff-new"));
+
+	write(&old_src.join("lua"), &[
+		("autorun/server/sv_init.lua", "include(\"diff/sv_c.lua\")\n"),
+		("diff/sh_a.lua", "local a = 1\nlocal b = 2\nlocal c = 3\n"),
+		("diff/cl_b.lua", "print(\"b\")\n"),
+		("diff/sv_c.lua", "print(\"c\")\n"),
+		("diff/moved.lua", "print(\"moved\")\n")
+	]);
+	write(&new_src.join("lua"), &[
+		("autorun/server/sv_init.lua", "include(\"diff/sv_c.lua\")\n"),
+		("autorun/server/sv_init2.lua", "include(\"diff/sv_d.lua\")\n"),
+		("diff/sh_a.lua", "local a = 1\nlocal b = 20\nlocal c = 3\n"),
+		("diff/sv_c.lua", "print(\"c\")\n"),
+		("diff/sv_d.lua", "print(\"d\")\n"),
+		("diff/moved.lua", "print(\"moved\")\n")
+	]);
+
+	// moved.lua is shared in the new addon
+	Packer::pack(old_src.clone(), PackOptions::new().out_dir(&old).quiet(true).config(config("diff/nothing.lua"))).await.unwrap();
+	let mut new_config = config("diff/moved.lua");
+	new_config.include_cl.pop();
+	Packer::pack(new_src.clone(), PackOptions::new().out_dir(&new).quiet(true).config(new_config)).await.unwrap();
+
+	let report = Differ::diff(old.clone(), new.clone(), DiffOptions::new().patch(true)).await;
+	let unchanged = Differ::diff(old.clone(), old.clone(), DiffOptions::new()).await;
+
+	for dir in [&old_src, &new_src, &old, &new] {
+		std::fs::remove_dir_all(dir).ok();
+	}
+
+	let (report, unchanged) = (report.unwrap(), unchanged.unwrap());
+	assert!(unchanged.is_empty());
+
+	let file = |path: &str| report.files.iter().find(|file| file.path == path).cloned();
+	assert_eq!(report.files.len(), 5, "{:#?}", report.files);
+	assert!(file("diff/cl_b.lua").unwrap().is_removed());
+	assert!(file("diff/sv_d.lua").unwrap().is_added());
+	assert!(file("autorun/server/sv_init2.lua").unwrap().is_added());
+	assert_eq!(file("diff/moved.lua"), Some(FileDiff { path: "diff/moved.lua".to_string(), old_realm: Some("cl"), new_realm: Some("sh"), modified: false, patch: None }));
+	assert_eq!(file("diff/sv_c.lua"), None);
+
+	let modified = file("diff/sh_a.lua").unwrap();
+	assert!(modified.modified && !modified.is_moved());
+	assert_eq!(modified.patch.as_deref(), Some("--- a/diff/sh_a.lua\n+++ b/diff/sh_a.lua\n@@ -1,3 +1,3 @@\n local a = 1\n-local b = 2\n+local b = 20\n local c = 3\n"));
+
+	assert_eq!(report.entry_files.len(), 1);
+	assert_eq!((report.entry_files[0].realm, report.entry_files[0].path.as_str(), report.entry_files[0].added), ("sv", "autorun/server/sv_init2.lua", true));
+}
+
+#[test]
+fn unified_diff() {
+	let old = (1..=20).map(|i| format!("line {}\n", i)).collect::<String>();
+
+	// Changes far apart get their own hunks
+	let new = old.replace("line 2\n", "line two\n").replace("line 18\n", "");
+	assert_eq!(diff::unified_diff("a.lua", old.as_bytes(), new.as_bytes(), 1), "--- a/a.lua\n+++ b/a.lua\n@@ -1,3 +1,3 @@\n line 1\n-line 2\n+line two\n line 3\n@@ -17,3 +17,2 @@\n line 17\n-line 18\n line 19\n");
+
+	// Changes close together share a hunk
+	let new = old.replace("line 5\n", "").replace("line 7\n", "line seven\n");
+	assert_eq!(diff::unified_diff("a.lua", old.as_bytes(), new.as_bytes(), 1), "--- a/a.lua\n+++ b/a.lua\n@@ -4,5 +4,4 @@\n line 4\n-line 5\n line 6\n-line 7\n+line seven\n line 8\n");
+
+	assert_eq!(diff::unified_diff("a.lua", b"", b"a", 3), "--- a/a.lua\n+++ b/a.lua\n@@ -0,0 +1,1 @@\n+a\n\\ No newline at end of file\n");
+	assert_eq!(diff::unified_diff("a.lua", b"a\nb\n", b"a\nb\n", 3), "--- a/a.lua\n+++ b/a.lua\n");
+}
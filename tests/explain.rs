@@ -0,0 +1,153 @@
+use gluapack::{config::{ConfigOverrides, RealmConflicts}, explain::Decision, Config, Explainer, ExplainOptions, ExplainReport, Packer, PackOptions};
+
+mod common;
+use common::{fixture, out_dir, write};
+
+async fn explain(name: &str, files: &[(&str, &str)], options: ExplainOptions) -> ExplainReport {
+	let dir = out_dir(name);
+	write(&dir, files);
+	let report = Explainer::explain(dir.clone(), options).await;
+	std::fs::remove_dir_all(&dir).ok();
+	report.unwrap()
+}
+
+fn matched<'a>(report: &'a ExplainReport, path: &str) -> Vec<(&'a str, &'a str)> {
+	report.file(path).unwrap().matched.iter().map(|matched| (matched.option, matched.pattern.as_str())).collect()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn agrees_with_packer() {
+	for (name, config) in [
+		("explain-default", Config::default()),
+		("explain-pruned", Config { prune_unreachable: true, ..Default::default() }),
+		("explain-auto", Config { auto_realms: true, ..Default::default() })
+	] {
+		let out = out_dir(name);
+		let packed = Packer::pack(fixture(), PackOptions::new().out_dir(&out).quiet(true).config(config.clone())).await;
+		let explained = Explainer::explain(fixture(), ExplainOptions::new().config(config.clone())).await;
+		std::fs::remove_dir_all(&out).ok();
+
+		let (packed, explained) = (packed.unwrap(), explained.unwrap());
+		let files_in = |realm: &str| explained.files.iter().filter(|file| file.realm() == Some(realm)).count();
+		assert_eq!((files_in("sv"), files_in("cl"), files_in("sh")), (packed.sv.files, packed.cl.files, packed.sh.files), "{}", name);
+		if config.prune_unreachable {
+			assert_eq!(explained.count(Decision::Pruned), packed.unreachable.len(), "{}", name);
+		}
+	}
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn explains_patterns_and_decisions() {
+	let report = explain("explain-decisions", &[
+		("gluapack.json", r#"{ "exclude": ["my_addon/dev/*"], "entry_sh": ["autorun/*.lua", "my_addon/sh_*.lua"] }"#),
+		("lua/autorun/server/sv_boot.lua", "print(\"boot\")\n"),
+		("lua/my_addon/sh_init.lua", "print(\"init\")\n"),
+		("lua/my_addon/dev/cl_debug.lua", "print(\"debug\")\n"),
+		("lua/my_addon/sv_hud.cl.lua", "print(\"hud\")\n"),
+		("lua/my_addon/other.lua", "print(\"other\")\n"),
+		("lua/gluapack/old/gluapack.1.cl.lua", "-- packed\n")
+	], ExplainOptions::new()).await;
+
+	let boot = report.file("autorun/server/sv_boot.lua").unwrap();
+	assert_eq!(matched(&report, "autorun/server/sv_boot.lua"), vec![("include_sv", "**/sv_*.lua"), ("entry_sv", "autorun/server/*.lua")]);
+	assert_eq!((boot.decision, boot.realm(), boot.describe().as_str()), (Decision::Packed, Some("sv"), "packed sv, entry file"));
+
+	assert_eq!(report.file("my_addon/sh_init.lua").unwrap().describe(), "packed sh, entry file");
+	assert_eq!(matched(&report, "my_addon/sh_init.lua"), vec![("include_sh", "**/sh_*.lua"), ("entry_sh", "my_addon/sh_*.lua")]);
+
+	assert_eq!(report.file("my_addon/dev/cl_debug.lua").unwrap().decision, Decision::Excluded);
+	assert_eq!(matched(&report, "my_addon/dev/cl_debug.lua"), vec![("include_cl", "**/cl_*.lua"), ("exclude", "my_addon/dev/*")]);
+
+	let conflict = report.file("my_addon/sv_hud.cl.lua").unwrap();
+	assert_eq!((conflict.decision, conflict.realms.clone()), (Decision::Conflict, vec!["sv", "cl"]));
+
+	assert_eq!(report.file("my_addon/other.lua").unwrap().decision, Decision::Untouched);
+	assert!(report.file("my_addon/other.lua").unwrap().matched.is_empty());
+
+	// Packed files of previous builds are always excluded
+	assert_eq!(report.file("gluapack/old/gluapack.1.cl.lua").unwrap().decision, Decision::Excluded);
+	assert_eq!(matched(&report, "gluapack/old/gluapack.1.cl.lua"), vec![("include_cl", "**/*.cl.lua"), ("exclude", "gluapack/*/*")]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn explains_realm_conflicts_and_overrides() {
+	let files = [
+		("lua/autorun/client/cl_menu.lua", "print(\"menu\")\n"),
+		("lua/my_addon/sh_menu.cl.lua", "print(\"menu\")\n"),
+		("lua/my_addon/cl_unused.lua", "print(\"unused\")\n")
+	];
+
+	let config = Config { realm_conflicts: RealmConflicts::PromoteShared, ..Default::default() };
+	let report = explain("explain-resolved", &files, ExplainOptions::new().config(config)).await;
+	let resolved = report.file("my_addon/sh_menu.cl.lua").unwrap();
+	assert_eq!((resolved.decision, resolved.realm()), (Decision::Packed, Some("sh")));
+	assert_eq!(resolved.notes, vec!["it matches cl and sh, realm_conflicts packs it as sh".to_string(), "it can't be reached from any entry file (see prune_unreachable)".to_string()]);
+
+	let mut overrides = ConfigOverrides::new();
+	overrides.set("--realm-conflicts", "realm_conflicts", &["prefer_cl"]).unwrap();
+	overrides.set("--prune-unreachable", "prune_unreachable", &["true"]).unwrap();
+	let report = explain("explain-overrides", &files, ExplainOptions::new().overrides(overrides)).await;
+	let pruned = report.file("my_addon/sh_menu.cl.lua").unwrap();
+	assert_eq!((pruned.decision, pruned.realms.clone()), (Decision::Pruned, vec!["cl"]));
+	assert_eq!(pruned.describe(), "not packed, pruned from cl by prune_unreachable");
+	assert_eq!(report.count(Decision::Pruned), 2);
+	assert_eq!(report.file("autorun/client/cl_menu.lua").unwrap().describe(), "packed cl, entry file");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn explains_auto_realms() {
+	let config = Config { auto_realms: true, ..Default::default() };
+	let report = explain("explain-auto-realms", &[
+		("lua/autorun/my_addon.lua", "if SERVER then include(\"my_addon/database.lua\") end\n"),
+		("lua/my_addon/database.lua", "print(\"db\")\n"),
+		("lua/my_addon/cl_hud.lua", "print(\"hud\")\n"),
+		("lua/my_addon/unknown.lua", "print(\"unknown\")\n")
+	], ExplainOptions::new().config(config)).await;
+
+	let database = report.file("my_addon/database.lua").unwrap();
+	assert_eq!(database.realm(), Some("sv"));
+	assert!(database.notes[0].starts_with("auto_realms classified it as sv"), "{:?}", database.notes);
+
+	let hud = report.file("my_addon/cl_hud.lua").unwrap();
+	assert_eq!(hud.realm(), Some("cl"));
+	assert_eq!(hud.notes[0], "auto_realms couldn't classify it, falling back to include_cl");
+
+	let unknown = report.file("my_addon/unknown.lua").unwrap();
+	assert_eq!(unknown.decision, Decision::Untouched);
+	assert_eq!(unknown.notes, vec!["auto_realms couldn't classify it, and it matches no include pattern".to_string()]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn explains_workspaces() {
+	let (dir, out) = (out_dir("explain-workspace"), out_dir("explain-workspace-out"));
+	write(&dir, &[
+		("gluapack-workspace.json", r#"{ "addons": ["library", "gamemode"], "prune_unreachable": true }"#),
+		("library/gluapack.json", r#"{ "include_sh": ["library/*.lua"], "entry_sh": ["autorun/library.lua"] }"#),
+		("library/lua/autorun/library.lua", "include(\"library/sh_core.lua\")\n"),
+		("library/lua/library/sh_core.lua", "Library = {}\n"),
+		("library/lua/library/sh_unused.lua", "print(\"unused\")\n"),
+		("library/lua/other/unpacked.lua", "print(\"not packed\")\n"),
+		("gamemode/gluapack.json", r#"{ "auto_realms": true, "entry_sh": ["autorun/gamemode.lua"] }"#),
+		// Reaches into the library addon
+		("gamemode/lua/autorun/gamemode.lua", "AddCSLuaFile(\"library/sh_unused.lua\")\ninclude(\"gamemode/sv_init.lua\")\n"),
+		("gamemode/lua/gamemode/sv_init.lua", "print(\"init\")\n")
+	]);
+
+	let packed = Packer::pack_workspace(dir.clone(), PackOptions::new().out_dir(&out).quiet(true)).await;
+	let explained = Explainer::explain_workspace(dir.clone(), ExplainOptions::new()).await;
+	std::fs::remove_dir_all(&dir).ok();
+	std::fs::remove_dir_all(&out).ok();
+
+	let (packed, explained) = (packed.unwrap(), explained.unwrap());
+	let files_in = |realm: &str| explained.files.iter().filter(|file| file.realm() == Some(realm)).count();
+	assert_eq!((files_in("sv"), files_in("cl"), files_in("sh")), (packed.sv.files, packed.cl.files, packed.sh.files));
+	assert_eq!(explained.count(Decision::Pruned), packed.unreachable.len());
+
+	let unpacked = explained.file("other/unpacked.lua").unwrap();
+	assert_eq!((unpacked.addon.as_deref(), unpacked.decision), (Some("library"), Decision::Untouched));
+	let init = explained.file("gamemode/sv_init.lua").unwrap();
+	assert_eq!((init.addon.as_deref(), init.realm()), (Some("gamemode"), Some("sv")));
+
+	// Reachability is followed across the workspace's addons
+	assert_eq!(explained.file("library/sh_unused.lua").unwrap().realm(), Some("sh"));
+}
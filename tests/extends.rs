@@ -0,0 +1,80 @@
+use gluapack::{Config, PackingError};
+
+mod common;
+use common::{out_dir, write};
+
+/// Writes the files and reads the config at `path`.
+fn read(name: &str, path: &str, files: &[(&str, &str)]) -> Result<Config, PackingError> {
+	let dir = out_dir(name);
+	write(&dir, files);
+	let config = Config::read(dir.join(path));
+	std::fs::remove_dir_all(&dir).ok();
+	config
+}
+
+fn patterns(patterns: &[gluapack::config::GlobPattern]) -> Vec<&str> {
+	patterns.iter().map(|pattern| pattern.as_str()).collect()
+}
+
+#[test]
+fn merges_base_config() {
+	let config = read("extends-merge", "addon/gluapack.toml", &[
+		("shared/base.json", r#"{ "exclude": ["tests/*", "docs/*"], "minify": { "sv": true }, "chunk_size": 1000, "include_cl": ["ui/*.lua"] }"#),
+		("addon/gluapack.toml", "extends = \"../shared/base.json\"\nexclude = [\"docs/*\", \"old/*\"]\nchunk_size = 2000\n\n[minify]\ncl = true\n")
+	]).unwrap();
+
+	// Arrays are appended, objects merged and scalars overridden
+	assert_eq!(patterns(&config.exclude), vec!["tests/*", "docs/*", "old/*"]);
+	assert!(config.minify.sv && config.minify.cl && !config.minify.sh);
+	assert_eq!(config.chunk_size, 2000);
+	assert_eq!(patterns(&config.include_cl), vec!["ui/*.lua"]);
+
+	// Options no config sets keep their defaults
+	assert_eq!(patterns(&config.include_sh), patterns(&Config::default().include_sh));
+	assert_eq!(config.extends.as_deref(), Some("../shared/base.json"));
+}
+
+#[test]
+fn presets() {
+	// Extending the default preset adds to the default patterns instead of replacing them
+	let extended = read("extends-preset-default", "gluapack.json", &[("gluapack.json", r#"{ "extends": "preset:default", "include_sh": ["lib/*.lua"] }"#)]).unwrap();
+	let replaced = read("extends-no-preset", "gluapack.json", &[("gluapack.json", r#"{ "include_sh": ["lib/*.lua"] }"#)]).unwrap();
+	assert_eq!(patterns(&extended.include_sh), vec!["**/sh_*.lua", "**/*.sh.lua", "lib/*.lua"]);
+	assert_eq!(patterns(&replaced.include_sh), vec!["lib/*.lua"]);
+
+	// Presets can be extended through a base config
+	let config = read("extends-preset-chain", "addon/gluapack.yaml", &[
+		("base.json5", "{ extends: 'preset:darkrp-module', unique_id: 'darkrp' }"),
+		("addon/gluapack.yaml", "extends: ../base.json5\nexclude: [darkrp_modules/disabled/**]\n")
+	]).unwrap();
+	assert_eq!(config.unique_id.as_deref(), Some("darkrp"));
+	assert!(patterns(&config.include_sh).contains(&"darkrp_customthings/*.lua"));
+	assert!(patterns(&config.include_sv).contains(&"darkrp_config/mysql.lua"));
+	assert_eq!(patterns(&config.exclude), vec!["darkrp_modules/disabled/**"]);
+
+	for preset in Config::PRESETS.iter() {
+		assert!(Config::preset(preset).is_some(), "{}", preset);
+	}
+}
+
+#[test]
+fn errors() {
+	let error = |result: Result<Config, PackingError>| match result {
+		Err(PackingError::ConfigError { error, .. }) => error,
+		result => panic!("expected a config error, got {:?}", result.map(|_| ()))
+	};
+
+	let unknown = error(read("extends-unknown-preset", "gluapack.json", &[("gluapack.json", "{\n  \"extends\": \"preset:nope\"\n}")]));
+	assert_eq!((unknown.line, unknown.column), (2, 4));
+	assert_eq!(unknown.message, "unknown preset `nope`, the presets are: default, darkrp-module");
+
+	let missing = error(read("extends-missing", "gluapack.json", &[("gluapack.json", r#"{ "extends": "base.json" }"#)]));
+	assert!(missing.message.starts_with("couldn't find"), "{}", missing.message);
+
+	let circular = error(read("extends-circular", "a.json", &[("a.json", r#"{ "extends": "b.json" }"#), ("b.json", r#"{ "extends": "a.json" }"#)]));
+	assert!(circular.message.starts_with("circular extends: ") && circular.message.ends_with("a.json"), "{}", circular.message);
+
+	// Errors in a base config point into it
+	let base = error(read("extends-base-error", "gluapack.json", &[("gluapack.json", r#"{ "extends": "base.toml" }"#), ("base.toml", "chunking = \"fast\"\n")]));
+	assert!(base.path.ends_with("base.toml") && base.line == 1, "{:?}", base);
+}
@@ -0,0 +1 @@
+include("fixture/cl_hud.lua")
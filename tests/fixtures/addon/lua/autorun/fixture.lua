@@ -0,0 +1,2 @@
+AddCSLuaFile()
+include("fixture/sh_init.lua")
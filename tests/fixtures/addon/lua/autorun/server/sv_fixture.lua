@@ -0,0 +1 @@
+include("fixture/sv_database.lua")
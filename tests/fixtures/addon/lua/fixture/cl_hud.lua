@@ -0,0 +1,3 @@
+hook.Add("HUDPaint", "fixture", function()
+	draw.SimpleText("fixture", "DermaDefault", 8, 8, color_white)
+end)
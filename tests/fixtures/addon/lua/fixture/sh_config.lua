@@ -0,0 +1,3 @@
+FIXTURE.Config = {
+	Enabled = true,
+}
@@ -0,0 +1,6 @@
+FIXTURE = FIXTURE or {}
+FIXTURE.Version = "1.0.0"
+
+if SERVER then
+	AddCSLuaFile("fixture/cl_hud.lua")
+end
@@ -0,0 +1 @@
+FIXTURE.Lang = "en"
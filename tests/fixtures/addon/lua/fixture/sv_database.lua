@@ -0,0 +1,5 @@
+FIXTURE.DB = {}
+
+function FIXTURE.DB:Query(query)
+	return sql.Query(query)
+end
@@ -0,0 +1,6 @@
+local PANEL = {}
+
+function PANEL:Init()
+end
+
+vgui.Register("FixturePanel", PANEL, "DPanel")
@@ -0,0 +1,51 @@
+use std::io::{Cursor, Read};
+
+use gluapack::{format::{PackHeader, FLAG_CHECKSUMS, FLAG_DEDUP, FORMAT_VERSION, HEADER_LEN}, UnpackingError};
+
+fn read(bytes: &[u8]) -> (Result<PackHeader, UnpackingError>, Vec<u8>) {
+	let mut cursor = Cursor::new(bytes.to_vec());
+	let header = PackHeader::read(&mut cursor);
+	let mut rest = vec![];
+	cursor.read_to_end(&mut rest).unwrap();
+	(header, rest)
+}
+
+#[test]
+fn round_trip() {
+	let header = PackHeader::with_flags(FLAG_CHECKSUMS | FLAG_DEDUP);
+	let mut bytes = header.to_bytes().to_vec();
+	assert_eq!(bytes, format!("GLUAPACK{:02x}09\n", FORMAT_VERSION).into_bytes());
+	assert_eq!(bytes.len(), HEADER_LEN);
+
+	bytes.extend_from_slice(b"print(1)");
+	let (read, rest) = read(&bytes);
+	assert_eq!(read.unwrap(), header);
+	assert_eq!(rest, b"print(1)");
+}
+
+#[test]
+fn legacy_packs_have_no_header() {
+	// The reader is rewound, so nothing of the pack is skipped
+	for bytes in [&b"autorun/foo.lua\0"[..], b"GLUA", b""] {
+		let (header, rest) = read(bytes);
+		assert_eq!(header.unwrap(), PackHeader::LEGACY);
+		assert_eq!(rest, bytes);
+	}
+}
+
+#[test]
+fn rejects_newer_versions_and_unknown_flags() {
+	let (header, _) = read(format!("GLUAPACK{:02x}00\n", FORMAT_VERSION + 1).as_bytes());
+	assert!(matches!(header, Err(UnpackingError::UnsupportedFormatVersion { .. })), "{:?}", header);
+
+	let (header, _) = read(format!("GLUAPACK{:02x}80\n", FORMAT_VERSION).as_bytes());
+	assert!(matches!(header, Err(UnpackingError::UnsupportedFormatFlags { .. })), "{:?}", header);
+}
+
+#[test]
+fn rejects_corrupt_headers() {
+	for bytes in ["GLUAPACKaéb\n", "GLUAPACK0g00\n", "GLUAPACK0100", "GLUAPACK01", "GLUAPACK0100\r", "GLUAPACK+1+1\n"] {
+		let (header, _) = read(bytes.as_bytes());
+		assert!(matches!(header, Err(UnpackingError::InvalidHeader { .. })), "{:?}: {:?}", bytes, header);
+	}
+}
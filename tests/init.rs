@@ -0,0 +1,102 @@
+use std::path::Path;
+
+use gluapack::{init::Reason, Config, Initializer, InitReport, Packer, PackOptions};
+
+mod common;
+use common::{out_dir, write};
+
+/// A legacy addon without a gluapack.json.
+fn legacy_addon(dir: &Path) {
+	write(dir, &[
+		("lua/autorun/my_addon.lua", "AddCSLuaFile(\"my_addon/sh_config.lua\")\ninclude(\"my_addon/sh_config.lua\")\nif SERVER then\n\tinclude(\"my_addon/server/init.lua\")\n\tAddCSLuaFile(\"my_addon/ui/menu.lua\")\n\tAddCSLuaFile(\"my_addon/ui/hud.lua\")\nelse\n\tinclude(\"my_addon/ui/menu.lua\")\n\tinclude(\"my_addon/ui/hud.lua\")\nend\n"),
+		("lua/autorun/server/sv_boot.lua", "print(\"boot\")\n"),
+		("lua/autorun/client/boot.lua", "print(\"boot\")\n"),
+		("lua/my_addon/sh_config.lua", "MyAddon = {}\n"),
+		("lua/my_addon/cl_extra.lua", "print(\"extra\")\n"),
+		("lua/my_addon/server/init.lua", "include(\"db.lua\")\n"),
+		("lua/my_addon/server/db.lua", "print(\"db\")\n"),
+		("lua/my_addon/ui/menu.lua", "print(\"menu\")\n"),
+		("lua/my_addon/ui/hud.lua", "print(\"hud\")\n"),
+		("lua/my_addon/ui/[legacy].lua", "AddCSLuaFile()\nprint(\"legacy\")\n"),
+		("lua/misc/orphan.lua", "print(\"orphan\")\n")
+	]);
+}
+
+fn patterns(patterns: &[gluapack::init::ProposedPattern]) -> Vec<(&str, Reason, usize)> {
+	patterns.iter().map(|pattern| (pattern.pattern.as_str(), pattern.reason, pattern.files)).collect()
+}
+
+fn realm<'a>(report: &'a InitReport, path: &str) -> (Option<&'a str>, bool, Option<Reason>) {
+	let file = report.files.iter().find(|file| file.path == path).unwrap();
+	(file.realm, file.entry, file.reason)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn classifies_conventions() {
+	let dir = out_dir("init-classify");
+	legacy_addon(&dir);
+	let report = Initializer::init(dir.clone()).await;
+	std::fs::remove_dir_all(&dir).ok();
+	let report = report.unwrap();
+
+	assert_eq!(realm(&report, "autorun/my_addon.lua"), (Some("sh"), true, Some(Reason::EntryFolder)));
+	assert_eq!(realm(&report, "autorun/client/boot.lua"), (Some("cl"), true, Some(Reason::EntryFolder)));
+	assert_eq!(realm(&report, "my_addon/cl_extra.lua"), (Some("cl"), false, Some(Reason::Name)));
+	assert_eq!(realm(&report, "my_addon/server/db.lua"), (Some("sv"), false, Some(Reason::Included)));
+	assert_eq!(realm(&report, "my_addon/ui/hud.lua"), (Some("cl"), false, Some(Reason::Included)));
+	assert_eq!(realm(&report, "my_addon/ui/[legacy].lua"), (Some("sh"), false, Some(Reason::AddCSLuaFile)));
+	assert_eq!(report.unclassified().map(|file| file.path.as_str()).collect::<Vec<_>>(), vec!["misc/orphan.lua"]);
+
+	// Folders with files of more than one realm are listed by path, with glob characters escaped
+	assert_eq!(patterns(&report.include_sh), vec![("**/sh_*.lua", Reason::Name, 1), ("my_addon/ui/[[]legacy[]].lua", Reason::AddCSLuaFile, 1)]);
+	assert_eq!(patterns(&report.include_cl), vec![("**/cl_*.lua", Reason::Name, 1), ("my_addon/ui/hud.lua", Reason::Included, 1), ("my_addon/ui/menu.lua", Reason::Included, 1)]);
+	assert_eq!(patterns(&report.include_sv), vec![("**/sv_*.lua", Reason::Name, 1), ("my_addon/server/*.lua", Reason::Included, 2)]);
+	assert_eq!(patterns(&report.entry_cl), vec![("autorun/client/*.lua", Reason::EntryFolder, 1)]);
+	assert_eq!(patterns(&report.entry_sh), vec![("autorun/*.lua", Reason::EntryFolder, 1)]);
+	assert_eq!(patterns(&report.entry_sv), vec![("autorun/server/*.lua", Reason::EntryFolder, 1)]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn skips_patterns_matching_other_realms() {
+	// sh_menu.lua is in autorun/client/, so it's a clientside entry file and **/sh_*.lua would pack it as shared too
+	let dir = out_dir("init-conflict");
+	write(&dir, &[
+		("lua/autorun/client/sh_menu.lua", "print(\"menu\")\n"),
+		("lua/my_addon/sh_a.lua", "print(\"a\")\n"),
+		("lua/my_addon/sh_b.lua", "print(\"b\")\n")
+	]);
+	let report = Initializer::init(dir.clone()).await;
+	std::fs::remove_dir_all(&dir).ok();
+	let report = report.unwrap();
+
+	assert_eq!(patterns(&report.include_sh), vec![("my_addon/*.lua", Reason::Name, 2)]);
+	assert_eq!(patterns(&report.entry_cl), vec![("autorun/client/*.lua", Reason::EntryFolder, 1)]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn generated_config_packs() {
+	let (dir, packed) = (out_dir("init-pack-src"), out_dir("init-pack"));
+	legacy_addon(&dir);
+
+	let report = Initializer::init(dir.clone()).await.unwrap();
+	let json = report.config_json();
+	std::fs::write(dir.join("gluapack.json"), &json).unwrap();
+	let config = Config::read(dir.join("gluapack.json"));
+	let packed_report = Packer::pack(dir.clone(), PackOptions::new().out_dir(&packed).quiet(true)).await;
+
+	std::fs::remove_dir_all(&dir).ok();
+	std::fs::remove_dir_all(&packed).ok();
+
+	// The generated config is commented and lists the files that couldn't be classified
+	assert!(json.contains("\"my_addon/server/*.lua\" // 2 file(s), included from the entry files\n"), "{}", json);
+	assert!(json.contains("    //   misc/orphan.lua\n"), "{}", json);
+
+	let config = config.unwrap();
+	assert_eq!(config.include_sv.iter().map(|pattern| pattern.as_str()).collect::<Vec<_>>(), vec!["**/sv_*.lua", "my_addon/server/*.lua"]);
+	assert_eq!(config.chunk_size, Config::default().chunk_size);
+
+	// Every classified file is packed, without realm conflicts
+	let packed_report = packed_report.unwrap();
+	assert_eq!((packed_report.sv.files, packed_report.cl.files, packed_report.sh.files), (report.files_in("sv"), report.files_in("cl"), report.files_in("sh")));
+	assert_eq!(packed_report.unpacked_files(), report.files.len() - 1);
+}
@@ -0,0 +1,73 @@
+use gluapack::{unpack::EntryFiles, Config, Inspector, Packer, PackOptions};
+
+mod common;
+use common::{fixture, out_dir, write};
+
+async fn inspect_fixture(name: &str) -> gluapack::InspectReport {
+	let out = out_dir(name);
+	let config = Config { unique_id: Some("inspect".to_string()), ..Default::default() };
+	Packer::pack(fixture(), PackOptions::new().out_dir(&out).quiet(true).config(config)).await.unwrap();
+	let report = Inspector::inspect(out.clone()).await;
+	std::fs::remove_dir_all(&out).ok();
+	report.unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn table() {
+	let report = inspect_fixture("inspect-table").await;
+	assert_eq!(report.table(), "\
+gluapack/inspect/
+  REALM  SIZE  CHUNKS  ENTRY  PATH
+  sv       35  sv      yes    autorun/server/sv_fixture.lua
+  sv       79  sv             fixture/sv_database.lua
+  cl       30  1       yes    autorun/client/cl_fixture.lua
+  cl      111  1              fixture/cl_hud.lua
+  cl       92  1       yes    vgui/fixture_panel.lua
+  sh       46  1       yes    autorun/fixture.lua
+  sh       38  1              fixture/sh_config.lua
+  sh      106  1              fixture/sh_init.lua
+  sh       20  1              fixture/shared.sh.lua
+
+9 packed file(s), 557 byte(s)
+");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn json() {
+	let report = inspect_fixture("inspect-json").await;
+	let json: serde_json::Value = serde_json::from_str(&report.json()).unwrap();
+
+	let files = json["files"].as_array().unwrap();
+	assert_eq!(files.len(), 9);
+	assert_eq!(files[0], serde_json::json!({
+		"unique_id": "inspect",
+		"path": "autorun/server/sv_fixture.lua",
+		"realm": "sv",
+		"size": 35,
+		"chunks": ["gluapack/inspect/gluapack.sv.lua"],
+		"entry": true,
+		"duplicate_of": null
+	}));
+	assert_eq!(files.iter().filter(|file| file["entry"] == true).count(), 4);
+	assert!(files.iter().all(|file| file["chunks"].as_array().unwrap().len() == 1));
+}
+
+#[test]
+fn reads_entry_files_by_name() {
+	let dir = out_dir("inspect-loaders");
+	write(&dir, &[
+		// Tables are found by name, whatever the rest of the loader looks like
+		("named.lua", "local ENTRY_FILES_SH = {\"a.lua\"}\nlocal ENTRY_FILES_CL = {}\nlocal ENTRY_FILES_SV = {\"b.lua\",\"c\\\"d.lua\"}\nfor _, v in ipairs({\"unrelated.lua\"}) do end\n"),
+		// Loaders of gluapack 0.3.0 inline them
+		("legacy.lua", "local function includeEntryFiles()\n\tfor _, v in ipairs({\"a.lua\"}) do end\n\tfor _, v in ipairs({}) do end\n\tfor _, v in ipairs({\"b.lua\"}) do end\nend\n"),
+		("invalid.lua", "local ENTRY_FILES_SH = {\"a.lua\"}\n")
+	]);
+	let named = EntryFiles::read_loader(dir.join("named.lua"));
+	let legacy = EntryFiles::read_loader(dir.join("legacy.lua"));
+	let invalid = EntryFiles::read_loader(dir.join("invalid.lua"));
+	std::fs::remove_dir_all(&dir).ok();
+
+	assert_eq!(named.unwrap(), EntryFiles { sv: vec!["b.lua".to_string(), "c\"d.lua".to_string()], cl: vec![], sh: vec!["a.lua".to_string()] });
+	assert_eq!(legacy.unwrap(), EntryFiles { sv: vec!["b.lua".to_string()], cl: vec![], sh: vec!["a.lua".to_string()] });
+	assert!(invalid.is_err());
+}
@@ -0,0 +1,55 @@
+use gluapack::{Config, Packer, PackOptions, PackingError, RealmReport, Unpacker, UnpackOptions, UnpackingError};
+
+mod common;
+use common::{fixture, out_dir};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pack_and_unpack_reports() {
+	let (packed, unpacked) = (out_dir("library-packed"), out_dir("library-unpacked"));
+
+	let config = Config { chunk_size: 1024, ..Default::default() };
+	let pack_report = Packer::pack(fixture(), PackOptions::new().out_dir(&packed).quiet(true).config(config)).await;
+	let unpack_report = Unpacker::unpack(packed.clone(), UnpackOptions::new().out_dir(&unpacked).quiet(true)).await;
+	let unpacked_lua = unpacked.join("lua/fixture/sh_init.lua").is_file();
+	let copied = unpacked.join("materials/fixture/icon.png").is_file();
+
+	std::fs::remove_dir_all(&packed).ok();
+	std::fs::remove_dir_all(&unpacked).ok();
+
+	let pack_report = pack_report.unwrap();
+	assert_eq!(pack_report.sv, RealmReport { files: 2, chunks: 1 });
+	assert_eq!(pack_report.cl, RealmReport { files: 3, chunks: 1 });
+	assert_eq!(pack_report.sh, RealmReport { files: 4, chunks: 1 });
+	assert_eq!(pack_report.unpacked_files(), 9);
+	assert_eq!(pack_report.packed_files(), 5);
+
+	let unpack_report = unpack_report.unwrap();
+	assert_eq!((unpack_report.sv, unpack_report.cl, unpack_report.sh), (pack_report.sv, pack_report.cl, pack_report.sh));
+	assert_eq!(unpack_report.unpacked_files(), pack_report.unpacked_files());
+	assert!(unpacked_lua && copied);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn no_copy_only_writes_lua_files() {
+	let (packed, unpacked) = (out_dir("library-no-copy-packed"), out_dir("library-no-copy-unpacked"));
+
+	let pack_report = Packer::pack(fixture(), PackOptions::new().out_dir(&packed).quiet(true).no_copy(true)).await;
+	let copied = packed.join("materials").exists();
+	let unpack_report = Unpacker::unpack(packed.clone(), UnpackOptions::new().out_dir(&unpacked).quiet(true).no_copy(true)).await;
+	let unpacked_lua = unpacked.join("lua/fixture/sh_init.lua").is_file();
+
+	std::fs::remove_dir_all(&packed).ok();
+	std::fs::remove_dir_all(&unpacked).ok();
+
+	assert_eq!(pack_report.unwrap().unpacked_files(), unpack_report.unwrap().unpacked_files());
+	assert!(!copied && unpacked_lua);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn output_cannot_be_the_addon() {
+	let result = Packer::pack(fixture(), PackOptions::new().out_dir(fixture()).quiet(true)).await;
+	assert!(matches!(result, Err(PackingError::OutputIsAddon { .. })), "{:?}", result.map(|report| report.unique_id));
+
+	let result = Unpacker::unpack(fixture(), UnpackOptions::new().out_dir(fixture()).quiet(true)).await;
+	assert!(matches!(result, Err(UnpackingError::OutputIsAddon { .. })), "{:?}", result.map(|report| report.elapsed));
+}
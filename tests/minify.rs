@@ -0,0 +1,42 @@
+use gluapack::{lexer::Lexer, minify::minify};
+
+/// Text and line of every token
+fn tokens(src: &[u8]) -> Vec<(Vec<u8>, usize)> {
+	Lexer::new(src).map(|token| token.unwrap()).map(|token| (token.text.to_vec(), token.line)).collect()
+}
+
+const SRC: &str = r#"-- A comment
+local x = 1 -- trailing comment
+/* GLua block
+   comment */
+if x != 2 && !false || x ~= 3 then // GLua line comment
+	print( "--not a comment", 'it''s' )
+	local s = [[
+  long -- string
+]]
+	local t = { [ [[key]] ] = x - -x, 1 ..x, 0x1p4 }
+	for i = 1, #t do
+		if i == 2 then continue end
+	end
+end
+--[==[ long
+comment ]==]
+return x..y
+"#;
+
+#[test]
+fn minify_preserves_tokens_and_lines() {
+	let minified = minify(SRC.as_bytes()).unwrap();
+	assert!(minified.len() < SRC.len());
+	assert_eq!(tokens(&minified), tokens(SRC.as_bytes()));
+}
+
+#[test]
+fn minify_output() {
+	assert_eq!(
+		String::from_utf8(minify(b"local  a = b - -c  -- comment\n\tprint( a .. \"x\" , 1 .. 2 )").unwrap()).unwrap(),
+		"local a=b- -c\nprint(a..\"x\",1 ..2)"
+	);
+	assert_eq!(String::from_utf8(minify(b"t[ [[a]] ] = #t").unwrap()).unwrap(), "t[ [[a]]]=#t");
+	assert!(minify(b"print(\"unfinished)").is_err());
+}
@@ -0,0 +1,116 @@
+use gluapack::{config::{ChunkingStrategy, ConfigOverrides}, Config, Packer, PackOptions, PackingError};
+
+mod common;
+use common::{out_dir, write};
+
+fn patterns(patterns: &[gluapack::config::GlobPattern]) -> Vec<&str> {
+	patterns.iter().map(|pattern| pattern.as_str()).collect()
+}
+
+fn invalid(result: Result<(), PackingError>) -> String {
+	match result {
+		Err(PackingError::InvalidOverride { error, .. }) => error,
+		result => panic!("expected an invalid override, got {:?}", result)
+	}
+}
+
+#[test]
+fn overrides_options() {
+	let mut overrides = ConfigOverrides::new();
+	overrides.set("--unique-id", "unique_id", &["my_addon"]).unwrap();
+	overrides.set("--chunk-size", "chunk_size", &["1024"]).unwrap();
+	overrides.set("--chunking", "chunking", &["file_aligned"]).unwrap();
+	overrides.set("--dedup", "dedup", &["no"]).unwrap();
+	overrides.set("--minify", "minify", &["cl,sh"]).unwrap();
+	overrides.append("--exclude", "exclude", &["tests/*", "docs/*"]).unwrap();
+	overrides.set("--set-include-sv", "include_sv", &["server/*.lua"]).unwrap();
+	assert_eq!(overrides.sources().collect::<Vec<_>>(), vec!["--unique-id", "--chunk-size", "--chunking", "--dedup", "--minify", "--exclude", "--set-include-sv"]);
+
+	let mut config = Config::default();
+	config.exclude = vec![gluapack::config::GlobPattern::new("docs/*"), gluapack::config::GlobPattern::new("old/*")];
+	let config = overrides.apply(config).unwrap();
+
+	assert_eq!(config.unique_id.as_deref(), Some("my_addon"));
+	assert_eq!(config.chunk_size, 1024);
+	assert_eq!(config.chunking, ChunkingStrategy::FileAligned);
+	assert!(!config.dedup);
+	assert!(!config.minify.sv && config.minify.cl && config.minify.sh);
+
+	// Appending skips patterns that are already there, setting replaces them
+	assert_eq!(patterns(&config.exclude), vec!["docs/*", "old/*", "tests/*"]);
+	assert_eq!(patterns(&config.include_sv), vec!["server/*.lua"]);
+	assert_eq!(patterns(&config.include_cl), patterns(&Config::default().include_cl));
+}
+
+#[test]
+fn later_overrides_win() {
+	let mut overrides = ConfigOverrides::new();
+	overrides.set("GLUAPACK_CHUNK_SIZE", "chunk_size", &["2048"]).unwrap();
+	overrides.set("GLUAPACK_UNIQUE_ID", "unique_id", &["from_env"]).unwrap();
+	overrides.set("--chunk-size", "chunk_size", &["4096"]).unwrap();
+	overrides.set("--unique-id", "unique_id", &[""]).unwrap();
+
+	let config = overrides.apply(Config::default()).unwrap();
+	assert_eq!(config.chunk_size, 4096);
+	assert_eq!(config.unique_id, None);
+}
+
+#[test]
+fn invalid_overrides_name_their_source() {
+	let mut overrides = ConfigOverrides::new();
+	assert_eq!(invalid(overrides.set("--dedup", "dedup", &["maybe"])), "--dedup: expected true or false, found `maybe`");
+	assert_eq!(invalid(overrides.set("GLUAPACK_CHUNK_SIZE", "chunk_size", &["big"])), "GLUAPACK_CHUNK_SIZE: expected a number, found `big`");
+	assert_eq!(invalid(overrides.set("--minify", "minify", &["cl,client"])), "--minify: unknown realm `client`, expected sv, cl, sh or none");
+	assert_eq!(invalid(overrides.append("--chunking", "chunking", &["stream"])), "--chunking: `chunking` isn't a list");
+	assert_eq!(invalid(overrides.set("--extends", "extends", &["preset:default"])), "--extends: unknown config option `extends`");
+
+	let error = invalid(overrides.set("--chunking", "chunking", &["fast"]));
+	assert!(error.starts_with("--chunking: unknown variant `fast`"), "{}", error);
+
+	assert!(overrides.is_empty());
+}
+
+#[test]
+fn reads_environment() {
+	// The only test that touches the environment, as tests run in parallel
+	std::env::set_var("GLUAPACK_SET_EXCLUDE", "a/*");
+	std::env::set_var("GLUAPACK_EXCLUDE", "b/*, c/*,");
+	std::env::set_var("GLUAPACK_AUTO_REALMS", "true");
+	let overrides = ConfigOverrides::from_env();
+	std::env::set_var("GLUAPACK_DEDUP", "sometimes");
+	let invalid = ConfigOverrides::from_env();
+	for env in ["GLUAPACK_SET_EXCLUDE", "GLUAPACK_EXCLUDE", "GLUAPACK_AUTO_REALMS", "GLUAPACK_DEDUP"] {
+		std::env::remove_var(env);
+	}
+
+	let overrides = overrides.unwrap();
+	assert_eq!(overrides.sources().collect::<Vec<_>>(), vec!["GLUAPACK_SET_EXCLUDE", "GLUAPACK_EXCLUDE", "GLUAPACK_AUTO_REALMS"]);
+	let config = overrides.apply(Config::default()).unwrap();
+	assert_eq!(patterns(&config.exclude), vec!["a/*", "b/*", "c/*"]);
+	assert!(config.auto_realms);
+
+	assert!(matches!(invalid, Err(PackingError::InvalidOverride { error, .. }) if error.starts_with("GLUAPACK_DEDUP:")));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn overrides_config_file() {
+	let (dir, packed) = (out_dir("overrides-src"), out_dir("overrides"));
+	write(&dir, &[
+		("gluapack.json", r#"{ "unique_id": "from_file", "include_sh": ["my_addon/*.lua"], "entry_sh": ["autorun/my_addon.lua"] }"#),
+		("lua/autorun/my_addon.lua", "include(\"my_addon/core.lua\")\n"),
+		("lua/my_addon/core.lua", "MyAddon = {}\n"),
+		("lua/my_addon/debug.lua", "MyAddon.Debug = true\n")
+	]);
+
+	let mut overrides = ConfigOverrides::new();
+	overrides.set("--unique-id", "unique_id", &["overridden"]).unwrap();
+	overrides.append("--exclude", "exclude", &["my_addon/debug.lua"]).unwrap();
+	let report = Packer::pack(dir.clone(), PackOptions::new().out_dir(&packed).quiet(true).overrides(overrides)).await;
+
+	std::fs::remove_dir_all(&dir).ok();
+	std::fs::remove_dir_all(&packed).ok();
+
+	let report = report.unwrap();
+	assert_eq!(report.unique_id, "overridden");
+	assert_eq!(report.sh.files, 2);
+}
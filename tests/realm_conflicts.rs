@@ -0,0 +1,79 @@
+use std::path::Path;
+
+use gluapack::{config::{GlobPattern, RealmConflicts}, Config, Inspector, Packer, PackOptions, PackingError};
+
+mod common;
+use common::{out_dir};
+
+/// An addon whose files all match more than one realm.
+fn addon(dir: &Path) {
+	let lua = dir.join("lua");
+	std::fs::create_dir_all(lua.join("autorun/client")).unwrap();
+	std::fs::create_dir_all(lua.join("conflict")).unwrap();
+
+	std::fs::write(lua.join("autorun/client/cl_conflict.lua"), "include(\"conflict/a.lua\")\n").unwrap();
+	std::fs::write(lua.join("conflict/a.lua"), "print(\"a\")\n").unwrap();
+	std::fs::write(lua.join("conflict/b.lua"), "print(\"b\")\n").unwrap();
+	std::fs::write(lua.join("conflict/sh_c.lua"), "print(\"c\")\n").unwrap();
+}
+
+fn config(realm_conflicts: RealmConflicts) -> Config {
+	Config {
+		include_sv: vec![GlobPattern::new("conflict/*.lua")],
+		include_cl: vec![GlobPattern::new("conflict/*.lua")],
+		include_sh: vec![GlobPattern::new("conflict/sh_*.lua")],
+		entry_sv: vec![],
+		entry_sh: vec![],
+		realm_conflicts,
+		..Default::default()
+	}
+}
+
+/// Packs the addon, returning the realm of each packed file in the conflict folder.
+async fn pack(name: &str, realm_conflicts: RealmConflicts) -> Result<Vec<(String, &'static str)>, PackingError> {
+	let (dir, out) = (out_dir(&format!("{}-src", name)), out_dir(name));
+	addon(&dir);
+
+	let result = Packer::pack(dir.clone(), PackOptions::new().out_dir(&out).quiet(true).config(config(realm_conflicts))).await;
+	let realms = match result {
+		Ok(_) => Ok(Inspector::inspect(out.clone()).await.unwrap().files.into_iter().filter(|file| file.path.starts_with("conflict/")).map(|file| (file.path, file.realm)).collect()),
+		Err(error) => Err(error)
+	};
+
+	std::fs::remove_dir_all(&dir).ok();
+	std::fs::remove_dir_all(&out).ok();
+
+	realms
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn lists_every_conflict() {
+	match pack("realm-conflicts-error", RealmConflicts::Error).await {
+		Err(PackingError::RealmConflict { error, .. }) => {
+			let conflicts = error.iter().map(|conflict| (conflict.path.as_str(), conflict.realms.clone())).collect::<Vec<_>>();
+			assert_eq!(conflicts, vec![
+				("conflict/a.lua", vec!["sv", "cl"]),
+				("conflict/b.lua", vec!["sv", "cl"]),
+				("conflict/sh_c.lua", vec!["sv", "cl", "sh"])
+			]);
+		},
+		result => panic!("expected a realm conflict, got {:?}", result)
+	}
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn resolves_conflicts() {
+	let realms = |a, b, c| vec![("conflict/a.lua".to_string(), a), ("conflict/b.lua".to_string(), b), ("conflict/sh_c.lua".to_string(), c)];
+
+	let mut promoted = pack("realm-conflicts-promote-shared", RealmConflicts::PromoteShared).await.unwrap();
+	promoted.sort();
+	assert_eq!(promoted, realms("sh", "sh", "sh"));
+
+	let mut sv = pack("realm-conflicts-prefer-sv", RealmConflicts::PreferSv).await.unwrap();
+	sv.sort();
+	assert_eq!(sv, realms("sv", "sv", "sv"));
+
+	let mut cl = pack("realm-conflicts-prefer-cl", RealmConflicts::PreferCl).await.unwrap();
+	cl.sort();
+	assert_eq!(cl, realms("cl", "cl", "cl"));
+}
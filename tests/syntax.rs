@@ -0,0 +1,101 @@
+use gluapack::{Packer, PackOptions, PackingError, syntax::{validate, Diagnostic}};
+
+mod common;
+use common::{out_dir};
+
+const VALID: &str = r#"
+local PANEL = {}
+function PANEL:Init(...)
+	local args = { ... }
+	if #args != 0 && !args[1] || args[2] ~= nil then return end
+	for i = 1, 10, 2 do
+		if i == 3 then continue end
+	end
+	for k, v in pairs(args) do print(k, v) end
+	while false do break end
+	repeat local x = 1 until x
+	::skip::
+	goto skip
+end
+vgui.Register("Panel", PANEL, "DPanel")
+hook.Add("Think", "x", function() return 0x1p4, 1e-3, .5, 1ULL end)
+local t = { [1] = "a"; b = [[long]], "c", }
+t.x, t[1] = print"a", print{ t }
+return t
+"#;
+
+fn error(src: &str) -> (String, usize, usize) {
+	let error = validate(src.as_bytes()).unwrap_err();
+	(error.message, error.line, error.column)
+}
+
+#[test]
+fn accepts_valid_glua() {
+	assert_eq!(validate(VALID.as_bytes()), Ok(()));
+}
+
+#[test]
+fn rejects_invalid_glua() {
+	assert_eq!(error("if x then\n\tprint(1)\n"), ("'end' expected (to close 'if' at line 1) near '<eof>'".to_string(), 3, 1));
+	assert_eq!(error("local x = = 1"), ("unexpected symbol near '='".to_string(), 1, 11));
+	assert_eq!(error("x + 1"), ("syntax error near '+'".to_string(), 1, 3));
+	assert_eq!(error("f()\n(g)()"), ("ambiguous syntax (function call x new statement) near '('".to_string(), 2, 1));
+	assert_eq!(error("local function f() return ... end"), ("cannot use '...' outside a vararg function near '...'".to_string(), 1, 27));
+	assert_eq!(error("x = 1abc"), ("malformed number near '1abc'".to_string(), 1, 5));
+	assert_eq!(error("return 1\nprint(2)"), ("'<eof>' expected near 'print'".to_string(), 2, 1));
+	assert_eq!(error("print(\"unfinished)"), ("unfinished string".to_string(), 1, 7));
+}
+
+#[test]
+fn limits_nesting_like_luajit() {
+	let nested = |depth: usize, open: &str, inner: &str, close: &str| format!("x = {}{}{}", open.repeat(depth), inner, close.repeat(depth));
+	let too_deep = "chunk has too many syntax levels";
+
+	// The chunk and the assigned expression take two of LuaJIT's 200 levels
+	assert_eq!(validate(nested(197, "(", "1", ")").as_bytes()), Ok(()));
+	assert_eq!(error(&nested(198, "(", "1", ")")), (too_deep.to_string(), 1, 203));
+	assert_eq!(validate(nested(198, "{", "", "}").as_bytes()), Ok(()));
+	assert_eq!(error(&nested(199, "{", "", "}")).0, too_deep);
+
+	// Right associative and unary operators nest, left associative ones don't
+	assert_eq!(error(&format!("x = {}1", "a .. ".repeat(200))).0, too_deep);
+	assert_eq!(error(&format!("x = {}1", "not ".repeat(200))).0, too_deep);
+	assert_eq!(validate(format!("x = {}1", "a + ".repeat(10000)).as_bytes()), Ok(()));
+	assert_eq!(error(&format!("{}{}", "do ".repeat(200), "end ".repeat(200))).0, too_deep);
+
+	// Instead of overflowing the stack
+	assert_eq!(error(&nested(100000, "(", "1", ")")).0, too_deep);
+	assert_eq!(error(&nested(100000, "{", "", "}")).0, too_deep);
+}
+
+#[test]
+fn diagnostic_excerpt() {
+	let src = "local a = 1\r\n\tlocal b = = 2\r\n";
+	let error = validate(src.as_bytes()).unwrap_err();
+	let diagnostic = Diagnostic::new("test.lua".to_string(), src.as_bytes(), error);
+	assert_eq!(diagnostic.excerpt, "\tlocal b = = 2");
+	assert_eq!(diagnostic.to_string(), "test.lua:2:12: unexpected symbol near '='\n 2 |     local b = = 2\n   |               ^");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pack_fails_on_syntax_errors() {
+	let (addon, out) = (out_dir("syntax-addon"), out_dir("syntax-out"));
+	std::fs::create_dir_all(addon.join("lua/autorun")).unwrap();
+	std::fs::write(addon.join("lua/autorun/broken.lua"), "print(\"ok\")\nif true then\n").unwrap();
+
+	let options = PackOptions::new().out_dir(&out).quiet(true);
+	let result = Packer::pack(addon.clone(), options.clone()).await;
+	let unvalidated = Packer::pack(addon.clone(), options.validate(false)).await;
+
+	std::fs::remove_dir_all(&addon).ok();
+	std::fs::remove_dir_all(&out).ok();
+
+	match result {
+		Err(PackingError::SyntaxError { error, .. }) => {
+			assert_eq!(error.path, "autorun/broken.lua");
+			assert_eq!((error.line, error.column), (3, 1));
+		},
+		other => panic!("expected a syntax error, got {:?}", other)
+	}
+	assert!(unvalidated.is_ok());
+}
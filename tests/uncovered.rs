@@ -0,0 +1,57 @@
+use gluapack::{config::Uncovered, Config, Packer, PackOptions, PackingError, UnusedPattern};
+
+mod common;
+use common::{out_dir, write};
+
+async fn pack(name: &str, config: &str) -> Result<gluapack::PackReport, PackingError> {
+	let (dir, packed) = (out_dir(&format!("{}-src", name)), out_dir(name));
+	write(&dir, &[
+		("gluapack.json", config),
+		("lua/autorun/my_addon.lua", "include(\"my_addon/sh_init.lua\")\n"),
+		("lua/my_addon/sh_init.lua", "MyAddon = {}\n"),
+		("lua/my_addon/legacy.lua", "print(\"legacy\")\n"),
+		("lua/my_addon/dev/debug.lua", "print(\"debug\")\n")
+	]);
+	let report = Packer::pack(dir.clone(), PackOptions::new().out_dir(&packed).quiet(true)).await;
+	std::fs::remove_dir_all(&dir).ok();
+	std::fs::remove_dir_all(&packed).ok();
+	report
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reports_uncovered_files() {
+	// Excluded files aren't uncovered
+	let report = pack("uncovered-warn", r#"{ "exclude": ["my_addon/dev/*"] }"#).await.unwrap();
+	assert_eq!(report.uncovered, vec!["my_addon/legacy.lua".to_string()]);
+	assert_eq!(Config::default().uncovered, Uncovered::Warn);
+
+	let report = pack("uncovered-ignore", r#"{ "uncovered": "ignore" }"#).await.unwrap();
+	assert_eq!(report.uncovered, vec!["my_addon/dev/debug.lua".to_string(), "my_addon/legacy.lua".to_string()]);
+
+	match pack("uncovered-error", r#"{ "uncovered": "error", "exclude": ["my_addon/dev/*"] }"#).await {
+		Err(PackingError::Uncovered { error, .. }) => assert_eq!(error, vec!["my_addon/legacy.lua".to_string()]),
+		result => panic!("expected uncovered files, got {:?}", result.map(|_| ()))
+	}
+
+	let report = pack("uncovered-none", r#"{ "uncovered": "error", "include_sh": ["**/sh_*.lua", "my_addon/*.lua"], "exclude": ["my_addon/dev/*"] }"#).await.unwrap();
+	assert!(report.uncovered.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn lints_unused_patterns() {
+	let report = pack("unused-patterns", r#"{
+		"extends": "preset:darkrp-module",
+		"include_sv": ["lua/my_addon/*.lua", "server/*.lua"],
+		"exclude": ["my_addon/dev/*", "my_addon/tests/*"],
+		"entry_cl": ["autorun/client/*.lua", "my_addon/cl_init.lua"]
+	}"#).await.unwrap();
+
+	// Patterns from the defaults and presets aren't checked
+	assert_eq!(report.unused_patterns, vec![
+		UnusedPattern { option: "include_sv", pattern: "lua/my_addon/*.lua".to_string(), relative_to_root: true },
+		UnusedPattern { option: "include_sv", pattern: "server/*.lua".to_string(), relative_to_root: false },
+		UnusedPattern { option: "exclude", pattern: "my_addon/tests/*".to_string(), relative_to_root: false },
+		UnusedPattern { option: "entry_cl", pattern: "my_addon/cl_init.lua".to_string(), relative_to_root: false }
+	]);
+	assert_eq!(report.unused_patterns[0].to_string(), "include_sv: lua/my_addon/*.lua (this matches files relative to the addon's root, but patterns are relative to lua/)");
+}
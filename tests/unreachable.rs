@@ -0,0 +1,25 @@
+use gluapack::{Config, Inspector, Packer, PackOptions};
+
+mod common;
+use common::{fixture, out_dir};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reports_and_prunes_unreachable_files() {
+	let (kept, pruned) = (out_dir("unreachable-kept"), out_dir("unreachable-pruned"));
+
+	let report = Packer::pack(fixture(), PackOptions::new().out_dir(&kept).quiet(true)).await.unwrap();
+	assert_eq!(report.unreachable, vec!["fixture/sh_config.lua".to_string(), "fixture/shared.sh.lua".to_string()]);
+
+	let config = Config { prune_unreachable: true, ..Default::default() };
+	let pruned_report = Packer::pack(fixture(), PackOptions::new().out_dir(&pruned).quiet(true).config(config)).await.unwrap();
+	assert_eq!(pruned_report.unreachable, report.unreachable);
+	assert_eq!(pruned_report.sh.files, report.sh.files - 2);
+
+	let packed = Inspector::inspect(pruned.clone()).await.unwrap().files.into_iter().map(|file| file.path).collect::<Vec<_>>();
+
+	std::fs::remove_dir_all(&kept).ok();
+	std::fs::remove_dir_all(&pruned).ok();
+
+	assert!(packed.iter().any(|path| path == "fixture/sh_init.lua"));
+	assert!(!packed.iter().any(|path| report.unreachable.contains(path)));
+}
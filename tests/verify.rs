@@ -0,0 +1,61 @@
+use std::{path::Path, process::Command};
+
+use gluapack::{verify::Mismatch, Config, Packer, PackOptions, Verifier};
+
+mod common;
+use common::{fixture, out_dir};
+
+/// Replaces the first occurrence of `from` in a file with `to`, which must be the same length.
+fn tamper(path: &Path, from: &[u8], to: &[u8]) {
+	assert_eq!(from.len(), to.len());
+	let mut bytes = std::fs::read(path).unwrap();
+	let start = bytes.windows(from.len()).position(|window| window == from).unwrap_or_else(|| panic!("{:?} not found in {}", String::from_utf8_lossy(from), path.display()));
+	bytes[start..start + from.len()].copy_from_slice(to);
+	std::fs::write(path, bytes).unwrap();
+}
+
+fn verify_command(dir: &Path) -> bool {
+	Command::new(env!("CARGO_BIN_EXE_gluapack")).arg("--quiet").arg("verify").arg(dir).output().unwrap().status.success()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn detects_tampering() {
+	let out = out_dir("verify-tampered");
+	let config = Config { unique_id: Some("verify".to_string()), ..Default::default() };
+	Packer::pack(fixture(), PackOptions::new().out_dir(&out).quiet(true).config(config)).await.unwrap();
+
+	let untouched = Verifier::verify(out.clone()).await.unwrap();
+	let untouched_command = verify_command(&out);
+
+	let pack_dir = out.join("lua/gluapack/verify");
+
+	// A file's contents
+	tamper(&pack_dir.join("gluapack.sv.lua"), b"include(\"fixture/sv_database.lua\")", b"include(\"fixture/sv_databasE.lua\")");
+
+	// The file count of a chunk, which changes its cache hash too
+	tamper(&pack_dir.join("gluapack.1.sh.lua"), b"\n--4|", b"\n--5|");
+
+	// The cache hash of an untouched chunk
+	let manifest = std::fs::read_to_string(pack_dir.join("manifest.lua")).unwrap();
+	let cl_hash = manifest.split("cl={\"").nth(1).unwrap()[..40].to_string();
+	tamper(&pack_dir.join("manifest.lua"), cl_hash.as_bytes(), &[b'0'; 40]);
+
+	let tampered = Verifier::verify(out.clone()).await.unwrap();
+	let tampered_command = verify_command(&out);
+
+	std::fs::remove_dir_all(&out).ok();
+
+	assert!(untouched.is_ok(), "{:?}", untouched.problems);
+	assert!(untouched_command);
+
+	let mut problems = tampered.problems.iter().map(|problem| (problem.path.as_str(), &problem.mismatch)).collect::<Vec<_>>();
+	problems.sort_by_key(|(path, _)| *path);
+	assert_eq!(problems.len(), 4, "{:?}", problems);
+	assert!(matches!(problems[0], ("autorun/server/sv_fixture.lua", Mismatch::FileHash { .. })), "{:?}", problems[0]);
+	assert!(matches!(problems[1], ("gluapack/verify/gluapack.1.cl.lua", Mismatch::ManifestHash { .. })), "{:?}", problems[1]);
+	assert!(problems[2..].iter().all(|(path, _)| *path == "gluapack/verify/gluapack.1.sh.lua"), "{:?}", problems);
+	assert!(problems[2..].iter().any(|(_, mismatch)| matches!(mismatch, Mismatch::FileCount { expected: 5, actual: 4 })), "{:?}", problems);
+	assert!(problems[2..].iter().any(|(_, mismatch)| matches!(mismatch, Mismatch::ManifestHash { .. })), "{:?}", problems);
+
+	assert!(!tampered_command);
+}
@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use gluapack::Watcher;
+
+mod common;
+use common::{out_dir, write};
+
+// The clock only moves when every task is waiting on it, so the timings below are exact
+#[tokio::test(start_paused = true)]
+async fn debounces_bursts_and_watches_config() {
+	let dir = out_dir("watch");
+	write(&dir, &[
+		("lua/autorun/watch.lua", "include(\"watch/sh_a.lua\")\n"),
+		("lua/watch/sh_a.lua", "print(\"a\")\n"),
+		("materials/watch.png", "png")
+	]);
+
+	let mut watcher = Watcher::new(dir.clone()).await.poll_interval(Duration::from_millis(10)).debounce(Duration::from_millis(300));
+
+	// A burst of edits, each well within the debounce
+	let burst = {
+		let dir = dir.clone();
+		tokio::spawn(async move {
+			for (path, contents) in [("lua/watch/sh_a.lua", "print(\"a2\")\n"), ("lua/watch/sh_b.lua", "print(\"b\")\n"), ("lua/autorun/watch.lua", "include(\"watch/sh_b.lua\")\n")] {
+				tokio::time::sleep(Duration::from_millis(20)).await;
+				write(&dir, &[(path, contents)]);
+			}
+		})
+	};
+	let burst_changes = watcher.changed().await;
+	burst.await.unwrap();
+
+	// Nothing changed since, so there's no second repack
+	let quiet = tokio::time::timeout(Duration::from_millis(200), watcher.changed()).await;
+
+	// Files outside lua/ aren't watched, the config file is
+	write(&dir, &[("materials/watch.png", "png2")]);
+	let config_write = {
+		let dir = dir.clone();
+		tokio::spawn(async move {
+			tokio::time::sleep(Duration::from_millis(50)).await;
+			write(&dir, &[("gluapack.json", "{ \"unique_id\": \"watch\" }")]);
+		})
+	};
+	let config_changes = watcher.changed().await;
+	config_write.await.unwrap();
+
+	std::fs::remove_dir_all(&dir).ok();
+
+	assert_eq!(burst_changes, 3);
+	assert!(quiet.is_err());
+	assert_eq!(config_changes, 1);
+}
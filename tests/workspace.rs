@@ -0,0 +1,88 @@
+use std::path::Path;
+
+use gluapack::{unpack::EntryFiles, Inspector, Packer, PackOptions, PackingError, Unpacker, UnpackOptions};
+
+mod common;
+use common::{out_dir, write};
+
+/// A workspace of two addons, each with its own gluapack.json.
+fn workspace(dir: &Path, extra: &[(&str, &str)]) {
+	write(dir, &[
+		("gluapack-workspace.json", r#"{ "addons": ["library", "gamemode"], "unique_id": "workspace" }"#),
+
+		("library/gluapack.json", r#"{ "include_sh": ["library/*.lua"], "entry_sh": ["autorun/library.lua"] }"#),
+		("library/lua/autorun/library.lua", "include(\"library/sh_core.lua\")\n"),
+		("library/lua/library/sh_core.lua", "Library = {}\n"),
+		("library/lua/other/unpacked.lua", "print(\"not packed\")\n"),
+		("library/materials/library.png", "png"),
+
+		("gamemode/gluapack.json", r#"{ "include_cl": ["gamemode/cl_*.lua"], "include_sv": ["gamemode/sv_*.lua"], "entry_sh": ["autorun/gamemode.lua"] }"#),
+		("gamemode/lua/autorun/gamemode.lua", "include(\"gamemode/sv_init.lua\")\n"),
+		("gamemode/lua/gamemode/cl_hud.lua", "print(\"hud\")\n"),
+		("gamemode/lua/gamemode/sv_init.lua", "print(\"init\")\n")
+	]);
+	write(dir, extra);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn packs_addons_together() {
+	let (dir, packed, unpacked) = (out_dir("workspace-src"), out_dir("workspace"), out_dir("workspace-unpacked"));
+	workspace(&dir, &[]);
+
+	let report = Packer::pack_workspace(dir.clone(), PackOptions::new().out_dir(&packed).quiet(true)).await;
+	let in_place = Packer::pack_workspace(dir.clone(), PackOptions::new().in_place(true).quiet(true)).await;
+
+	let loaders = std::fs::read_dir(packed.join("lua/autorun")).unwrap().map(|entry| entry.unwrap().path()).filter(|path| path.file_name().unwrap().to_string_lossy().contains("_gluapack_")).collect::<Vec<_>>();
+	let entry_files = EntryFiles::read_loader(&loaders[0]).unwrap();
+	let inspected = Inspector::inspect(packed.clone()).await.unwrap();
+	Unpacker::unpack(packed.clone(), UnpackOptions::new().out_dir(&unpacked).quiet(true)).await.unwrap();
+
+	let copied = (packed.join("materials/library.png").is_file(), packed.join("lua/other/unpacked.lua").is_file());
+	let unpacked_hud = std::fs::read_to_string(unpacked.join("lua/gamemode/cl_hud.lua")).ok();
+
+	for dir in [&dir, &packed, &unpacked] {
+		std::fs::remove_dir_all(dir).ok();
+	}
+
+	report.unwrap();
+	assert!(matches!(in_place, Err(PackingError::WorkspaceInPlace { .. })));
+
+	assert_eq!(loaders.len(), 1);
+	assert_eq!(entry_files.sh, vec!["autorun/library.lua".to_string(), "autorun/gamemode.lua".to_string()]);
+
+	let mut files = inspected.files.iter().map(|file| (file.path.as_str(), file.realm)).collect::<Vec<_>>();
+	files.sort();
+	assert_eq!(files, vec![
+		("autorun/gamemode.lua", "sh"),
+		("autorun/library.lua", "sh"),
+		("gamemode/cl_hud.lua", "cl"),
+		("gamemode/sv_init.lua", "sv"),
+		("library/sh_core.lua", "sh")
+	]);
+
+	assert_eq!(copied, (true, true));
+	assert_eq!(unpacked_hud.as_deref(), Some("print(\"hud\")\n"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn detects_path_collisions() {
+	let collisions = |result: Result<_, PackingError>| match result {
+		Err(PackingError::PathCollision { error, .. }) => error.into_iter().map(|collision| (collision.path, collision.addons)).collect::<Vec<_>>(),
+		result => panic!("expected a path collision, got {:?}", result.map(|_| ()))
+	};
+	let addons = vec!["library".to_string(), "gamemode".to_string()];
+
+	let (dir, packed) = (out_dir("workspace-lua-collision-src"), out_dir("workspace-lua-collision"));
+	workspace(&dir, &[("library/lua/gamemode/cl_hud.lua", "print(\"mine now\")\n")]);
+	let result = Packer::pack_workspace(dir.clone(), PackOptions::new().out_dir(&packed).quiet(true)).await;
+	std::fs::remove_dir_all(&dir).ok();
+	std::fs::remove_dir_all(&packed).ok();
+	assert_eq!(collisions(result), vec![("lua/gamemode/cl_hud.lua".to_string(), addons.clone())]);
+
+	let (dir, packed) = (out_dir("workspace-copy-collision-src"), out_dir("workspace-copy-collision"));
+	workspace(&dir, &[("gamemode/materials/library.png", "another png")]);
+	let result = Packer::pack_workspace(dir.clone(), PackOptions::new().out_dir(&packed).quiet(true)).await;
+	std::fs::remove_dir_all(&dir).ok();
+	std::fs::remove_dir_all(&packed).ok();
+	assert_eq!(collisions(result), vec![("materials/library.png".to_string(), addons)]);
+}